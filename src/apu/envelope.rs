@@ -0,0 +1,120 @@
+/// Volume envelope, shared by the square and noise channels
+#[derive(Debug, Clone, Default)]
+pub struct Envelope {
+    /// Envelope register (R/W).
+    /// ```text
+    /// 00000000
+    /// |||||```- Period, in envelope clocks. 0 stops the envelope.
+    /// ||||`---- Direction (0: down, 1: up)
+    /// ````----- Initial volume
+    /// ```
+    register: u8,
+    /// Current volume, 0-15
+    volume: u8,
+    /// Envelope clocks left until the volume changes
+    timer: u8,
+}
+
+impl Envelope {
+    pub fn read(&self) -> u8 {
+        self.register
+    }
+
+    pub fn write(&mut self, byte: u8) {
+        self.register = byte;
+    }
+
+    /// The DAC of a channel is on as long as the initial volume or direction
+    /// is non-zero
+    pub fn dac_enabled(&self) -> bool {
+        self.register & 0xF8 != 0
+    }
+
+    pub fn volume(&self) -> u8 {
+        self.volume
+    }
+
+    fn period(&self) -> u8 {
+        self.register & 0x07
+    }
+
+    /// Restart the envelope from the initial volume
+    pub fn trigger(&mut self) {
+        self.volume = self.register >> 4;
+        self.timer = self.period();
+    }
+
+    /// Clock the envelope, stepping the volume once the period has passed
+    pub fn clock(&mut self) {
+        if self.period() == 0 {
+            return;
+        }
+
+        self.timer = self.timer.saturating_sub(1);
+        if self.timer > 0 {
+            return;
+        }
+
+        self.timer = self.period();
+        if self.register & 0x08 != 0 {
+            self.volume = (self.volume + 1).min(15);
+        } else {
+            self.volume = self.volume.saturating_sub(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn volume_down() {
+        let mut envelope = Envelope::default();
+        envelope.write(0x32);
+        envelope.trigger();
+        assert_eq!(envelope.volume(), 3);
+
+        for volume in (0..3).rev() {
+            envelope.clock();
+            assert_eq!(envelope.volume(), volume + 1);
+            envelope.clock();
+            assert_eq!(envelope.volume(), volume);
+        }
+        envelope.clock();
+        envelope.clock();
+        assert_eq!(envelope.volume(), 0);
+    }
+
+    #[test]
+    fn volume_up() {
+        let mut envelope = Envelope::default();
+        envelope.write(0xE9);
+        envelope.trigger();
+        envelope.clock();
+        assert_eq!(envelope.volume(), 15);
+        envelope.clock();
+        assert_eq!(envelope.volume(), 15);
+    }
+
+    #[test]
+    fn period_zero_stops() {
+        let mut envelope = Envelope::default();
+        envelope.write(0x58);
+        envelope.trigger();
+        for _ in 0..16 {
+            envelope.clock();
+        }
+        assert_eq!(envelope.volume(), 5);
+    }
+
+    #[test]
+    fn dac() {
+        let mut envelope = Envelope::default();
+        assert!(!envelope.dac_enabled());
+        envelope.write(0x08);
+        assert!(envelope.dac_enabled());
+        envelope.write(0x07);
+        assert!(!envelope.dac_enabled());
+    }
+}
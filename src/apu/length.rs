@@ -0,0 +1,96 @@
+/// Length counter, turning a channel off after a set amount of frame
+/// sequencer length clocks
+#[derive(Debug, Clone)]
+pub struct LengthCounter {
+    /// Length of the channel when loaded with 0
+    max: u16,
+    /// Length clocks left until the channel is turned off
+    counter: u16,
+    /// Set if the counter is clocked
+    enabled: bool,
+}
+
+impl LengthCounter {
+    pub fn new(max: u16) -> Self {
+        Self {
+            max,
+            counter: 0,
+            enabled: false,
+        }
+    }
+
+    /// Load the length register. The channel plays for `max - length` clocks.
+    pub fn load(&mut self, length: u8) {
+        self.counter = self.max - length as u16;
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// Triggering a channel that has run out of length restarts it at the
+    /// full length
+    pub fn trigger(&mut self) {
+        if self.counter == 0 {
+            self.counter = self.max;
+        }
+    }
+
+    /// Clock the counter.
+    ///
+    /// # Returns
+    /// True if the counter ran out, meaning the channel should be turned off.
+    pub fn clock(&mut self) -> bool {
+        if !self.enabled || self.counter == 0 {
+            return false;
+        }
+        self.counter -= 1;
+        self.counter == 0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn runs_out() {
+        let mut length = LengthCounter::new(64);
+        length.load(61);
+        length.set_enabled(true);
+        assert!(!length.clock());
+        assert!(!length.clock());
+        assert!(length.clock());
+        assert!(!length.clock());
+    }
+
+    #[test]
+    fn disabled() {
+        let mut length = LengthCounter::new(64);
+        length.load(63);
+        assert!(!length.clock());
+        length.set_enabled(true);
+        assert!(length.clock());
+    }
+
+    #[test]
+    fn trigger_reloads_when_empty() {
+        let mut length = LengthCounter::new(256);
+        length.set_enabled(true);
+        length.trigger();
+        for _ in 0..255 {
+            assert!(!length.clock());
+        }
+        assert!(length.clock());
+
+        // A counter with length left keeps it
+        length.load(254);
+        length.trigger();
+        assert!(!length.clock());
+        assert!(length.clock());
+    }
+}
@@ -0,0 +1,547 @@
+use crate::memory::region::MemoryRegion;
+
+mod envelope;
+mod length;
+mod noise;
+mod square;
+mod wave;
+
+use noise::Noise;
+use square::Square;
+use wave::{Wave, WAVE_RAM_SIZE};
+
+/// Memory mapped location of the channel 1 sweep register.
+pub const NR10: u16 = 0xFF10;
+/// Memory mapped location of the channel 1 control register.
+pub const NR14: u16 = 0xFF14;
+/// Memory mapped location of the channel 2 control register.
+pub const NR24: u16 = 0xFF19;
+/// Memory mapped location of the channel 3 DAC enable register.
+pub const NR30: u16 = 0xFF1A;
+/// Memory mapped location of the channel 3 control register.
+pub const NR34: u16 = 0xFF1E;
+/// Memory mapped location of the channel 4 control register.
+pub const NR44: u16 = 0xFF23;
+/// Memory mapped location of the [`nr50`](#structfield.nr50) register.
+pub const NR50: u16 = 0xFF24;
+/// Memory mapped location of the [`nr51`](#structfield.nr51) register.
+pub const NR51: u16 = 0xFF25;
+/// Memory mapped location of the sound on/off register.
+pub const NR52: u16 = 0xFF26;
+pub const WAVE_RAM_START: u16 = 0xFF30;
+pub const WAVE_RAM_END: u16 = 0xFF3F;
+
+/// T-cycles per second
+pub const CLOCK_RATE: u32 = 4_194_304;
+
+/// Bits that always read as 1 for NR10-0xFF2F. Unused registers read as 0xFF.
+const READ_MASKS: [u8; 0x20] = [
+    0x80, 0x3F, 0x00, 0xFF, 0xBF, // NR10-NR14
+    0xFF, 0x3F, 0x00, 0xFF, 0xBF, // NR20-NR24
+    0x7F, 0xFF, 0x9F, 0xFF, 0xBF, // NR30-NR34
+    0xFF, 0xFF, 0x00, 0x00, 0xBF, // NR40-NR44
+    0x00, 0x00, 0x70, // NR50-NR52
+    0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+];
+
+/// Bit of the DIV register whose falling edge clocks the frame sequencer
+const FRAME_SEQUENCER_DIV_BIT: u8 = 1 << 4;
+
+/// How much of its charge the high-pass filter capacitor keeps every T-cycle
+const CAPACITOR_CHARGE: f32 = 0.999958;
+
+/// A stereo sample, left and right, each from -1.0 to 1.0
+pub type Sample = [f32; 2];
+
+/// One sample of each channel on its own, from -1.0 to 1.0
+pub type ChannelSample = [f32; 4];
+
+/// High-pass filter, standing in for the capacitor that blocks the DC offset
+/// of the DACs
+#[derive(Debug, Clone, Copy, Default)]
+struct HighPass {
+    capacitor: f32,
+}
+
+impl HighPass {
+    /// Filter `input`, where `charge` is how much of its charge the capacitor
+    /// keeps between samples
+    fn filter(&mut self, input: f32, charge: f32) -> f32 {
+        let output = input - self.capacitor;
+        self.capacitor = input - output * charge;
+        output
+    }
+}
+
+/// The audio processing unit. Owns the sound registers and wave RAM, runs the
+/// four sound channels, and mixes them into stereo samples on request.
+#[derive(Debug, Clone)]
+pub struct Apu {
+    square1: Square,
+    square2: Square,
+    wave: Wave,
+    noise: Noise,
+    /// Master volume (R/W).
+    /// ```text
+    /// 00000000
+    ///  ||| ```- Right volume
+    ///  ```----- Left volume
+    /// ```
+    nr50: u8,
+    /// Panning (R/W). Bits 0-3 send channels 1-4 to the right, bits 4-7 send
+    /// them to the left.
+    nr51: u8,
+    /// Set while the APU is powered on, NR52 bit 7
+    powered: bool,
+    /// Internal use. Step of the frame sequencer, 0-7.
+    frame_step: u8,
+    /// Internal use. Last seen state of the DIV bit clocking the frame
+    /// sequencer.
+    div_bit: bool,
+    /// Internal use. Samples per second to collect, or [`None`] if samples
+    /// aren't collected.
+    sample_rate: Option<u32>,
+    /// Internal use. Advances by the sample rate every T-cycle, and a sample
+    /// is taken every time it passes [`CLOCK_RATE`].
+    sample_clock: u64,
+    /// Internal use. High-pass filters of the left and right output.
+    filters: [HighPass; 2],
+    /// Internal use. High-pass filters of each channel, for channel samples.
+    channel_filters: [HighPass; 4],
+    /// Internal use. How much of its charge a capacitor keeps every sample.
+    capacitor_charge: f32,
+    /// Samples collected since last taken
+    samples: Vec<Sample>,
+    /// Samples of each channel collected since last taken, or [`None`] if
+    /// they aren't collected
+    channel_samples: Option<Vec<ChannelSample>>,
+}
+
+impl Default for Apu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Apu {
+    pub fn new() -> Self {
+        Self {
+            square1: Square::with_sweep(),
+            square2: Square::new(),
+            wave: Wave::new(),
+            noise: Noise::new(),
+            nr50: 0,
+            nr51: 0,
+            powered: true,
+            frame_step: 0,
+            div_bit: false,
+            sample_rate: None,
+            sample_clock: 0,
+            filters: [HighPass::default(); 2],
+            channel_filters: [HighPass::default(); 4],
+            capacitor_charge: 0.0,
+            samples: Vec::new(),
+            channel_samples: None,
+        }
+    }
+
+    /// Start collecting samples at `sample_rate` samples per second, or stop
+    /// collecting with [`None`]
+    pub fn set_sample_rate(&mut self, sample_rate: Option<u32>) {
+        self.sample_rate = sample_rate;
+        self.sample_clock = 0;
+        self.samples.clear();
+        if let Some(rate) = sample_rate {
+            self.capacitor_charge = CAPACITOR_CHARGE.powf(CLOCK_RATE as f32 / rate as f32);
+        }
+    }
+
+    /// Also collect a sample of each channel on its own every time a sample
+    /// is collected
+    pub fn set_channel_samples(&mut self, enabled: bool) {
+        self.channel_samples = enabled.then(Vec::new);
+    }
+
+    /// Take the channel samples collected since the last call
+    pub fn take_channel_samples(&mut self) -> Vec<ChannelSample> {
+        self.channel_samples
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// Number of samples collected since last taken
+    pub fn sample_count(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Take the samples collected since the last call
+    pub fn take_samples(&mut self) -> Vec<Sample> {
+        std::mem::take(&mut self.samples)
+    }
+
+    /// Run the APU for a number of machine cycles.
+    ///
+    /// # Arguments
+    /// * `machine_cycles` - The amount of machine cycles that have ticked since
+    ///   last invocation.
+    /// * `div` - The current value of the DIV register. The frame sequencer
+    ///   is clocked at 512 Hz by the falling edge of its bit 4.
+    pub fn tick(&mut self, machine_cycles: usize, div: u8) {
+        let div_bit = div & FRAME_SEQUENCER_DIV_BIT != 0;
+        if self.div_bit && !div_bit && self.powered {
+            self.clock_frame_sequencer();
+        }
+        self.div_bit = div_bit;
+
+        let mut cycles = machine_cycles as u64 * 4;
+        let Some(rate) = self.sample_rate.map(u64::from) else {
+            self.tick_channels(cycles as u32);
+            return;
+        };
+
+        // Run up to each sample point in turn
+        while cycles > 0 {
+            let until_sample = (CLOCK_RATE as u64 - self.sample_clock).div_ceil(rate);
+            let step = until_sample.min(cycles);
+            self.tick_channels(step as u32);
+            cycles -= step;
+
+            self.sample_clock += step * rate;
+            if self.sample_clock >= CLOCK_RATE as u64 {
+                self.sample_clock -= CLOCK_RATE as u64;
+                let sample = self.mix();
+                self.samples.push(sample);
+            }
+        }
+    }
+
+    fn tick_channels(&mut self, cycles: u32) {
+        self.square1.tick(cycles);
+        self.square2.tick(cycles);
+        self.wave.tick(cycles);
+        self.noise.tick(cycles);
+    }
+
+    /// Step the frame sequencer, clocking the length counters at 256 Hz, the
+    /// sweep at 128 Hz and the volume envelopes at 64 Hz
+    fn clock_frame_sequencer(&mut self) {
+        if self.frame_step.is_multiple_of(2) {
+            self.square1.clock_length();
+            self.square2.clock_length();
+            self.wave.clock_length();
+            self.noise.clock_length();
+        }
+        if self.frame_step == 2 || self.frame_step == 6 {
+            self.square1.clock_sweep();
+        }
+        if self.frame_step == 7 {
+            self.square1.clock_envelope();
+            self.square2.clock_envelope();
+            self.noise.clock_envelope();
+        }
+        self.frame_step = (self.frame_step + 1) % 8;
+    }
+
+    /// Output of each channel after its DAC, from -1.0 to 1.0. A channel with
+    /// its DAC off outputs 0.
+    fn channel_outputs(&self) -> [f32; 4] {
+        let dac = |enabled: bool, output: u8| {
+            if enabled {
+                output as f32 / 7.5 - 1.0
+            } else {
+                0.0
+            }
+        };
+        [
+            dac(self.square1.dac_enabled(), self.square1.output()),
+            dac(self.square2.dac_enabled(), self.square2.output()),
+            dac(self.wave.dac_enabled(), self.wave.output()),
+            dac(self.noise.dac_enabled(), self.noise.output()),
+        ]
+    }
+
+    /// Mix the channels into a stereo sample according to the panning and
+    /// master volume, and run it through a high-pass filter to remove the DC
+    /// offset of the DACs
+    fn mix(&mut self) -> Sample {
+        let outputs = self.channel_outputs();
+        let side = |shift: u8| {
+            let sum: f32 = (0..4)
+                .filter(|channel| self.nr51 & (1 << (channel + shift)) != 0)
+                .map(|channel| outputs[channel as usize])
+                .sum();
+            let volume = ((self.nr50 >> shift) & 0x07) + 1;
+            sum / 4.0 * volume as f32 / 8.0
+        };
+        let (left, right) = (side(4), side(0));
+
+        let charge = self.capacitor_charge;
+        if let Some(channel_samples) = &mut self.channel_samples {
+            let filters = &mut self.channel_filters;
+            channel_samples.push(std::array::from_fn(|channel| {
+                filters[channel].filter(outputs[channel], charge)
+            }));
+        }
+
+        let [left_filter, right_filter] = &mut self.filters;
+        [
+            left_filter.filter(left, charge),
+            right_filter.filter(right, charge),
+        ]
+    }
+
+    /// Turn the APU off, clearing all registers except wave RAM
+    fn power_off(&mut self) {
+        self.square1 = Square::with_sweep();
+        self.square2 = Square::new();
+        self.wave.power_off();
+        self.noise = Noise::new();
+        self.nr50 = 0;
+        self.nr51 = 0;
+        self.powered = false;
+    }
+
+    fn read_nr52(&self) -> u8 {
+        ((self.powered as u8) << 7)
+            | ((self.noise.enabled() as u8) << 3)
+            | ((self.wave.enabled() as u8) << 2)
+            | ((self.square2.enabled() as u8) << 1)
+            | self.square1.enabled() as u8
+    }
+
+    fn write_nr52(&mut self, byte: u8) {
+        let powered = byte & 0x80 != 0;
+        if self.powered && !powered {
+            self.power_off();
+        } else if !self.powered && powered {
+            self.powered = true;
+            self.frame_step = 0;
+        }
+    }
+}
+
+impl MemoryRegion for Apu {
+    fn read_byte(&self, addr: u16) -> u8 {
+        let value = match addr {
+            NR10..=NR14 => self.square1.read(addr - NR10),
+            0xFF15..=NR24 => self.square2.read(addr - 0xFF15),
+            NR30..=NR34 => self.wave.read(addr - NR30),
+            0xFF1F..=NR44 => self.noise.read(addr - 0xFF1F),
+            NR50 => self.nr50,
+            NR51 => self.nr51,
+            NR52 => self.read_nr52(),
+            0xFF27..=0xFF2F => 0,
+            WAVE_RAM_START..=WAVE_RAM_END => {
+                return self.wave.read_ram((addr - WAVE_RAM_START) as usize);
+            }
+            _ => panic!("Invalid sound register address: {:x}", addr),
+        };
+        value | READ_MASKS[(addr - NR10) as usize]
+    }
+
+    /// Write a byte to a sound register. While the APU is off, only NR52 and
+    /// wave RAM can be written.
+    fn write_byte(&mut self, byte: u8, addr: u16) {
+        match addr {
+            NR52 => self.write_nr52(byte),
+            WAVE_RAM_START..=WAVE_RAM_END => {
+                self.wave
+                    .write_ram(byte, (addr - WAVE_RAM_START) as usize % WAVE_RAM_SIZE);
+            }
+            _ if !self.powered => {}
+            NR10..=NR14 => self.square1.write(byte, addr - NR10),
+            0xFF15..=NR24 => self.square2.write(byte, addr - 0xFF15),
+            NR30..=NR34 => self.wave.write(byte, addr - NR30),
+            0xFF1F..=NR44 => self.noise.write(byte, addr - 0xFF1F),
+            NR50 => self.nr50 = byte,
+            NR51 => self.nr51 = byte,
+            0xFF27..=0xFF2F => {}
+            _ => panic!("Invalid sound register address: {:x}", addr),
+        }
+    }
+
+    fn read_word(&self, addr: u16) -> u16 {
+        let lo = self.read_byte(addr);
+        let hi = self.read_byte(addr + 1);
+        u16::from_le_bytes([lo, hi])
+    }
+
+    fn write_word(&mut self, word: u16, addr: u16) {
+        let [lo, hi] = word.to_le_bytes();
+        self.write_byte(lo, addr);
+        self.write_byte(hi, addr + 1);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// DIV value that ticks the frame sequencer when followed by 0
+    const DIV_HIGH: u8 = FRAME_SEQUENCER_DIV_BIT;
+
+    /// Clock the frame sequencer `steps` times through DIV
+    fn clock_frame_sequencer(apu: &mut Apu, steps: usize) {
+        for _ in 0..steps {
+            apu.tick(0, DIV_HIGH);
+            apu.tick(0, 0);
+        }
+    }
+
+    /// Trigger channel 1 with full volume and a 50% duty cycle
+    fn play_square1(apu: &mut Apu) {
+        apu.write_byte(0x80, 0xFF11); // NR11
+        apu.write_byte(0xF0, 0xFF12); // NR12
+        apu.write_byte(0x00, 0xFF13); // NR13
+        apu.write_byte(0x87, NR14);
+    }
+
+    #[test]
+    fn read_masks() {
+        let apu = Apu::new();
+        assert_eq!(apu.read_byte(NR10), 0x80);
+        assert_eq!(apu.read_byte(0xFF13), 0xFF); // NR13
+        assert_eq!(apu.read_byte(NR30), 0x7F);
+        assert_eq!(apu.read_byte(NR52), 0xF0);
+        assert_eq!(apu.read_byte(0xFF27), 0xFF);
+    }
+
+    #[test]
+    fn channel_status() {
+        let mut apu = Apu::new();
+        play_square1(&mut apu);
+        assert_eq!(apu.read_byte(NR52), 0xF1);
+
+        apu.write_byte(0xF0, 0xFF21); // NR42
+        apu.write_byte(0x80, NR44);
+        assert_eq!(apu.read_byte(NR52), 0xF9);
+    }
+
+    #[test]
+    fn length_clocked_by_frame_sequencer() {
+        let mut apu = Apu::new();
+        play_square1(&mut apu);
+        apu.write_byte(0xBE, 0xFF11); // NR11, Length 2
+        apu.write_byte(0x40, NR14);
+
+        clock_frame_sequencer(&mut apu, 2);
+        assert_eq!(apu.read_byte(NR52) & 0x01, 0x01);
+        clock_frame_sequencer(&mut apu, 1);
+        assert_eq!(apu.read_byte(NR52) & 0x01, 0x00);
+    }
+
+    #[test]
+    fn frame_sequencer_on_falling_edge() {
+        let mut apu = Apu::new();
+        play_square1(&mut apu);
+        apu.write_byte(0xBF, 0xFF11); // NR11, Length 1
+        apu.write_byte(0x40, NR14);
+
+        // Rising edges and staying high don't clock it
+        apu.tick(0, 0x10);
+        apu.tick(0, 0x1F);
+        assert_eq!(apu.read_byte(NR52) & 0x01, 0x01);
+
+        apu.tick(0, 0x20);
+        assert_eq!(apu.read_byte(NR52) & 0x01, 0x00);
+    }
+
+    #[test]
+    fn power_off() {
+        let mut apu = Apu::new();
+        play_square1(&mut apu);
+        apu.write_byte(0x77, NR50);
+        apu.write_byte(0xAB, WAVE_RAM_START);
+
+        apu.write_byte(0x00, NR52);
+        assert_eq!(apu.read_byte(NR52), 0x70);
+        assert_eq!(apu.read_byte(0xFF12), 0x00); // NR12
+        assert_eq!(apu.read_byte(NR50), 0x00);
+
+        // Registers can't be written while off, but wave RAM can
+        apu.write_byte(0x77, NR50);
+        assert_eq!(apu.read_byte(NR50), 0x00);
+        apu.write_byte(0xCD, WAVE_RAM_START + 1);
+        assert_eq!(apu.read_byte(WAVE_RAM_START), 0xAB);
+        assert_eq!(apu.read_byte(WAVE_RAM_START + 1), 0xCD);
+
+        apu.write_byte(0x80, NR52);
+        apu.write_byte(0x77, NR50);
+        assert_eq!(apu.read_byte(NR50), 0x77);
+    }
+
+    #[test]
+    fn no_samples_unless_requested() {
+        let mut apu = Apu::new();
+        apu.tick(1000, 0);
+        assert!(apu.take_samples().is_empty());
+    }
+
+    #[test]
+    fn sample_rate() {
+        let mut apu = Apu::new();
+        apu.set_sample_rate(Some(32768));
+        apu.tick(CLOCK_RATE as usize / 4, 0);
+        assert_eq!(apu.take_samples().len(), 32768);
+
+        apu.set_sample_rate(Some(44100));
+        for _ in 0..CLOCK_RATE / 4 / 16 {
+            apu.tick(16, 0);
+        }
+        assert_eq!(apu.take_samples().len(), 44100);
+    }
+
+    #[test]
+    fn panning() {
+        let mut apu = Apu::new();
+        apu.set_sample_rate(Some(CLOCK_RATE / 64));
+        apu.write_byte(0x77, NR50);
+        apu.write_byte(0x10, NR51); // Channel 1 left only
+        play_square1(&mut apu);
+        apu.tick(4096, 0);
+
+        let samples = apu.take_samples();
+        assert!(samples.iter().any(|[left, _]| *left > 0.1));
+        assert!(samples.iter().any(|[left, _]| *left < -0.1));
+        assert!(samples.iter().all(|[_, right]| *right == 0.0));
+    }
+
+    #[test]
+    fn master_volume() {
+        let peak = |nr50| {
+            let mut apu = Apu::new();
+            apu.set_sample_rate(Some(CLOCK_RATE / 64));
+            apu.write_byte(nr50, NR50);
+            apu.write_byte(0x11, NR51);
+            play_square1(&mut apu);
+            apu.tick(4096, 0);
+            apu.take_samples()
+                .iter()
+                .map(|[left, right]| (left.abs(), right.abs()))
+                .fold((0.0f32, 0.0f32), |(l, r), (left, right)| {
+                    (l.max(left), r.max(right))
+                })
+        };
+
+        let (left, right) = peak(0x70);
+        assert!((left / right - 8.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn channel_samples() {
+        let mut apu = Apu::new();
+        apu.set_sample_rate(Some(CLOCK_RATE / 64));
+        play_square1(&mut apu);
+        apu.tick(4096, 0);
+        assert!(apu.take_channel_samples().is_empty());
+        apu.take_samples();
+
+        apu.set_channel_samples(true);
+        apu.tick(4096, 0);
+        let samples = apu.take_channel_samples();
+        assert_eq!(samples.len(), apu.take_samples().len());
+        assert!(samples.iter().any(|sample| sample[0] > 0.1));
+        assert!(samples.iter().all(|sample| sample[1..] == [0.0; 3]));
+    }
+}
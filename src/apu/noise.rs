@@ -0,0 +1,192 @@
+use super::envelope::Envelope;
+use super::length::LengthCounter;
+
+/// Base divisors for the noise frequency, in T-cycles
+const DIVISORS: [u32; 8] = [8, 16, 32, 48, 64, 80, 96, 112];
+
+/// Noise channel, channel 4. Outputs the low bit of a linear feedback shift
+/// register, inverted.
+///
+/// Registers, relative to NR40:
+/// ```text
+/// 0: Unused
+/// 1: Length (bits 0-5)
+/// 2: Volume envelope
+/// 3: Clock shift (bits 4-7), LFSR width (bit 3, 1: 7 bits) and divisor
+///    (bits 0-2)
+/// 4: Trigger (bit 7) and length enable (bit 6)
+/// ```
+#[derive(Debug, Clone)]
+pub struct Noise {
+    length: LengthCounter,
+    envelope: Envelope,
+    /// Frequency and LFSR width register, NR43
+    polynomial: u8,
+    /// 15-bit linear feedback shift register
+    lfsr: u16,
+    /// T-cycles left until the LFSR is shifted
+    timer: u32,
+    /// Set while the channel is playing
+    enabled: bool,
+}
+
+impl Noise {
+    pub fn new() -> Self {
+        Self {
+            length: LengthCounter::new(64),
+            envelope: Envelope::default(),
+            polynomial: 0,
+            lfsr: 0,
+            timer: 0,
+            enabled: false,
+        }
+    }
+
+    fn period(&self) -> u32 {
+        DIVISORS[self.polynomial as usize & 0x07] << (self.polynomial >> 4)
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn dac_enabled(&self) -> bool {
+        self.envelope.dac_enabled()
+    }
+
+    /// Current output level, 0-15
+    pub fn output(&self) -> u8 {
+        if !self.enabled || self.lfsr & 1 != 0 {
+            return 0;
+        }
+        self.envelope.volume()
+    }
+
+    /// Run the channel for a number of T-cycles
+    pub fn tick(&mut self, cycles: u32) {
+        let mut cycles = cycles;
+        while cycles >= self.timer {
+            cycles -= self.timer;
+            self.timer = self.period();
+            self.shift();
+        }
+        self.timer -= cycles;
+    }
+
+    /// Shift the LFSR, feeding back the XOR of the two low bits into bit 14,
+    /// and also bit 6 in 7-bit mode
+    fn shift(&mut self) {
+        let feedback = (self.lfsr ^ (self.lfsr >> 1)) & 1;
+        self.lfsr = (self.lfsr >> 1) | (feedback << 14);
+        if self.polynomial & 0x08 != 0 {
+            self.lfsr = (self.lfsr & !(1 << 6)) | (feedback << 6);
+        }
+    }
+
+    pub fn clock_length(&mut self) {
+        if self.length.clock() {
+            self.enabled = false;
+        }
+    }
+
+    pub fn clock_envelope(&mut self) {
+        self.envelope.clock();
+    }
+
+    /// Restart the channel with a fresh LFSR
+    fn trigger(&mut self) {
+        self.enabled = self.dac_enabled();
+        self.length.trigger();
+        self.timer = self.period();
+        self.envelope.trigger();
+        self.lfsr = 0x7FFF;
+    }
+
+    /// Read the readable bits of `register`. Write-only bits read as 0.
+    pub fn read(&self, register: u16) -> u8 {
+        match register {
+            0 | 1 => 0,
+            2 => self.envelope.read(),
+            3 => self.polynomial,
+            4 => (self.length.enabled() as u8) << 6,
+            _ => panic!("Invalid noise channel register: {}", register),
+        }
+    }
+
+    pub fn write(&mut self, byte: u8, register: u16) {
+        match register {
+            0 => {}
+            1 => self.length.load(byte & 0x3F),
+            2 => {
+                self.envelope.write(byte);
+                if !self.dac_enabled() {
+                    self.enabled = false;
+                }
+            }
+            3 => self.polynomial = byte,
+            4 => {
+                self.length.set_enabled(byte & 0x40 != 0);
+                if byte & 0x80 != 0 {
+                    self.trigger();
+                }
+            }
+            _ => panic!("Invalid noise channel register: {}", register),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn play(noise: &mut Noise, polynomial: u8) {
+        noise.write(0xF0, 2);
+        noise.write(polynomial, 3);
+        noise.write(0x80, 4);
+    }
+
+    #[test]
+    fn lfsr_15_bit() {
+        let mut noise = Noise::new();
+        play(&mut noise, 0x00);
+        assert_eq!(noise.output(), 0);
+
+        // All ones shift in zeroes from the top first
+        noise.tick(8);
+        assert_eq!(noise.lfsr, 0x3FFF);
+        for _ in 0..13 {
+            noise.tick(8);
+        }
+        assert_eq!(noise.lfsr, 0x0001);
+        noise.tick(8);
+        assert_eq!(noise.lfsr, 0x4000);
+        assert_eq!(noise.output(), 15);
+    }
+
+    #[test]
+    fn lfsr_7_bit() {
+        let mut noise = Noise::new();
+        play(&mut noise, 0x08);
+        noise.tick(8);
+        assert_eq!(noise.lfsr, 0x3FBF);
+    }
+
+    #[test]
+    fn period() {
+        let mut noise = Noise::new();
+        play(&mut noise, 0x25); // Divisor 80, shift 2
+        noise.tick(319);
+        assert_eq!(noise.lfsr, 0x7FFF);
+        noise.tick(1);
+        assert_eq!(noise.lfsr, 0x3FFF);
+    }
+
+    #[test]
+    fn dac_off_disables() {
+        let mut noise = Noise::new();
+        play(&mut noise, 0x00);
+        assert!(noise.enabled());
+        noise.write(0x07, 2);
+        assert!(!noise.enabled());
+    }
+}
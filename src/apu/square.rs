@@ -0,0 +1,360 @@
+use super::envelope::Envelope;
+use super::length::LengthCounter;
+
+/// Waveforms for the four duty cycles, one bit per step
+const DUTY_CYCLES: [u8; 4] = [0b0000_0001, 0b1000_0001, 0b1000_0111, 0b0111_1110];
+
+/// Highest frequency value, above which the sweep turns the channel off
+const MAX_FREQUENCY: u16 = 2047;
+
+/// Frequency sweep of channel 1
+#[derive(Debug, Clone, Default)]
+struct Sweep {
+    /// Sweep register (R/W).
+    /// ```text
+    /// x0000000
+    ///  ||||```- Shift
+    ///  |||`---- Direction (0: up, 1: down)
+    ///  ```----- Period, in sweep clocks. 0 stops the sweep.
+    /// ```
+    register: u8,
+    /// Set if the sweep is running
+    enabled: bool,
+    /// Copy of the frequency that the sweep works on
+    shadow: u16,
+    /// Sweep clocks left until the frequency changes
+    timer: u8,
+}
+
+impl Sweep {
+    fn period(&self) -> u8 {
+        (self.register >> 4) & 0x07
+    }
+
+    fn shift(&self) -> u8 {
+        self.register & 0x07
+    }
+
+    /// Reload the timer. A period of 0 is treated as 8.
+    fn reload(&mut self) {
+        self.timer = match self.period() {
+            0 => 8,
+            period => period,
+        };
+    }
+
+    /// The next frequency of the sweep
+    fn next_frequency(&self) -> u16 {
+        let delta = self.shadow >> self.shift();
+        if self.register & 0x08 != 0 {
+            self.shadow - delta
+        } else {
+            self.shadow + delta
+        }
+    }
+}
+
+/// Square wave channel, used for channel 1 (with a frequency sweep) and
+/// channel 2.
+///
+/// Registers, relative to NR10 or NR20:
+/// ```text
+/// 0: Sweep, channel 1 only
+/// 1: Duty cycle (bits 6-7) and length (bits 0-5)
+/// 2: Volume envelope
+/// 3: Frequency, lower 8 bits
+/// 4: Trigger (bit 7), length enable (bit 6) and frequency, upper 3 bits
+/// ```
+#[derive(Debug, Clone)]
+pub struct Square {
+    sweep: Option<Sweep>,
+    /// Duty cycle, index into [`DUTY_CYCLES`]
+    duty: u8,
+    length: LengthCounter,
+    envelope: Envelope,
+    /// 11-bit frequency value. The waveform steps every `2048 - frequency`
+    /// APU cycles.
+    frequency: u16,
+    /// T-cycles left until the next step of the waveform
+    timer: u32,
+    /// Current step of the waveform, 0-7
+    position: u8,
+    /// Set while the channel is playing
+    enabled: bool,
+}
+
+impl Square {
+    pub fn new() -> Self {
+        Self {
+            sweep: None,
+            duty: 0,
+            length: LengthCounter::new(64),
+            envelope: Envelope::default(),
+            frequency: 0,
+            timer: 0,
+            position: 0,
+            enabled: false,
+        }
+    }
+
+    /// Create a channel with a frequency sweep unit, like channel 1
+    pub fn with_sweep() -> Self {
+        Self {
+            sweep: Some(Sweep::default()),
+            ..Self::new()
+        }
+    }
+
+    fn period(&self) -> u32 {
+        (2048 - self.frequency as u32) * 4
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn dac_enabled(&self) -> bool {
+        self.envelope.dac_enabled()
+    }
+
+    /// Current output level, 0-15
+    pub fn output(&self) -> u8 {
+        if !self.enabled {
+            return 0;
+        }
+        let high = (DUTY_CYCLES[self.duty as usize] >> (7 - self.position)) & 1;
+        high * self.envelope.volume()
+    }
+
+    /// Run the channel for a number of T-cycles
+    pub fn tick(&mut self, cycles: u32) {
+        let mut cycles = cycles;
+        while cycles >= self.timer {
+            cycles -= self.timer;
+            self.timer = self.period();
+            self.position = (self.position + 1) % 8;
+        }
+        self.timer -= cycles;
+    }
+
+    pub fn clock_length(&mut self) {
+        if self.length.clock() {
+            self.enabled = false;
+        }
+    }
+
+    pub fn clock_envelope(&mut self) {
+        self.envelope.clock();
+    }
+
+    pub fn clock_sweep(&mut self) {
+        let Some(sweep) = &mut self.sweep else {
+            return;
+        };
+
+        sweep.timer = sweep.timer.saturating_sub(1);
+        if sweep.timer > 0 {
+            return;
+        }
+        sweep.reload();
+        if !sweep.enabled || sweep.period() == 0 {
+            return;
+        }
+
+        let frequency = sweep.next_frequency();
+        if frequency > MAX_FREQUENCY {
+            self.enabled = false;
+            return;
+        }
+        if sweep.shift() != 0 {
+            sweep.shadow = frequency;
+            self.frequency = frequency;
+
+            // The new frequency is checked for overflow right away as well
+            if sweep.next_frequency() > MAX_FREQUENCY {
+                self.enabled = false;
+            }
+        }
+    }
+
+    /// Restart the channel
+    fn trigger(&mut self) {
+        self.enabled = self.dac_enabled();
+        self.length.trigger();
+        self.timer = self.period();
+        self.envelope.trigger();
+
+        if let Some(sweep) = &mut self.sweep {
+            sweep.shadow = self.frequency;
+            sweep.reload();
+            sweep.enabled = sweep.period() != 0 || sweep.shift() != 0;
+            if sweep.shift() != 0 && sweep.next_frequency() > MAX_FREQUENCY {
+                self.enabled = false;
+            }
+        }
+    }
+
+    /// Read the readable bits of `register`. Write-only bits read as 0.
+    pub fn read(&self, register: u16) -> u8 {
+        match register {
+            0 => self.sweep.as_ref().map_or(0, |sweep| sweep.register),
+            1 => self.duty << 6,
+            2 => self.envelope.read(),
+            3 => 0,
+            4 => (self.length.enabled() as u8) << 6,
+            _ => panic!("Invalid square channel register: {}", register),
+        }
+    }
+
+    pub fn write(&mut self, byte: u8, register: u16) {
+        match register {
+            0 => {
+                if let Some(sweep) = &mut self.sweep {
+                    sweep.register = byte & 0x7F;
+                }
+            }
+            1 => {
+                self.duty = byte >> 6;
+                self.length.load(byte & 0x3F);
+            }
+            2 => {
+                self.envelope.write(byte);
+                if !self.dac_enabled() {
+                    self.enabled = false;
+                }
+            }
+            3 => self.frequency = (self.frequency & 0x700) | byte as u16,
+            4 => {
+                self.frequency = (self.frequency & 0xFF) | ((byte as u16 & 0x07) << 8);
+                self.length.set_enabled(byte & 0x40 != 0);
+                if byte & 0x80 != 0 {
+                    self.trigger();
+                }
+            }
+            _ => panic!("Invalid square channel register: {}", register),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Trigger `square` with full volume, duty cycle `duty` and frequency
+    /// `frequency`
+    fn play(square: &mut Square, duty: u8, frequency: u16) {
+        square.write(duty << 6, 1);
+        square.write(0xF0, 2);
+        square.write(frequency as u8, 3);
+        square.write(0x80 | (frequency >> 8) as u8, 4);
+    }
+
+    /// The waveform over one period, sampled once per step
+    fn waveform(square: &mut Square) -> Vec<u8> {
+        let step = square.period();
+        (0..8)
+            .map(|_| {
+                square.tick(step);
+                square.output()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn duty_cycles() {
+        let mut square = Square::new();
+        play(&mut square, 0, 0x700);
+        assert_eq!(waveform(&mut square), [0, 0, 0, 0, 0, 0, 15, 0]);
+        play(&mut square, 2, 0x700);
+        assert_eq!(waveform(&mut square), [0, 0, 0, 0, 15, 15, 15, 15]);
+    }
+
+    #[test]
+    fn frequency() {
+        let mut square = Square::new();
+        play(&mut square, 2, 2047);
+        assert_eq!(square.position, 0);
+        square.tick(3);
+        assert_eq!(square.position, 0);
+        square.tick(1);
+        assert_eq!(square.position, 1);
+        square.tick(4 * 7);
+        assert_eq!(square.position, 0);
+    }
+
+    #[test]
+    fn dac_off_disables() {
+        let mut square = Square::new();
+        play(&mut square, 2, 0x700);
+        assert!(square.enabled());
+        square.write(0x00, 2);
+        assert!(!square.enabled());
+
+        // Triggering doesn't help with the DAC off
+        square.write(0x80, 4);
+        assert!(!square.enabled());
+    }
+
+    #[test]
+    fn length() {
+        let mut square = Square::new();
+        play(&mut square, 2, 0x700);
+        square.write(62, 1);
+        square.write(0x40, 4);
+        square.clock_length();
+        assert!(square.enabled());
+        square.clock_length();
+        assert!(!square.enabled());
+    }
+
+    #[test]
+    fn sweep_up() {
+        let mut square = Square::with_sweep();
+        square.write(0x11, 0); // Period 1, shift 1
+        play(&mut square, 2, 0x100);
+        square.clock_sweep();
+        assert_eq!(square.frequency, 0x180);
+        square.clock_sweep();
+        assert_eq!(square.frequency, 0x240);
+    }
+
+    #[test]
+    fn sweep_down() {
+        let mut square = Square::with_sweep();
+        square.write(0x29, 0); // Period 2, down, shift 1
+        play(&mut square, 2, 0x400);
+        square.clock_sweep();
+        assert_eq!(square.frequency, 0x400);
+        square.clock_sweep();
+        assert_eq!(square.frequency, 0x200);
+    }
+
+    #[test]
+    fn sweep_overflow() {
+        let mut square = Square::with_sweep();
+        square.write(0x11, 0);
+        play(&mut square, 2, 0x500);
+        assert!(square.enabled());
+
+        // 0x500 -> 0x780, which would overflow on the next step
+        square.clock_sweep();
+        assert_eq!(square.frequency, 0x780);
+        assert!(!square.enabled());
+
+        // Overflow is also checked on trigger
+        let mut square = Square::with_sweep();
+        square.write(0x01, 0);
+        play(&mut square, 2, 0x7F0);
+        assert!(!square.enabled());
+    }
+
+    #[test]
+    fn no_sweep_on_channel_2() {
+        let mut square = Square::new();
+        square.write(0x11, 0);
+        play(&mut square, 2, 0x100);
+        square.clock_sweep();
+        assert_eq!(square.frequency, 0x100);
+        assert_eq!(square.read(0), 0);
+    }
+}
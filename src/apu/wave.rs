@@ -0,0 +1,216 @@
+use super::length::LengthCounter;
+
+/// Bytes of wave RAM, holding 32 4-bit samples
+pub const WAVE_RAM_SIZE: usize = 16;
+
+/// Wave channel, channel 3. Plays back the 32 samples in wave RAM, high
+/// nybble first.
+///
+/// Registers, relative to NR30:
+/// ```text
+/// 0: DAC enable (bit 7)
+/// 1: Length
+/// 2: Output level (bits 5-6). 0: mute, 1: 100%, 2: 50%, 3: 25%
+/// 3: Frequency, lower 8 bits
+/// 4: Trigger (bit 7), length enable (bit 6) and frequency, upper 3 bits
+/// ```
+#[derive(Debug, Clone)]
+pub struct Wave {
+    /// Set if the DAC is on
+    dac: bool,
+    length: LengthCounter,
+    /// Output level, bits 5-6 of NR32
+    level: u8,
+    /// 11-bit frequency value. The channel steps to the next sample every
+    /// `2048 - frequency` APU cycles.
+    frequency: u16,
+    /// T-cycles left until the next sample
+    timer: u32,
+    /// Index of the current sample, 0-31
+    position: u8,
+    /// The sample last read from wave RAM
+    sample: u8,
+    ram: [u8; WAVE_RAM_SIZE],
+    /// Set while the channel is playing
+    enabled: bool,
+}
+
+impl Wave {
+    pub fn new() -> Self {
+        Self {
+            dac: false,
+            length: LengthCounter::new(256),
+            level: 0,
+            frequency: 0,
+            timer: 0,
+            position: 0,
+            sample: 0,
+            ram: [0; WAVE_RAM_SIZE],
+            enabled: false,
+        }
+    }
+
+    /// Reset all registers. Wave RAM is left as it is.
+    pub fn power_off(&mut self) {
+        *self = Self {
+            ram: self.ram,
+            ..Self::new()
+        };
+    }
+
+    fn period(&self) -> u32 {
+        (2048 - self.frequency as u32) * 2
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn dac_enabled(&self) -> bool {
+        self.dac
+    }
+
+    /// Current output level, 0-15
+    pub fn output(&self) -> u8 {
+        if !self.enabled {
+            return 0;
+        }
+        match self.level {
+            0 => 0,
+            level => self.sample >> (level - 1),
+        }
+    }
+
+    /// Run the channel for a number of T-cycles
+    pub fn tick(&mut self, cycles: u32) {
+        let mut cycles = cycles;
+        while cycles >= self.timer {
+            cycles -= self.timer;
+            self.timer = self.period();
+            self.position = (self.position + 1) % 32;
+            let byte = self.ram[self.position as usize / 2];
+            self.sample = if self.position.is_multiple_of(2) {
+                byte >> 4
+            } else {
+                byte & 0x0F
+            };
+        }
+        self.timer -= cycles;
+    }
+
+    pub fn clock_length(&mut self) {
+        if self.length.clock() {
+            self.enabled = false;
+        }
+    }
+
+    /// Restart the channel from the first sample
+    fn trigger(&mut self) {
+        self.enabled = self.dac;
+        self.length.trigger();
+        self.timer = self.period();
+        self.position = 0;
+    }
+
+    /// Read the readable bits of `register`. Write-only bits read as 0.
+    pub fn read(&self, register: u16) -> u8 {
+        match register {
+            0 => (self.dac as u8) << 7,
+            1 => 0,
+            2 => self.level << 5,
+            3 => 0,
+            4 => (self.length.enabled() as u8) << 6,
+            _ => panic!("Invalid wave channel register: {}", register),
+        }
+    }
+
+    pub fn write(&mut self, byte: u8, register: u16) {
+        match register {
+            0 => {
+                self.dac = byte & 0x80 != 0;
+                if !self.dac {
+                    self.enabled = false;
+                }
+            }
+            1 => self.length.load(byte),
+            2 => self.level = (byte >> 5) & 0x03,
+            3 => self.frequency = (self.frequency & 0x700) | byte as u16,
+            4 => {
+                self.frequency = (self.frequency & 0xFF) | ((byte as u16 & 0x07) << 8);
+                self.length.set_enabled(byte & 0x40 != 0);
+                if byte & 0x80 != 0 {
+                    self.trigger();
+                }
+            }
+            _ => panic!("Invalid wave channel register: {}", register),
+        }
+    }
+
+    pub fn read_ram(&self, index: usize) -> u8 {
+        self.ram[index]
+    }
+
+    pub fn write_ram(&mut self, byte: u8, index: usize) {
+        self.ram[index] = byte;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Trigger `wave` with a ramp in wave RAM, output level `level` and the
+    /// highest frequency
+    fn play(wave: &mut Wave, level: u8) {
+        for i in 0..WAVE_RAM_SIZE {
+            let hi = (2 * i as u8) & 0x0F;
+            let lo = (2 * i as u8 + 1) & 0x0F;
+            wave.write_ram((hi << 4) | lo, i);
+        }
+        wave.write(0x80, 0);
+        wave.write(level << 5, 2);
+        wave.write(0xFF, 3);
+        wave.write(0x87, 4);
+    }
+
+    #[test]
+    fn plays_wave_ram() {
+        let mut wave = Wave::new();
+        play(&mut wave, 1);
+        for sample in 1..32 {
+            wave.tick(2);
+            assert_eq!(wave.output(), sample & 0x0F);
+        }
+        wave.tick(2);
+        assert_eq!(wave.output(), 0);
+    }
+
+    #[test]
+    fn output_level() {
+        let mut wave = Wave::new();
+        for (level, expected) in [(0, 0), (1, 15), (2, 7), (3, 3)] {
+            play(&mut wave, level);
+            wave.tick(2 * 15);
+            assert_eq!(wave.output(), expected);
+        }
+    }
+
+    #[test]
+    fn dac_off_disables() {
+        let mut wave = Wave::new();
+        play(&mut wave, 1);
+        assert!(wave.enabled());
+        wave.write(0x00, 0);
+        assert!(!wave.enabled());
+    }
+
+    #[test]
+    fn power_off_keeps_ram() {
+        let mut wave = Wave::new();
+        play(&mut wave, 1);
+        wave.power_off();
+        assert!(!wave.enabled());
+        assert_eq!(wave.read(2), 0);
+        assert_eq!(wave.read_ram(1), 0x23);
+    }
+}
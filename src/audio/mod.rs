@@ -0,0 +1,52 @@
+mod recorder;
+mod resampler;
+#[cfg(feature = "sdl")]
+pub mod sdl;
+mod wav;
+
+pub use recorder::Recorder;
+pub use resampler::Resampler;
+
+use crate::apu::CLOCK_RATE;
+
+/// Rate at which samples are collected from the APU, once per machine cycle
+pub const APU_SAMPLE_RATE: u32 = CLOCK_RATE / 4;
+
+/// Largest adjustment made to the output rate by dynamic rate control, as a
+/// fraction of the nominal rate
+const MAX_RATE_DELTA: f64 = 0.005;
+
+/// Dynamic rate control. Emulation is paced by the video refresh rate, which
+/// never quite matches the audio clock of the host. Instead of letting the
+/// audio queue run dry or grow without bounds, the resampling rate is nudged
+/// up when the queue is less than `target` samples and down when it's more,
+/// by at most [`MAX_RATE_DELTA`]. The pitch change is too small to hear.
+///
+/// # Arguments
+/// * `rate` - The nominal output rate of the host.
+/// * `queued` - Samples currently waiting to be played.
+/// * `target` - The amount of queued samples to aim for.
+///
+/// # Returns
+/// The output rate to resample to.
+#[cfg_attr(not(feature = "sdl"), allow(dead_code))]
+pub fn adjusted_rate(rate: u32, queued: usize, target: usize) -> f64 {
+    let fill = (queued as f64 / (2 * target) as f64).min(1.0);
+    rate as f64 * (1.0 + MAX_RATE_DELTA * (1.0 - 2.0 * fill))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rate_control() {
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-6;
+        assert!(close(adjusted_rate(48000, 1000, 1000), 48000.0));
+        assert!(close(adjusted_rate(48000, 0, 1000), 48240.0));
+        assert!(close(adjusted_rate(48000, 2000, 1000), 47760.0));
+        assert!(close(adjusted_rate(48000, 10000, 1000), 47760.0));
+        assert!(adjusted_rate(48000, 500, 1000) > 48000.0);
+        assert!(adjusted_rate(48000, 1500, 1000) < 48000.0);
+    }
+}
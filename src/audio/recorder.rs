@@ -0,0 +1,142 @@
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+
+use super::wav::WavWriter;
+use super::{Resampler, APU_SAMPLE_RATE};
+use crate::apu::{ChannelSample, Sample};
+
+/// Sample rate of recordings
+pub const RECORDING_RATE: u32 = 44100;
+
+/// Records the audio output to WAV files: the stereo mix, and optionally
+/// each channel on its own in mono. Resampling runs at a fixed rate, so the
+/// same input always gives the same files.
+pub struct Recorder {
+    mix: WavWriter<BufWriter<File>>,
+    resampler: Resampler,
+    /// Files and resampler for the separate channels, if recorded
+    channels: Option<(Vec<WavWriter<BufWriter<File>>>, Resampler<4>)>,
+}
+
+impl Recorder {
+    /// Start recording the mix to `path`. With `separate_channels`, channel
+    /// `n` is also recorded to `path` with the extension `.chn.wav`.
+    pub fn create(path: &Path, separate_channels: bool) -> io::Result<Self> {
+        let mix = WavWriter::new(BufWriter::new(File::create(path)?), 2, RECORDING_RATE)?;
+        let channels = if separate_channels {
+            let files = (1..=4)
+                .map(|channel| {
+                    let file = File::create(channel_path(path, channel))?;
+                    WavWriter::new(BufWriter::new(file), 1, RECORDING_RATE)
+                })
+                .collect::<io::Result<_>>()?;
+            Some((files, Resampler::new(APU_SAMPLE_RATE, RECORDING_RATE)))
+        } else {
+            None
+        };
+
+        Ok(Self {
+            mix,
+            resampler: Resampler::new(APU_SAMPLE_RATE, RECORDING_RATE),
+            channels,
+        })
+    }
+
+    /// Record samples collected from the APU at [`APU_SAMPLE_RATE`]
+    pub fn write(
+        &mut self,
+        samples: &[Sample],
+        channel_samples: &[ChannelSample],
+    ) -> io::Result<()> {
+        let mut mix = Vec::new();
+        self.resampler.process(samples, &mut mix);
+        self.mix.write(mix.as_flattened())?;
+
+        if let Some((files, resampler)) = &mut self.channels {
+            let mut resampled = Vec::new();
+            resampler.process(channel_samples, &mut resampled);
+            for (channel, file) in files.iter_mut().enumerate() {
+                let samples: Vec<f32> = resampled.iter().map(|sample| sample[channel]).collect();
+                file.write(&samples)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Flush all files
+    pub fn finish(self) -> io::Result<()> {
+        self.mix.finish()?;
+        for file in self.channels.into_iter().flat_map(|(files, _)| files) {
+            file.finish()?;
+        }
+        Ok(())
+    }
+}
+
+/// Path of the file for `channel`, next to the mix at `path`
+fn channel_path(path: &Path, channel: usize) -> PathBuf {
+    path.with_extension(format!("ch{}.wav", channel))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("gibberish-{}-{}.wav", std::process::id(), name))
+    }
+
+    #[test]
+    fn channel_paths() {
+        assert_eq!(
+            channel_path(Path::new("dir/out.wav"), 3),
+            Path::new("dir/out.ch3.wav")
+        );
+        assert_eq!(channel_path(Path::new("out"), 1), Path::new("out.ch1.wav"));
+    }
+
+    #[test]
+    fn records_mix_and_channels() {
+        let path = temp_path("recorder");
+        let mut recorder = Recorder::create(&path, true).unwrap();
+
+        let samples = vec![[0.5, -0.5]; APU_SAMPLE_RATE as usize / 10];
+        let channel_samples = vec![[0.1, 0.2, 0.3, 0.4]; APU_SAMPLE_RATE as usize / 10];
+        recorder.write(&samples, &channel_samples).unwrap();
+        recorder.finish().unwrap();
+
+        let frames = RECORDING_RATE as u64 / 10;
+        let mix_size = std::fs::metadata(&path).unwrap().len();
+        assert!((mix_size - 44).abs_diff(frames * 4) <= 4);
+        for channel in 1..=4 {
+            let channel_path = channel_path(&path, channel);
+            let size = std::fs::metadata(&channel_path).unwrap().len();
+            assert!((size - 44).abs_diff(frames * 2) <= 2);
+            std::fs::remove_file(channel_path).unwrap();
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn deterministic() {
+        let record = |name| {
+            let path = temp_path(name);
+            let mut recorder = Recorder::create(&path, false).unwrap();
+            let samples: Vec<Sample> = (0..APU_SAMPLE_RATE / 20)
+                .map(|i| [((i / 300) % 2) as f32 - 0.5, ((i / 700) % 2) as f32 - 0.5])
+                .collect();
+
+            // Chunking shouldn't matter
+            let chunk = if name == "a" { 1000 } else { 1234 };
+            for samples in samples.chunks(chunk) {
+                recorder.write(samples, &[]).unwrap();
+            }
+            recorder.finish().unwrap();
+            let bytes = std::fs::read(&path).unwrap();
+            std::fs::remove_file(path).unwrap();
+            bytes
+        };
+        assert_eq!(record("a"), record("b"));
+    }
+}
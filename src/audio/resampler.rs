@@ -0,0 +1,181 @@
+use std::f64::consts::PI;
+
+/// Fraction of the output rate to put the low-pass cutoff at, leaving some
+/// room below the Nyquist frequency for the filter to roll off
+const CUTOFF: f64 = 0.45;
+
+/// Q of the two sections of a fourth order Butterworth filter
+const BUTTERWORTH_Q: [f64; 2] = [0.541_196_1, 1.306_563];
+
+/// A second order low-pass filter section
+#[derive(Debug, Clone, Default)]
+struct Biquad {
+    b0: f64,
+    b1: f64,
+    b2: f64,
+    a1: f64,
+    a2: f64,
+    /// Previous two inputs
+    x: [f64; 2],
+    /// Previous two outputs
+    y: [f64; 2],
+}
+
+impl Biquad {
+    /// Low-pass section with `cutoff` Hz at `rate` samples per second
+    fn low_pass(cutoff: f64, rate: f64, q: f64) -> Self {
+        let omega = 2.0 * PI * cutoff / rate;
+        let alpha = omega.sin() / (2.0 * q);
+        let cos = omega.cos();
+        let a0 = 1.0 + alpha;
+        Self {
+            b0: (1.0 - cos) / 2.0 / a0,
+            b1: (1.0 - cos) / a0,
+            b2: (1.0 - cos) / 2.0 / a0,
+            a1: -2.0 * cos / a0,
+            a2: (1.0 - alpha) / a0,
+            ..Self::default()
+        }
+    }
+
+    fn process(&mut self, input: f64) -> f64 {
+        let output = self.b0 * input + self.b1 * self.x[0] + self.b2 * self.x[1]
+            - self.a1 * self.y[0]
+            - self.a2 * self.y[1];
+        self.x = [input, self.x[0]];
+        self.y = [output, self.y[0]];
+        output
+    }
+}
+
+/// Converts samples from the APU rate to the host rate. The input is
+/// low-pass filtered below the Nyquist frequency of the output to avoid
+/// aliasing, then decimated by interpolating between input samples. The output
+/// rate can be adjusted on the fly for dynamic rate control.
+///
+/// Every sample has `N` channels, two for stereo.
+#[derive(Debug, Clone)]
+pub struct Resampler<const N: usize = 2> {
+    input_rate: f64,
+    output_rate: f64,
+    /// Low-pass filter sections for each channel
+    filters: [[Biquad; 2]; N],
+    /// Position of the next output sample, in input samples after
+    /// [`previous`](#structfield.previous)
+    position: f64,
+    /// The last filtered input sample
+    previous: [f32; N],
+}
+
+impl<const N: usize> Resampler<N> {
+    pub fn new(input_rate: u32, output_rate: u32) -> Self {
+        let (input_rate, output_rate) = (input_rate as f64, output_rate as f64);
+        let section = |q| Biquad::low_pass(output_rate * CUTOFF, input_rate, q);
+        Self {
+            input_rate,
+            output_rate,
+            filters: std::array::from_fn(|_| BUTTERWORTH_Q.map(section)),
+            position: 1.0,
+            previous: [0.0; N],
+        }
+    }
+
+    /// Change the output rate. The filter cutoff is left where it is, so this
+    /// is meant for small adjustments.
+    #[cfg_attr(not(feature = "sdl"), allow(dead_code))]
+    pub fn set_output_rate(&mut self, output_rate: f64) {
+        self.output_rate = output_rate;
+    }
+
+    /// Resample `input`, appending the result to `output`
+    pub fn process(&mut self, input: &[[f32; N]], output: &mut Vec<[f32; N]>) {
+        let step = self.input_rate / self.output_rate;
+        for sample in input {
+            let current: [f32; N] = std::array::from_fn(|channel| {
+                self.filters[channel]
+                    .iter_mut()
+                    .fold(sample[channel] as f64, |sample, filter| {
+                        filter.process(sample)
+                    }) as f32
+            });
+
+            while self.position <= 1.0 {
+                let t = self.position as f32;
+                output.push(std::array::from_fn(|channel| {
+                    self.previous[channel] + (current[channel] - self.previous[channel]) * t
+                }));
+                self.position += step;
+            }
+            self.position -= 1.0;
+            self.previous = current;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const INPUT_RATE: u32 = 1 << 20;
+
+    /// A sine wave of `frequency` Hz, one second long
+    fn sine(frequency: f64) -> Vec<[f32; 2]> {
+        (0..INPUT_RATE)
+            .map(|i| {
+                let value = (2.0 * PI * frequency * i as f64 / INPUT_RATE as f64).sin() as f32;
+                [value, -value]
+            })
+            .collect()
+    }
+
+    /// Peak amplitude of the left channel, skipping the filter settling
+    fn peak(samples: &[[f32; 2]]) -> f32 {
+        samples[samples.len() / 2..]
+            .iter()
+            .map(|[left, _]| left.abs())
+            .fold(0.0, f32::max)
+    }
+
+    #[test]
+    fn output_length() {
+        let mut resampler: Resampler = Resampler::new(INPUT_RATE, 48000);
+        let mut output = Vec::new();
+        resampler.process(&vec![[0.0, 0.0]; INPUT_RATE as usize], &mut output);
+        assert!(output.len().abs_diff(48000) <= 1);
+
+        // Feeding in pieces gives the same amount
+        let mut resampler: Resampler = Resampler::new(INPUT_RATE, 48000);
+        let mut output = Vec::new();
+        for _ in 0..1024 {
+            resampler.process(&[[0.0, 0.0]; INPUT_RATE as usize / 1024], &mut output);
+        }
+        assert!(output.len().abs_diff(48000) <= 1);
+    }
+
+    #[test]
+    fn passes_low_frequencies() {
+        let mut resampler: Resampler = Resampler::new(INPUT_RATE, 48000);
+        let mut output = Vec::new();
+        resampler.process(&sine(1000.0), &mut output);
+        assert!((peak(&output) - 1.0).abs() < 0.02);
+        assert!(output.iter().all(|[left, right]| left == &-right));
+    }
+
+    #[test]
+    fn removes_high_frequencies() {
+        // Would alias down to 4 kHz without filtering
+        let mut resampler: Resampler = Resampler::new(INPUT_RATE, 48000);
+        let mut output = Vec::new();
+        resampler.process(&sine(52000.0), &mut output);
+        assert!(peak(&output) < 0.05);
+    }
+
+    #[test]
+    fn adjusted_rate() {
+        let mut resampler: Resampler = Resampler::new(INPUT_RATE, 48000);
+        resampler.set_output_rate(48240.0);
+        let mut output = Vec::new();
+        resampler.process(&vec![[0.0, 0.0]; INPUT_RATE as usize], &mut output);
+        assert!(output.len().abs_diff(48240) <= 1);
+    }
+}
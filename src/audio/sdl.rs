@@ -0,0 +1,79 @@
+use std::time::Duration;
+
+use sdl2::audio::{AudioQueue, AudioSpecDesired};
+
+use super::{adjusted_rate, Resampler, APU_SAMPLE_RATE};
+use crate::apu::Sample;
+
+/// Output rate to ask the host for
+const HOST_RATE: i32 = 48000;
+/// Samples per SDL audio buffer
+const BUFFER_SAMPLES: u16 = 1024;
+/// How much audio to keep queued. Enough to cover a late frame, without
+/// adding noticeable latency.
+const TARGET_LATENCY: Duration = Duration::from_millis(50);
+
+/// Audio output through an SDL audio queue. APU samples are resampled to the
+/// rate of the device and queued, with the rate adjusted to keep the queue
+/// close to [`TARGET_LATENCY`].
+pub struct SdlAudio {
+    queue: AudioQueue<f32>,
+    resampler: Resampler,
+    /// Nominal rate of the device
+    rate: u32,
+    /// Resampled samples waiting to be queued
+    buffer: Vec<Sample>,
+}
+
+impl SdlAudio {
+    pub fn new(sdl: &sdl2::Sdl) -> Result<Self, String> {
+        let audio = sdl.audio()?;
+        let desired = AudioSpecDesired {
+            freq: Some(HOST_RATE),
+            channels: Some(2),
+            samples: Some(BUFFER_SAMPLES),
+        };
+        let queue = audio.open_queue::<f32, _>(None, &desired)?;
+        let rate = queue.spec().freq as u32;
+        queue.resume();
+
+        Ok(Self {
+            queue,
+            resampler: Resampler::new(APU_SAMPLE_RATE, rate),
+            rate,
+            buffer: Vec::new(),
+        })
+    }
+
+    /// Stereo samples waiting in the queue
+    fn queued(&self) -> usize {
+        self.queue.size() as usize / (2 * std::mem::size_of::<f32>())
+    }
+
+    /// Amount of audio waiting to be played
+    pub fn latency(&self) -> Duration {
+        Duration::from_secs_f64(self.queued() as f64 / self.rate as f64)
+    }
+
+    /// Check whether more than enough audio is queued, meaning emulation is
+    /// running ahead
+    pub fn ahead(&self) -> bool {
+        self.latency() > 2 * TARGET_LATENCY
+    }
+
+    /// Resample and queue samples collected from the APU at
+    /// [`APU_SAMPLE_RATE`]
+    pub fn queue(&mut self, samples: &[Sample]) -> Result<(), String> {
+        let target = (TARGET_LATENCY.as_secs_f64() * self.rate as f64) as usize;
+        self.resampler
+            .set_output_rate(adjusted_rate(self.rate, self.queued(), target));
+
+        self.buffer.clear();
+        self.resampler.process(samples, &mut self.buffer);
+        if self.queue.queue(self.buffer.as_flattened()) {
+            Ok(())
+        } else {
+            Err(sdl2::get_error())
+        }
+    }
+}
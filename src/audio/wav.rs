@@ -0,0 +1,131 @@
+use std::io::{self, Seek, SeekFrom, Write};
+
+/// Size of the RIFF and WAVE headers before the sample data
+const HEADER_SIZE: u32 = 44;
+/// Bits per sample. Samples are written as signed 16-bit PCM.
+const BITS_PER_SAMPLE: u16 = 16;
+
+/// Writes 16-bit PCM WAV files. The sizes in the header are updated after
+/// every write, so the file is valid even if the emulator is killed before
+/// [`finish`](Self::finish) is called.
+pub struct WavWriter<W: Write + Seek> {
+    writer: W,
+    channels: u16,
+    /// Bytes of sample data written so far
+    data_size: u32,
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    pub fn new(mut writer: W, channels: u16, sample_rate: u32) -> io::Result<Self> {
+        let block_align = channels * BITS_PER_SAMPLE / 8;
+        writer.write_all(b"RIFF")?;
+        writer.write_all(&(HEADER_SIZE - 8).to_le_bytes())?;
+        writer.write_all(b"WAVE")?;
+
+        writer.write_all(b"fmt ")?;
+        writer.write_all(&16u32.to_le_bytes())?;
+        writer.write_all(&1u16.to_le_bytes())?; // PCM
+        writer.write_all(&channels.to_le_bytes())?;
+        writer.write_all(&sample_rate.to_le_bytes())?;
+        writer.write_all(&(sample_rate * block_align as u32).to_le_bytes())?;
+        writer.write_all(&block_align.to_le_bytes())?;
+        writer.write_all(&BITS_PER_SAMPLE.to_le_bytes())?;
+
+        writer.write_all(b"data")?;
+        writer.write_all(&0u32.to_le_bytes())?;
+
+        Ok(Self {
+            writer,
+            channels,
+            data_size: 0,
+        })
+    }
+
+    /// Write interleaved samples from -1.0 to 1.0. Anything outside of that is
+    /// clipped.
+    ///
+    /// # Panics
+    /// If the number of samples isn't a multiple of the number of channels.
+    pub fn write(&mut self, samples: &[f32]) -> io::Result<()> {
+        assert_eq!(samples.len() % self.channels as usize, 0);
+
+        let bytes: Vec<u8> = samples
+            .iter()
+            .flat_map(|sample| {
+                let sample = (sample.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16;
+                sample.to_le_bytes()
+            })
+            .collect();
+        self.writer.write_all(&bytes)?;
+        self.data_size += bytes.len() as u32;
+        self.update_header()
+    }
+
+    /// Write the current sizes into the header
+    fn update_header(&mut self) -> io::Result<()> {
+        self.writer.seek(SeekFrom::Start(4))?;
+        self.writer
+            .write_all(&(HEADER_SIZE - 8 + self.data_size).to_le_bytes())?;
+        self.writer.seek(SeekFrom::Start(HEADER_SIZE as u64 - 4))?;
+        self.writer.write_all(&self.data_size.to_le_bytes())?;
+        self.writer.seek(SeekFrom::End(0))?;
+        Ok(())
+    }
+
+    /// Flush everything written to the underlying writer
+    pub fn finish(mut self) -> io::Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use super::*;
+
+    fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+        u16::from_le_bytes(bytes[offset..offset + 2].try_into().unwrap())
+    }
+
+    #[test]
+    fn header() {
+        let wav = WavWriter::new(Cursor::new(Vec::new()), 2, 44100).unwrap();
+        let bytes = wav.finish().unwrap().into_inner();
+
+        assert_eq!(bytes.len(), HEADER_SIZE as usize);
+        assert_eq!(&bytes[0..4], b"RIFF");
+        assert_eq!(read_u32(&bytes, 4), 36);
+        assert_eq!(&bytes[8..16], b"WAVEfmt ");
+        assert_eq!(read_u32(&bytes, 16), 16);
+        assert_eq!(read_u16(&bytes, 20), 1);
+        assert_eq!(read_u16(&bytes, 22), 2);
+        assert_eq!(read_u32(&bytes, 24), 44100);
+        assert_eq!(read_u32(&bytes, 28), 44100 * 4);
+        assert_eq!(read_u16(&bytes, 32), 4);
+        assert_eq!(read_u16(&bytes, 34), 16);
+        assert_eq!(&bytes[36..40], b"data");
+        assert_eq!(read_u32(&bytes, 40), 0);
+    }
+
+    #[test]
+    fn samples() {
+        let mut wav = WavWriter::new(Cursor::new(Vec::new()), 1, 8000).unwrap();
+        wav.write(&[0.0, 1.0, -1.0]).unwrap();
+        wav.write(&[0.5, 2.0]).unwrap();
+        let bytes = wav.finish().unwrap().into_inner();
+
+        assert_eq!(read_u32(&bytes, 4), 36 + 10);
+        assert_eq!(read_u32(&bytes, 40), 10);
+        let samples: Vec<i16> = bytes[44..]
+            .chunks(2)
+            .map(|bytes| i16::from_le_bytes([bytes[0], bytes[1]]))
+            .collect();
+        assert_eq!(samples, [0, 32767, -32767, 16384, 32767]);
+    }
+}
@@ -0,0 +1,297 @@
+use std::fmt;
+
+use super::opcodes::OpCode;
+use super::{Cpu, FlagRegister, Registers};
+use crate::memory::map::usable;
+use crate::memory::watch::{WatchHit, Watchpoint};
+
+/// A CPU register, as seen from outside the CPU, e.g. by a debugger
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Register {
+    A,
+    F,
+    B,
+    C,
+    D,
+    E,
+    H,
+    L,
+    AF,
+    BC,
+    DE,
+    HL,
+    SP,
+    PC,
+}
+
+impl Register {
+    /// Look up a register by name, ignoring case
+    pub fn parse(name: &str) -> Option<Self> {
+        let register = match name.to_ascii_lowercase().as_str() {
+            "a" => Self::A,
+            "f" => Self::F,
+            "b" => Self::B,
+            "c" => Self::C,
+            "d" => Self::D,
+            "e" => Self::E,
+            "h" => Self::H,
+            "l" => Self::L,
+            "af" => Self::AF,
+            "bc" => Self::BC,
+            "de" => Self::DE,
+            "hl" => Self::HL,
+            "sp" => Self::SP,
+            "pc" => Self::PC,
+            _ => return None,
+        };
+        Some(register)
+    }
+}
+
+/// A decoded instruction
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instruction {
+    /// The instruction in assembly, with any argument filled in
+    pub text: String,
+    /// Size of the instruction in bytes
+    pub size: u8,
+}
+
+impl Cpu {
+    /// All registers, for displaying
+    pub fn registers(&self) -> &Registers {
+        &self.registers
+    }
+
+    /// Read a register. 8 bit registers are zero extended.
+    pub fn register(&self, register: Register) -> u16 {
+        let registers = &self.registers;
+        match register {
+            Register::A => registers.a as u16,
+            Register::F => registers.f.value() as u16,
+            Register::B => registers.b as u16,
+            Register::C => registers.c as u16,
+            Register::D => registers.d as u16,
+            Register::E => registers.e as u16,
+            Register::H => registers.h as u16,
+            Register::L => registers.l as u16,
+            Register::AF => registers.af(),
+            Register::BC => registers.bc(),
+            Register::DE => registers.de(),
+            Register::HL => registers.hl(),
+            Register::SP => registers.sp,
+            Register::PC => registers.pc,
+        }
+    }
+
+    /// Write a register. 8 bit registers take the low byte of `value`, and
+    /// only the flag bits of F can be set.
+    pub fn set_register(&mut self, register: Register, value: u16) {
+        let registers = &mut self.registers;
+        let byte = value as u8;
+        match register {
+            Register::A => registers.a = byte,
+            Register::F => registers.f.set(byte),
+            Register::B => registers.b = byte,
+            Register::C => registers.c = byte,
+            Register::D => registers.d = byte,
+            Register::E => registers.e = byte,
+            Register::H => registers.h = byte,
+            Register::L => registers.l = byte,
+            Register::AF => registers.put_af(value),
+            Register::BC => registers.put_bc(value),
+            Register::DE => registers.put_de(value),
+            Register::HL => registers.put_hl(value),
+            Register::SP => registers.sp = value,
+            Register::PC => registers.pc = value,
+        }
+    }
+
+    /// Decode the instruction at `addr`. Arguments are shown in hex, with
+    /// relative jumps resolved to the address they jump to.
+    pub fn disassemble(&self, addr: u16) -> Instruction {
+        let OpCode(mnemonic, _, size, _) = self.decode(addr);
+        let byte = || self.peek_byte(addr.wrapping_add(1));
+        let word = || u16::from_le_bytes([byte(), self.peek_byte(addr.wrapping_add(2))]);
+        let offset = || byte() as i8;
+
+        let text = if mnemonic.contains("d16") || mnemonic.contains("a16") {
+            mnemonic
+                .replace("d16", &format!("${:04x}", word()))
+                .replace("a16", &format!("${:04x}", word()))
+        } else if mnemonic.contains("d8") {
+            mnemonic.replace("d8", &format!("${:02x}", byte()))
+        } else if mnemonic.contains("a8") {
+            mnemonic.replace("a8", &format!("$ff{:02x}", byte()))
+        } else if mnemonic.starts_with("JR") {
+            let target = addr.wrapping_add(size as u16).wrapping_add(offset() as u16);
+            mnemonic.replace("r8", &format!("${:04x}", target))
+        } else if mnemonic.contains("+r8") {
+            mnemonic.replace("+r8", &format!("{:+}", offset()))
+        } else if mnemonic.contains("r8") {
+            mnemonic.replace("r8", &format!("{}", offset()))
+        } else {
+            mnemonic.to_owned()
+        };
+
+        Instruction { text, size }
+    }
+
+    /// Read a byte without triggering watchpoints, for looking at memory
+    /// from outside the CPU. The unusable area reads as 0xFF.
+    pub fn peek_byte(&self, addr: u16) -> u8 {
+        if !usable(addr) {
+            return 0xFF;
+        }
+        self.memory.peek_byte(addr)
+    }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.memory.watchpoints_mut().add(watchpoint);
+    }
+
+    /// Remove a watchpoint equal to `watchpoint`
+    ///
+    /// # Returns
+    /// * `false` if there was no such watchpoint, otherwise `true`.
+    pub fn remove_watchpoint(&mut self, watchpoint: &Watchpoint) -> bool {
+        self.memory.watchpoints_mut().remove(watchpoint)
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        self.memory.watchpoints().list()
+    }
+
+    /// The first watched access made by the last step, if any
+    pub fn take_watch_hit(&self) -> Option<WatchHit> {
+        self.memory.watchpoints().take_hit()
+    }
+}
+
+impl fmt::Display for FlagRegister {
+    /// Set flags by name, cleared ones as `-`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let flag = |set, name| if set { name } else { '-' };
+        write!(
+            f,
+            "{}{}{}{}",
+            flag(self.z, 'Z'),
+            flag(self.n, 'N'),
+            flag(self.h, 'H'),
+            flag(self.c, 'C')
+        )
+    }
+}
+
+impl fmt::Display for Registers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "A: {:02x}  F: {:02x} [{}]",
+            self.a,
+            self.f.value(),
+            self.f
+        )?;
+        writeln!(f, "B: {:02x}  C: {:02x}", self.b, self.c)?;
+        writeln!(f, "D: {:02x}  E: {:02x}", self.d, self.e)?;
+        writeln!(f, "H: {:02x}  L: {:02x}", self.h, self.l)?;
+        write!(f, "SP: {:04x}  PC: {:04x}", self.sp, self.pc)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::memory::map::{INT_ENABLE_ADDR, INT_FLAG_ADDR};
+    use crate::memory::watch::Access;
+
+    fn watchpoint(addr: u16, access: Access) -> Watchpoint {
+        Watchpoint {
+            range: addr..=addr,
+            access,
+            condition: None,
+        }
+    }
+
+    fn disassemble(bytes: &[u8]) -> Instruction {
+        let mut cpu = Cpu::default();
+        for (i, byte) in bytes.iter().enumerate() {
+            cpu.write_byte(*byte, 0xC000 + i as u16);
+        }
+        cpu.disassemble(0xC000)
+    }
+
+    #[test]
+    fn registers() {
+        let mut cpu = Cpu::default();
+        cpu.set_register(Register::BC, 0x1234);
+        assert_eq!(cpu.register(Register::B), 0x12);
+        assert_eq!(cpu.register(Register::C), 0x34);
+
+        cpu.set_register(Register::F, 0xFF);
+        assert_eq!(cpu.register(Register::F), 0xF0);
+        cpu.set_register(Register::A, 0xAB01);
+        assert_eq!(cpu.register(Register::AF), 0x01F0);
+
+        cpu.set_register(Register::PC, 0x0150);
+        assert_eq!(cpu.register(Register::PC), 0x0150);
+        assert_eq!(Register::parse("Hl"), Some(Register::HL));
+        assert_eq!(Register::parse("ix"), None);
+    }
+
+    #[test]
+    fn display() {
+        let mut cpu = Cpu::default();
+        cpu.set_register(Register::AF, 0x01A0);
+        cpu.set_register(Register::SP, 0xFFFE);
+        let registers = cpu.registers().to_string();
+        assert!(registers.starts_with("A: 01  F: a0 [Z-H-]\n"));
+        assert!(registers.ends_with("SP: fffe  PC: 0000"));
+    }
+
+    #[test]
+    fn arguments() {
+        let cases: &[(&[u8], &str, u8)] = &[
+            (&[0x00], "NOP", 1),
+            (&[0x3E, 0x42], "LD A, $42", 2),
+            (&[0x21, 0x34, 0x12], "LD HL, $1234", 3),
+            (&[0xCD, 0x00, 0x02], "CALL $0200", 3),
+            (&[0xE0, 0x80], "LDH ($ff80), A", 2),
+            (&[0x18, 0xFE], "JR $c000", 2),
+            (&[0x20, 0x05], "JR NZ, $c007", 2),
+            (&[0xE8, 0xFE], "ADD SP, -2", 2),
+            (&[0xF8, 0x03], "LD HL, SP+3", 2),
+            (&[0xCB, 0x37], "SWAP A", 2),
+        ];
+        for (bytes, text, size) in cases {
+            let instruction = disassemble(bytes);
+            assert_eq!(instruction.text, *text);
+            assert_eq!(instruction.size, *size);
+        }
+    }
+
+    #[test]
+    fn watched_accesses() {
+        let mut cpu = Cpu::reset();
+        cpu.add_watchpoint(watchpoint(INT_ENABLE_ADDR, Access::ReadWrite));
+        cpu.add_watchpoint(watchpoint(INT_FLAG_ADDR, Access::ReadWrite));
+        cpu.add_watchpoint(watchpoint(0xC000, Access::Write));
+
+        // Checking for interrupts isn't an access by the program
+        cpu.step();
+        assert_eq!(cpu.take_watch_hit(), None);
+
+        // Neither is acknowledging one. LD ($c000), A in the timer handler is,
+        // and is reported at the handler.
+        for (i, byte) in [0xEA, 0x00, 0xC0].iter().enumerate() {
+            cpu.write_byte(*byte, 0x0050 + i as u16);
+        }
+        cpu.interrupt_master_enable = true;
+        cpu.write_byte(0x04, INT_ENABLE_ADDR);
+        cpu.write_byte(0x04, INT_FLAG_ADDR);
+        cpu.step();
+        let hit = cpu.take_watch_hit().unwrap();
+        assert_eq!((hit.pc, hit.addr), (0x0050, 0xC000));
+        assert_eq!(cpu.read_byte(INT_FLAG_ADDR) & 0x04, 0);
+    }
+}
@@ -115,6 +115,7 @@ impl InterruptController {
         Some(interrupt)
     }
 
+    #[allow(dead_code)]
     pub fn request_interrupt(&mut self, interrupt: Interrupt) {
         let interrupt_number: u8 = interrupt.into();
         self.request = (u8::from(self.request) | interrupt_number).into();
@@ -129,15 +130,18 @@ impl InterruptController {
         self.request = (u8::from(self.request) | interrupts).into();
     }
 
+    #[allow(dead_code)]
     pub fn unrequest_interrupts(&mut self, interrupts: u8) {
         self.request = (u8::from(self.request) & !interrupts).into();
     }
 
+    #[allow(dead_code)]
     pub fn enable_interrupt(&mut self, interrupt: Interrupt) {
         let interrupt_number: u8 = interrupt.into();
         self.enabled = (u8::from(self.enabled) | interrupt_number).into();
     }
 
+    #[allow(dead_code)]
     pub fn disable_interrupt(&mut self, interrupt: Interrupt) {
         let interrupt_number: u8 = interrupt.into();
         self.enabled = (u8::from(self.enabled) & !interrupt_number).into();
@@ -147,6 +151,7 @@ impl InterruptController {
         self.enabled = (u8::from(self.enabled) | interrupts).into();
     }
 
+    #[allow(dead_code)]
     pub fn disable_interrupts(&mut self, interrupts: u8) {
         self.enabled = (u8::from(self.enabled) & !interrupts).into();
     }
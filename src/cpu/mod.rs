@@ -1,12 +1,21 @@
+mod debug;
 pub mod interrupts;
 mod opcodes;
 
-use crate::memory::map::MemoryMap;
+use crate::apu::Apu;
+use crate::memory::cartridge::Cartridge;
+use crate::memory::joypad::Button;
+use crate::memory::map::{MemoryMap, INT_ENABLE_ADDR, INT_FLAG_ADDR};
+use crate::memory::serial::SerialDevice;
+use crate::ppu::Renderer;
 use interrupts::{Interrupt, InterruptController};
 use opcodes::{Argument, OpCode};
 
-#[derive(Debug, Clone, PartialEq)]
+pub use debug::Register;
+
+#[derive(Debug, Clone, PartialEq, Default)]
 enum RunningMode {
+    #[default]
     PowerUp,
     Stop,
     HaltImeSet,
@@ -15,23 +24,19 @@ enum RunningMode {
     Running,
 }
 
-impl Default for RunningMode {
-    fn default() -> Self {
-        RunningMode::PowerUp
-    }
-}
-
 #[derive(Debug, Clone, Default)]
 pub struct Cpu {
     registers: Registers,
     machine_cycles: u8,
+    /// Machine cycles run since reset
+    cycles: u64,
     memory: MemoryMap,
     current_instruction: u8,
     current_argument: Option<Argument>,
     inhibit_pc: bool,
     interrupt_master_enable: bool,
     mode: RunningMode,
-    interrupts: InterruptController,
+    rumble: bool,
 }
 
 impl Cpu {
@@ -40,7 +45,6 @@ impl Cpu {
 
         // TODO: (maybe)
         // - Run startup ROM at 0x00 - 0xFF
-        // - Validate checksum
 
         cpu.registers.pc = 0x100;
         cpu.registers.a = 0x01;
@@ -94,6 +98,12 @@ impl Cpu {
         cpu
     }
 
+    /// Make the PPU draw with `renderer`
+    pub fn with_renderer(mut self, renderer: Renderer) -> Self {
+        self.memory.get_ppu_mut().set_renderer(renderer);
+        self
+    }
+
     #[allow(dead_code)]
     /// Step through one instrucion
     pub fn step(&mut self) {
@@ -103,30 +113,53 @@ impl Cpu {
 
         self.machine_cycles = 0;
         self.inhibit_pc = false;
+        // Watchpoint hits are reported for a single step
+        self.memory.watchpoints().take_hit();
+        self.memory.watchpoints().set_pc(self.registers.pc);
 
         self.handle_interrupts();
-        self.current_instruction = self.read_byte(self.registers.pc);
+        // Dispatching an interrupt moves on to its handler
+        self.memory.watchpoints().set_pc(self.registers.pc);
+        self.current_instruction = self.memory.peek_byte(self.registers.pc);
 
-        let OpCode(_mnemonic, func, size, cycles) =
-            opcodes::OPCODES[self.current_instruction as usize];
+        let OpCode(_mnemonic, func, size, cycles) = self.decode(self.registers.pc);
 
         match size {
             2 => {
-                self.current_argument = Some(Argument::Byte(self.read_byte(self.registers.pc + 1)))
+                self.current_argument =
+                    Some(Argument::Byte(self.memory.peek_byte(self.registers.pc + 1)))
             }
             3 => {
-                self.current_argument = Some(Argument::Word(self.read_word(self.registers.pc + 1)))
+                self.current_argument =
+                    Some(Argument::Word(self.memory.peek_word(self.registers.pc + 1)))
             }
             _ => self.current_argument = None,
         }
 
         match self.mode {
             RunningMode::Running => func(self),
-            _ => return,
+            _ => {
+                // The rest of the system keeps running while halted
+                self.machine_cycles += 1;
+                self.cycles += self.machine_cycles as u64;
+                self.increment_timers();
+                self.tick_serial();
+                self.tick_apu();
+                self.tick_ppu();
+                self.memory.tick_dma(self.machine_cycles.into());
+                self.poll_joypad();
+                return;
+            }
         }
 
         self.machine_cycles += cycles;
+        self.cycles += self.machine_cycles as u64;
         self.increment_timers();
+        self.tick_serial();
+        self.tick_apu();
+        self.tick_ppu();
+        self.memory.tick_dma(self.machine_cycles.into());
+        self.poll_joypad();
         if !self.inhibit_pc {
             self.registers.pc = self.registers.pc.wrapping_add(size as u16);
         }
@@ -134,14 +167,13 @@ impl Cpu {
 
     /// Check for interrupts and handle them if enabled
     fn handle_interrupts(&mut self) {
-        self.interrupts.enable_interrupts(self.read_byte(0xFFFF));
-        self.interrupts.request_interrupts(self.read_byte(0xFF0F));
+        let mut interrupts = self.interrupts();
 
         if self.mode == RunningMode::Running && !self.interrupt_master_enable {
             return;
         }
 
-        if self.mode == RunningMode::HaltImeClear && self.interrupts.interrupts_requested() {
+        if self.mode == RunningMode::HaltImeClear && interrupts.interrupts_requested() {
             // Move out of halt mode
             self.mode = RunningMode::Running;
             return;
@@ -154,10 +186,11 @@ impl Cpu {
             return;
         }
 
-        if let Some(interrupt) = self.interrupts.get_pending_interrupt() {
+        if let Some(interrupt) = interrupts.get_pending_interrupt() {
             self.push(self.registers.pc);
             self.interrupt_master_enable = false;
-            self.write_byte(self.read_byte(0xFF0F) & !u8::from(interrupt), 0xFF0F);
+            let flags = self.memory.peek_byte(INT_FLAG_ADDR) & !u8::from(interrupt);
+            self.memory.poke_byte(flags, INT_FLAG_ADDR);
             match interrupt {
                 Interrupt::Vblank => self.registers.pc = 0x0040,
                 Interrupt::Lcdc => self.registers.pc = 0x0048,
@@ -176,6 +209,21 @@ impl Cpu {
         }
     }
 
+    /// The interrupts enabled in IE and requested in IF. Bookkeeping of the
+    /// CPU's own, rather than accesses by the program.
+    fn interrupts(&self) -> InterruptController {
+        let mut interrupts = InterruptController::default();
+        interrupts.enable_interrupts(self.memory.peek_byte(INT_ENABLE_ADDR));
+        interrupts.request_interrupts(self.memory.peek_byte(INT_FLAG_ADDR));
+        interrupts
+    }
+
+    /// Request `interrupts` by setting their bits in IF
+    fn request_interrupts(&mut self, interrupts: u8) {
+        let flags = self.memory.peek_byte(INT_FLAG_ADDR) | interrupts;
+        self.memory.poke_byte(flags, INT_FLAG_ADDR);
+    }
+
     /// Increment the timers, requesting an interrupt on overflow
     fn increment_timers(&mut self) {
         if let Some(interrupt) = self
@@ -184,25 +232,107 @@ impl Cpu {
             .get_timer_mut()
             .tick(self.machine_cycles.into())
         {
-            self.interrupts.request_interrupt(interrupt);
+            self.request_interrupts(interrupt.into());
+        }
+    }
+
+    /// Run the serial port for as long as the last instruction took,
+    /// requesting the serial interrupt when a transfer completes
+    fn tick_serial(&mut self) {
+        if let Some(interrupt) = self
+            .memory
+            .get_io_regs_mut()
+            .get_serial_mut()
+            .tick(self.machine_cycles.into())
+        {
+            self.request_interrupts(interrupt.into());
+        }
+    }
+
+    /// Plug a device into the serial port
+    pub fn connect_serial(&mut self, device: Box<dyn SerialDevice>) {
+        self.memory
+            .get_io_regs_mut()
+            .get_serial_mut()
+            .connect(device);
+    }
+
+    /// Run the APU for as long as the last instruction took
+    fn tick_apu(&mut self) {
+        self.memory
+            .get_io_regs_mut()
+            .tick_apu(self.machine_cycles.into());
+    }
+
+    /// Run the PPU for as long as the last instruction took, requesting any
+    /// interrupts it raises
+    fn tick_ppu(&mut self) {
+        let interrupts = self.memory.get_ppu_mut().tick(self.machine_cycles.into());
+        self.request_interrupts(interrupts);
+    }
+
+    /// Shades of the last frame drawn by the PPU, see
+    /// [`Ppu::frame_buffer`](crate::ppu::Ppu::frame_buffer)
+    pub fn frame_buffer(&self) -> &[u8] {
+        self.memory.get_ppu().frame_buffer()
+    }
+
+    /// Request the joypad interrupt if an input line went low since last
+    /// polled, either from a button press or from the game selecting a row
+    fn poll_joypad(&mut self) {
+        if let Some(interrupt) = self
+            .memory
+            .get_io_regs_mut()
+            .get_joypad_mut()
+            .take_interrupt()
+        {
+            self.request_interrupts(interrupt.into());
         }
     }
 
+    /// Press `button` on the joypad
+    pub fn press(&mut self, button: Button) {
+        self.memory.get_io_regs_mut().get_joypad_mut().press(button);
+    }
+
+    /// Release `button` on the joypad
+    pub fn release(&mut self, button: Button) {
+        self.memory
+            .get_io_regs_mut()
+            .get_joypad_mut()
+            .release(button);
+    }
+
+    /// The APU, for collecting samples
+    pub fn apu_mut(&mut self) -> &mut Apu {
+        self.memory.get_io_regs_mut().get_apu_mut()
+    }
+
+    /// Check whether the PPU completed a frame since the last call
+    #[cfg_attr(not(feature = "sdl"), allow(dead_code))]
+    pub fn poll_frame(&mut self) -> bool {
+        self.memory.get_ppu_mut().poll_frame()
+    }
+
+    /// Machine cycles run since reset
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
     pub fn is_stopped(&self) -> bool {
         self.mode == RunningMode::Stop
     }
 
     /// Print method for debugging
+    #[cfg_attr(feature = "sdl", allow(dead_code))]
     pub fn print_status(&self) {
-        let current_instruction = self.read_byte(self.registers.pc);
-        let OpCode(mnemonic, _, size, _) = opcodes::OPCODES[current_instruction as usize];
+        let OpCode(mnemonic, _, size, _) = self.decode(self.registers.pc);
 
-        let argument: Option<Argument>;
-        match size {
-            2 => argument = Some(Argument::Byte(self.read_byte(self.registers.pc + 1))),
-            3 => argument = Some(Argument::Word(self.read_word(self.registers.pc + 1))),
-            _ => argument = None,
-        }
+        let argument = match size {
+            2 => Some(Argument::Byte(self.memory.peek_byte(self.registers.pc + 1))),
+            3 => Some(Argument::Word(self.memory.peek_word(self.registers.pc + 1))),
+            _ => None,
+        };
 
         print!("AF: {:04x}, ", self.registers.af());
         print!("BC: {:04x}, ", self.registers.bc());
@@ -211,22 +341,28 @@ impl Cpu {
         print!("SP: {:04x}, ", self.registers.sp);
         print!("PC: {:04x}, ", self.registers.pc);
         print!("IMF: {}, ", self.interrupt_master_enable as u8);
-        print!("IE: {:05b}, ", self.read_byte(0xFFFF));
-        print!("IF: {:05b} | ", self.read_byte(0xFF0F));
+        print!("IE: {:05b}, ", self.memory.peek_byte(INT_ENABLE_ADDR));
+        print!("IF: {:05b} | ", self.memory.peek_byte(INT_FLAG_ADDR));
         println!("{mnemonic:12} {argument:x?}");
     }
 
     #[allow(dead_code)]
-    /// Step through specific opcode
+    /// Step through specific opcode. Prefixed instructions are given with the
+    /// prefix in the high byte, e.g. `0xCB37` for `SWAP A`.
     pub fn step_op(&mut self, op: usize) {
         self.current_instruction = op as u8;
-        let OpCode(mnemonic, func, size, cycles) = opcodes::OPCODES[op];
+        let OpCode(mnemonic, func, size, cycles) = match op {
+            0xCB00..=0xCBFF => opcodes::CB_OPCODES[op & 0xFF],
+            _ => opcodes::OPCODES[op],
+        };
         match size {
             2 => {
-                self.current_argument = Some(Argument::Byte(self.read_byte(self.registers.pc + 1)))
+                self.current_argument =
+                    Some(Argument::Byte(self.memory.peek_byte(self.registers.pc + 1)))
             }
             3 => {
-                self.current_argument = Some(Argument::Word(self.read_word(self.registers.pc + 1)))
+                self.current_argument =
+                    Some(Argument::Word(self.memory.peek_word(self.registers.pc + 1)))
             }
             _ => self.current_argument = None,
         }
@@ -242,6 +378,40 @@ impl Cpu {
         self.machine_cycles += cycles;
     }
 
+    /// Look up the instruction at a memory address, following the 0xCB prefix
+    /// into the table of prefixed instructions
+    fn decode(&self, addr: u16) -> OpCode<'static> {
+        match self.peek_byte(addr) {
+            opcodes::PREFIX_CB => {
+                opcodes::CB_OPCODES[self.peek_byte(addr.wrapping_add(1)) as usize]
+            }
+            op => opcodes::OPCODES[op as usize],
+        }
+    }
+
+    /// Insert a cartridge, replacing whatever was mapped into the ROM and
+    /// external RAM areas before
+    pub fn load_cartridge(&mut self, cartridge: Box<dyn Cartridge>) {
+        self.memory.load_cartridge(cartridge);
+    }
+
+    /// The inserted cartridge
+    pub fn cartridge(&self) -> &dyn Cartridge {
+        self.memory.get_cartridge()
+    }
+
+    /// Check whether the cartridge's rumble motor was switched on or off
+    /// since the last call. Returns the new state of the motor if it changed.
+    pub fn poll_rumble(&mut self) -> Option<bool> {
+        let rumble = self.memory.get_cartridge().rumble();
+        if rumble == self.rumble {
+            return None;
+        }
+
+        self.rumble = rumble;
+        Some(rumble)
+    }
+
     /// Store a byte at a memory address
     pub fn write_byte(&mut self, byte: u8, addr: u16) {
         self.memory.write_byte(byte, addr);
@@ -258,6 +428,7 @@ impl Cpu {
     }
 
     /// Retrieve a word from a memory address
+    #[allow(dead_code)]
     pub fn read_word(&self, addr: u16) -> u16 {
         self.memory.read_word(addr)
     }
@@ -301,7 +472,7 @@ impl Cpu {
 }
 
 #[derive(Debug, Clone, Default)]
-struct Registers {
+pub struct Registers {
     a: u8,
     b: u8,
     c: u8,
@@ -353,7 +524,7 @@ impl Registers {
 }
 
 #[derive(Debug, Clone, Default)]
-struct FlagRegister {
+pub struct FlagRegister {
     /// Zero flag
     ///
     /// Set if the result of a math operation is zero
@@ -407,3 +578,17 @@ impl FlagRegister {
         self.c = (value & 1 << 4) != 0;
     }
 }
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    /// Reset a CPU with `program` at the entry point
+    pub fn setup(program: &[u8]) -> Cpu {
+        let mut cpu = Cpu::reset();
+        for (i, byte) in program.iter().enumerate() {
+            cpu.write_byte(*byte, 0x0100 + i as u16);
+        }
+        cpu
+    }
+}
@@ -1,5 +1,5 @@
-/// This file is essentially a copy of add.rs, with added lines for adding the
-/// carry bit where appropriate. Should probably be merged with add.rs somehow
+// This file is essentially a copy of add.rs, with added lines for adding the
+// carry bit where appropriate. Should probably be merged with add.rs somehow
 
 macro_rules! adc {
     (a; $src:ident) => {
@@ -0,0 +1,195 @@
+/// Test bit in value, setting Z if the bit is not set
+/// Z 0 1 -
+fn test_bit(cpu: &mut crate::cpu::Cpu, value: u8, bit: u8) {
+    cpu.registers.f.z = value & (1 << bit) == 0;
+    cpu.registers.f.n = false;
+    cpu.registers.f.h = true;
+}
+
+macro_rules! bit {
+    ($name:ident, $bit:literal; $( $reg:ident ),+) => {
+        pub mod $name {
+            $(
+                /// Test bit $bit of register
+                /// Z 0 1 -
+                pub fn $reg(cpu: &mut crate::cpu::Cpu) {
+                    let value = cpu.registers.$reg;
+                    super::test_bit(cpu, value, $bit);
+                }
+             )+
+
+                /// Test bit $bit of value in memory pointed to by HL
+                /// Z 0 1 -
+                pub fn hl_ind(cpu: &mut crate::cpu::Cpu) {
+                    let value = cpu.read_byte(cpu.registers.hl());
+                    super::test_bit(cpu, value, $bit);
+                }
+        }
+    };
+}
+
+macro_rules! res {
+    ($name:ident, $bit:literal; $( $reg:ident ),+) => {
+        pub mod $name {
+            $(
+                /// Reset bit $bit of register
+                /// - - - -
+                pub fn $reg(cpu: &mut crate::cpu::Cpu) {
+                    cpu.registers.$reg &= !(1 << $bit);
+                }
+             )+
+
+                /// Reset bit $bit of value in memory pointed to by HL
+                /// - - - -
+                pub fn hl_ind(cpu: &mut crate::cpu::Cpu) {
+                    let value = cpu.read_byte(cpu.registers.hl());
+                    cpu.write_byte(value & !(1 << $bit), cpu.registers.hl());
+                }
+        }
+    };
+}
+
+macro_rules! set {
+    ($name:ident, $bit:literal; $( $reg:ident ),+) => {
+        pub mod $name {
+            $(
+                /// Set bit $bit of register
+                /// - - - -
+                pub fn $reg(cpu: &mut crate::cpu::Cpu) {
+                    cpu.registers.$reg |= 1 << $bit;
+                }
+             )+
+
+                /// Set bit $bit of value in memory pointed to by HL
+                /// - - - -
+                pub fn hl_ind(cpu: &mut crate::cpu::Cpu) {
+                    let value = cpu.read_byte(cpu.registers.hl());
+                    cpu.write_byte(value | 1 << $bit, cpu.registers.hl());
+                }
+        }
+    };
+}
+
+bit!(bit_0, 0; b, c, d, e, h, l, a);
+bit!(bit_1, 1; b, c, d, e, h, l, a);
+bit!(bit_2, 2; b, c, d, e, h, l, a);
+bit!(bit_3, 3; b, c, d, e, h, l, a);
+bit!(bit_4, 4; b, c, d, e, h, l, a);
+bit!(bit_5, 5; b, c, d, e, h, l, a);
+bit!(bit_6, 6; b, c, d, e, h, l, a);
+bit!(bit_7, 7; b, c, d, e, h, l, a);
+
+res!(res_0, 0; b, c, d, e, h, l, a);
+res!(res_1, 1; b, c, d, e, h, l, a);
+res!(res_2, 2; b, c, d, e, h, l, a);
+res!(res_3, 3; b, c, d, e, h, l, a);
+res!(res_4, 4; b, c, d, e, h, l, a);
+res!(res_5, 5; b, c, d, e, h, l, a);
+res!(res_6, 6; b, c, d, e, h, l, a);
+res!(res_7, 7; b, c, d, e, h, l, a);
+
+set!(set_0, 0; b, c, d, e, h, l, a);
+set!(set_1, 1; b, c, d, e, h, l, a);
+set!(set_2, 2; b, c, d, e, h, l, a);
+set!(set_3, 3; b, c, d, e, h, l, a);
+set!(set_4, 4; b, c, d, e, h, l, a);
+set!(set_5, 5; b, c, d, e, h, l, a);
+set!(set_6, 6; b, c, d, e, h, l, a);
+set!(set_7, 7; b, c, d, e, h, l, a);
+
+#[cfg(test)]
+mod test {
+    macro_rules! gen_bit_tests {
+        ($bit:literal, $bit_op:ident, $res_op:ident, $set_op:ident; $( $reg:ident ),+) => {
+            mod $bit_op {
+                $(
+                    #[test]
+                    fn $reg() {
+                        let mut cpu = crate::cpu::Cpu::reset();
+                        cpu.registers.f.set(0);
+                        cpu.registers.f.c = true;
+
+                        cpu.registers.$reg = !(1 << $bit);
+                        super::super::$bit_op::$reg(&mut cpu);
+                        assert!(cpu.registers.f.z);
+                        assert!(!cpu.registers.f.n);
+                        assert!(cpu.registers.f.h);
+                        assert!(cpu.registers.f.c);
+
+                        cpu.registers.$reg = 1 << $bit;
+                        super::super::$bit_op::$reg(&mut cpu);
+                        assert!(!cpu.registers.f.z);
+                    }
+                 )+
+
+                    #[test]
+                    fn hl_ind() {
+                        let mut cpu = crate::cpu::Cpu::reset();
+                        cpu.registers.put_hl(0xC000);
+                        cpu.write_byte(!(1 << $bit), cpu.registers.hl());
+                        super::super::$bit_op::hl_ind(&mut cpu);
+                        assert!(cpu.registers.f.z);
+
+                        cpu.write_byte(1 << $bit, cpu.registers.hl());
+                        super::super::$bit_op::hl_ind(&mut cpu);
+                        assert!(!cpu.registers.f.z);
+                    }
+            }
+
+            mod $res_op {
+                $(
+                    #[test]
+                    fn $reg() {
+                        let mut cpu = crate::cpu::Cpu::reset();
+                        cpu.registers.f.set(0xF0);
+                        cpu.registers.$reg = 0xFF;
+                        super::super::$res_op::$reg(&mut cpu);
+                        assert_eq!(cpu.registers.$reg, !(1 << $bit));
+                        assert_eq!(cpu.registers.f.value(), 0xF0);
+                    }
+                 )+
+
+                    #[test]
+                    fn hl_ind() {
+                        let mut cpu = crate::cpu::Cpu::reset();
+                        cpu.registers.put_hl(0xC000);
+                        cpu.write_byte(0xFF, cpu.registers.hl());
+                        super::super::$res_op::hl_ind(&mut cpu);
+                        assert_eq!(cpu.read_byte(0xC000), !(1 << $bit));
+                    }
+            }
+
+            mod $set_op {
+                $(
+                    #[test]
+                    fn $reg() {
+                        let mut cpu = crate::cpu::Cpu::reset();
+                        cpu.registers.f.set(0);
+                        cpu.registers.$reg = 0x00;
+                        super::super::$set_op::$reg(&mut cpu);
+                        assert_eq!(cpu.registers.$reg, 1 << $bit);
+                        assert_eq!(cpu.registers.f.value(), 0);
+                    }
+                 )+
+
+                    #[test]
+                    fn hl_ind() {
+                        let mut cpu = crate::cpu::Cpu::reset();
+                        cpu.registers.put_hl(0xC000);
+                        cpu.write_byte(0x00, cpu.registers.hl());
+                        super::super::$set_op::hl_ind(&mut cpu);
+                        assert_eq!(cpu.read_byte(0xC000), 1 << $bit);
+                    }
+            }
+        };
+    }
+
+    gen_bit_tests!(0, bit_0, res_0, set_0; b, c, d, e, h, l, a);
+    gen_bit_tests!(1, bit_1, res_1, set_1; b, c, d, e, h, l, a);
+    gen_bit_tests!(2, bit_2, res_2, set_2; b, c, d, e, h, l, a);
+    gen_bit_tests!(3, bit_3, res_3, set_3; b, c, d, e, h, l, a);
+    gen_bit_tests!(4, bit_4, res_4, set_4; b, c, d, e, h, l, a);
+    gen_bit_tests!(5, bit_5, res_5, set_5; b, c, d, e, h, l, a);
+    gen_bit_tests!(6, bit_6, res_6, set_6; b, c, d, e, h, l, a);
+    gen_bit_tests!(7, bit_7, res_7, set_7; b, c, d, e, h, l, a);
+}
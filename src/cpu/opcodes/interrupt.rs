@@ -13,7 +13,7 @@ pub fn stop(cpu: &mut Cpu) {
 pub fn halt(cpu: &mut Cpu) {
     if cpu.interrupt_master_enable {
         cpu.mode = RunningMode::HaltImeSet;
-    } else if !cpu.interrupts.interrupts_pending() {
+    } else if !cpu.interrupts().interrupts_pending() {
         cpu.mode = RunningMode::HaltImeClear;
     } else {
         cpu.mode = RunningMode::HaltBug;
@@ -43,6 +43,7 @@ pub fn reti(cpu: &mut Cpu) {
 #[cfg(test)]
 mod test {
     use crate::cpu::{interrupts::Interrupt, Cpu, RunningMode};
+    use crate::memory::map::{INT_ENABLE_ADDR, INT_FLAG_ADDR};
 
     #[test]
     fn ei() {
@@ -50,8 +51,8 @@ mod test {
         cpu.registers.a = 0;
         cpu.write_byte(0x3C, cpu.registers.pc); // INC A
         cpu.write_byte(0x3C, cpu.registers.pc + 1);
-        cpu.interrupts.request_interrupt(Interrupt::Vblank);
-        cpu.interrupts.enable_interrupt(Interrupt::Vblank);
+        cpu.request_interrupts(Interrupt::Vblank.into());
+        cpu.write_byte(Interrupt::Vblank.into(), INT_ENABLE_ADDR);
         cpu.step();
         assert_eq!(cpu.registers.a, 1);
         assert_ne!(cpu.registers.pc, 0x41);
@@ -69,8 +70,8 @@ mod test {
         let mut cpu = Cpu::reset();
         cpu.registers.a = 0;
         cpu.write_byte(0x3C, cpu.registers.pc); // INC A
-        cpu.interrupts.request_interrupt(Interrupt::Vblank);
-        cpu.interrupts.enable_interrupt(Interrupt::Vblank);
+        cpu.request_interrupts(Interrupt::Vblank.into());
+        cpu.write_byte(Interrupt::Vblank.into(), INT_ENABLE_ADDR);
         super::ei(&mut cpu);
         cpu.step();
         assert_ne!(cpu.registers.a, 1);
@@ -91,8 +92,8 @@ mod test {
         cpu.write_byte(0x3C, cpu.registers.pc + 1);
 
         cpu.write_byte(0xD9, 0x41); // RETI
-        cpu.interrupts.request_interrupt(Interrupt::Vblank);
-        cpu.interrupts.enable_interrupt(Interrupt::Vblank);
+        cpu.request_interrupts(Interrupt::Vblank.into());
+        cpu.write_byte(Interrupt::Vblank.into(), INT_ENABLE_ADDR);
         super::ei(&mut cpu);
 
         cpu.step(); // NOP in ISR
@@ -110,7 +111,7 @@ mod test {
         cpu.write_byte(0x76, cpu.registers.pc); // HALT
         cpu.write_byte(0x3C, cpu.registers.pc + 1); // INC A
         cpu.write_byte(0xD9, 0x41); // RETI
-        cpu.interrupts.enable_interrupt(Interrupt::Vblank);
+        cpu.write_byte(Interrupt::Vblank.into(), INT_ENABLE_ADDR);
 
         // Go into HALT mode
         cpu.step();
@@ -123,7 +124,7 @@ mod test {
             assert_eq!(cpu.registers.a, 0x0);
         }
 
-        cpu.interrupts.request_interrupt(Interrupt::Vblank);
+        cpu.request_interrupts(Interrupt::Vblank.into());
         cpu.step(); // NOP in ISR
         assert_eq!(cpu.registers.pc, 0x41);
         assert_eq!(cpu.mode, RunningMode::Running);
@@ -135,6 +136,52 @@ mod test {
         assert_eq!(cpu.registers.a, 0x1);
     }
 
+    #[test]
+    fn halt_until_vblank() {
+        let mut cpu = Cpu::reset();
+        cpu.interrupt_master_enable = true;
+        cpu.write_byte(0x76, cpu.registers.pc); // HALT
+        cpu.write_byte(Interrupt::Vblank.into(), INT_ENABLE_ADDR);
+
+        cpu.step();
+        assert_eq!(cpu.mode, RunningMode::HaltImeSet);
+
+        // The PPU keeps running while halted, and wakes the CPU up at VBlank
+        for _ in 0..144 * 114 {
+            cpu.step();
+            if cpu.mode == RunningMode::Running {
+                break;
+            }
+        }
+        assert_eq!(cpu.mode, RunningMode::Running);
+        assert_eq!(cpu.registers.pc, 0x41);
+        assert_eq!(cpu.read_byte(0xFF44), 144);
+    }
+
+    #[test]
+    fn vblank_sets_if() {
+        let mut cpu = Cpu::reset();
+        while cpu.read_byte(0xFF44) != 144 {
+            cpu.step();
+        }
+        assert_eq!(cpu.read_byte(INT_FLAG_ADDR) & 0x01, 0x01);
+    }
+
+    #[test]
+    fn clearing_if_cancels_interrupt() {
+        let mut cpu = Cpu::reset();
+        cpu.write_byte(Interrupt::Vblank.into(), INT_ENABLE_ADDR);
+        super::ei(&mut cpu);
+        while cpu.read_byte(INT_FLAG_ADDR) == 0 {
+            cpu.step();
+        }
+
+        cpu.write_byte(0x00, INT_FLAG_ADDR);
+        let pc = cpu.registers.pc;
+        cpu.step(); // NOP
+        assert_eq!(cpu.registers.pc, pc + 1);
+    }
+
     #[test]
     fn halt_ime_clear() {
         let mut cpu = Cpu::reset();
@@ -155,8 +202,8 @@ mod test {
             assert_eq!(cpu.mode, RunningMode::HaltImeClear);
         }
 
-        cpu.interrupts.request_interrupt(Interrupt::Vblank);
-        assert!(cpu.interrupts.interrupts_requested());
+        cpu.request_interrupts(Interrupt::Vblank.into());
+        assert!(cpu.interrupts().interrupts_requested());
         cpu.step(); // Would be NOP in ISR, is now just INC A
         assert_eq!(cpu.mode, RunningMode::Running);
         assert_eq!(cpu.registers.pc, 0x102);
@@ -170,8 +217,8 @@ mod test {
 
         // Halt bug combination
         cpu.interrupt_master_enable = false;
-        cpu.interrupts.request_interrupt(Interrupt::Vblank);
-        cpu.interrupts.enable_interrupt(Interrupt::Vblank);
+        cpu.request_interrupts(Interrupt::Vblank.into());
+        cpu.write_byte(Interrupt::Vblank.into(), INT_ENABLE_ADDR);
 
         cpu.write_byte(0x76, cpu.registers.pc); // HALT
         cpu.write_byte(0x3C, cpu.registers.pc + 1); // INC A
@@ -68,7 +68,7 @@ pub mod a {
     /// Load A with value at memory pointed to by immediate argument
     /// - - - -
     pub fn addr(cpu: &mut crate::cpu::Cpu) {
-        let addr = cpu.get_word_argument() as u16;
+        let addr = cpu.get_word_argument();
         cpu.registers.a = cpu.read_byte(addr);
     }
 
@@ -5,7 +5,7 @@ macro_rules! op {
                 /// Bitwise $operation A with $reg
                 /// Z 0 $set_h 0
                 pub fn $reg(cpu: &mut crate::cpu::Cpu) {
-                    cpu.registers.a = cpu.registers.a $op_symbol cpu.registers.$reg;
+                    cpu.registers.a $op_symbol cpu.registers.$reg;
 
                     cpu.registers.f.z = cpu.registers.a == 0;
                     cpu.registers.f.n = false;
@@ -17,7 +17,7 @@ macro_rules! op {
                 /// Bitwise $operation A with value in memory pointed to by HL
                 /// Z 0 $set_h 0
                 pub fn hl_ind(cpu: &mut crate::cpu::Cpu) {
-                    cpu.registers.a = cpu.registers.a $op_symbol cpu.read_byte(cpu.registers.hl());
+                    cpu.registers.a $op_symbol cpu.read_byte(cpu.registers.hl());
 
                     cpu.registers.f.z = cpu.registers.a == 0;
                     cpu.registers.f.n = false;
@@ -28,7 +28,7 @@ macro_rules! op {
                 /// Bitwise $operation A with immediate value
                 /// Z 0 $set_h 0
                 pub fn imm(cpu: &mut crate::cpu::Cpu) {
-                    cpu.registers.a = cpu.registers.a $op_symbol cpu.get_byte_argument();
+                    cpu.registers.a $op_symbol cpu.get_byte_argument();
 
                     cpu.registers.f.z = cpu.registers.a == 0;
                     cpu.registers.f.n = false;
@@ -39,9 +39,9 @@ macro_rules! op {
     };
 }
 
-op!(and, &, false, b, c, d, e, h, l, a);
-op!(or, |, false, b, c, d, e, h, l, a);
-op!(xor, ^, true, b, c, d, e, h, l, a);
+op!(and, &=, false, b, c, d, e, h, l, a);
+op!(or, |=, false, b, c, d, e, h, l, a);
+op!(xor, ^=, true, b, c, d, e, h, l, a);
 
 macro_rules! cp {
     ($( $reg:ident ),+) => {
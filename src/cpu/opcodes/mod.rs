@@ -1,6 +1,7 @@
 use crate::cpu::Cpu;
 mod adc;
 mod add;
+mod bit;
 mod call;
 mod dec;
 mod inc;
@@ -14,6 +15,7 @@ mod ret;
 mod rotate;
 mod rst;
 mod sbc;
+mod shift;
 mod stack;
 mod sub;
 
@@ -35,6 +37,10 @@ impl From<u16> for Argument {
     }
 }
 
+/// First byte of the instructions in [`CB_OPCODES`]
+pub const PREFIX_CB: u8 = 0xCB;
+
+#[derive(Clone, Copy)]
 pub struct OpCode<'a>(
     /// Mnemonic
     pub &'a str,
@@ -276,7 +282,7 @@ pub const OPCODES: [OpCode; 256] = [
     OpCode("RET Z"        , ret::z,               1, 2), // 5 if branch taken
     OpCode("RET"          , ret::ret,             1, 4),
     OpCode("JP Z, a16"    , jp::z,                3, 3), // 4 if branch taken
-    OpCode("UDF"          , undefined,            1, 1),
+    OpCode("PREFIX CB"    , undefined,            2, 1), // See CB_OPCODES
     OpCode("CALL Z, a16"  , call::z,              3, 3), // 6 if branch taken
     OpCode("CALL a16"     , call::a16,            3, 6),
     OpCode("ADC A, d8"    , adc::a::imm,          2, 2),
@@ -337,6 +343,298 @@ pub const OPCODES: [OpCode; 256] = [
     OpCode("RST 7"        , rst::rst_7,           1, 1),
 ];
 
+/// Instructions prefixed by 0xCB, indexed by the byte following the prefix
+#[rustfmt::skip]
+pub const CB_OPCODES: [OpCode; 256] = [
+    // 0x0_
+    OpCode("RLC B"        , rotate::rlc::b,       2, 2),
+    OpCode("RLC C"        , rotate::rlc::c,       2, 2),
+    OpCode("RLC D"        , rotate::rlc::d,       2, 2),
+    OpCode("RLC E"        , rotate::rlc::e,       2, 2),
+    OpCode("RLC H"        , rotate::rlc::h,       2, 2),
+    OpCode("RLC L"        , rotate::rlc::l,       2, 2),
+    OpCode("RLC (HL)"     , rotate::rlc::hl_ind,  2, 4),
+    OpCode("RLC A"        , rotate::rlc::a,       2, 2),
+    OpCode("RRC B"        , rotate::rrc::b,       2, 2),
+    OpCode("RRC C"        , rotate::rrc::c,       2, 2),
+    OpCode("RRC D"        , rotate::rrc::d,       2, 2),
+    OpCode("RRC E"        , rotate::rrc::e,       2, 2),
+    OpCode("RRC H"        , rotate::rrc::h,       2, 2),
+    OpCode("RRC L"        , rotate::rrc::l,       2, 2),
+    OpCode("RRC (HL)"     , rotate::rrc::hl_ind,  2, 4),
+    OpCode("RRC A"        , rotate::rrc::a,       2, 2),
+
+    // 0x1_
+    OpCode("RL B"         , rotate::rl::b,        2, 2),
+    OpCode("RL C"         , rotate::rl::c,        2, 2),
+    OpCode("RL D"         , rotate::rl::d,        2, 2),
+    OpCode("RL E"         , rotate::rl::e,        2, 2),
+    OpCode("RL H"         , rotate::rl::h,        2, 2),
+    OpCode("RL L"         , rotate::rl::l,        2, 2),
+    OpCode("RL (HL)"      , rotate::rl::hl_ind,   2, 4),
+    OpCode("RL A"         , rotate::rl::a,        2, 2),
+    OpCode("RR B"         , rotate::rr::b,        2, 2),
+    OpCode("RR C"         , rotate::rr::c,        2, 2),
+    OpCode("RR D"         , rotate::rr::d,        2, 2),
+    OpCode("RR E"         , rotate::rr::e,        2, 2),
+    OpCode("RR H"         , rotate::rr::h,        2, 2),
+    OpCode("RR L"         , rotate::rr::l,        2, 2),
+    OpCode("RR (HL)"      , rotate::rr::hl_ind,   2, 4),
+    OpCode("RR A"         , rotate::rr::a,        2, 2),
+
+    // 0x2_
+    OpCode("SLA B"        , shift::sla::b,        2, 2),
+    OpCode("SLA C"        , shift::sla::c,        2, 2),
+    OpCode("SLA D"        , shift::sla::d,        2, 2),
+    OpCode("SLA E"        , shift::sla::e,        2, 2),
+    OpCode("SLA H"        , shift::sla::h,        2, 2),
+    OpCode("SLA L"        , shift::sla::l,        2, 2),
+    OpCode("SLA (HL)"     , shift::sla::hl_ind,   2, 4),
+    OpCode("SLA A"        , shift::sla::a,        2, 2),
+    OpCode("SRA B"        , shift::sra::b,        2, 2),
+    OpCode("SRA C"        , shift::sra::c,        2, 2),
+    OpCode("SRA D"        , shift::sra::d,        2, 2),
+    OpCode("SRA E"        , shift::sra::e,        2, 2),
+    OpCode("SRA H"        , shift::sra::h,        2, 2),
+    OpCode("SRA L"        , shift::sra::l,        2, 2),
+    OpCode("SRA (HL)"     , shift::sra::hl_ind,   2, 4),
+    OpCode("SRA A"        , shift::sra::a,        2, 2),
+
+    // 0x3_
+    OpCode("SWAP B"       , shift::swap::b,       2, 2),
+    OpCode("SWAP C"       , shift::swap::c,       2, 2),
+    OpCode("SWAP D"       , shift::swap::d,       2, 2),
+    OpCode("SWAP E"       , shift::swap::e,       2, 2),
+    OpCode("SWAP H"       , shift::swap::h,       2, 2),
+    OpCode("SWAP L"       , shift::swap::l,       2, 2),
+    OpCode("SWAP (HL)"    , shift::swap::hl_ind,  2, 4),
+    OpCode("SWAP A"       , shift::swap::a,       2, 2),
+    OpCode("SRL B"        , shift::srl::b,        2, 2),
+    OpCode("SRL C"        , shift::srl::c,        2, 2),
+    OpCode("SRL D"        , shift::srl::d,        2, 2),
+    OpCode("SRL E"        , shift::srl::e,        2, 2),
+    OpCode("SRL H"        , shift::srl::h,        2, 2),
+    OpCode("SRL L"        , shift::srl::l,        2, 2),
+    OpCode("SRL (HL)"     , shift::srl::hl_ind,   2, 4),
+    OpCode("SRL A"        , shift::srl::a,        2, 2),
+
+    // 0x4_
+    OpCode("BIT 0, B"     , bit::bit_0::b,        2, 2),
+    OpCode("BIT 0, C"     , bit::bit_0::c,        2, 2),
+    OpCode("BIT 0, D"     , bit::bit_0::d,        2, 2),
+    OpCode("BIT 0, E"     , bit::bit_0::e,        2, 2),
+    OpCode("BIT 0, H"     , bit::bit_0::h,        2, 2),
+    OpCode("BIT 0, L"     , bit::bit_0::l,        2, 2),
+    OpCode("BIT 0, (HL)"  , bit::bit_0::hl_ind,   2, 3),
+    OpCode("BIT 0, A"     , bit::bit_0::a,        2, 2),
+    OpCode("BIT 1, B"     , bit::bit_1::b,        2, 2),
+    OpCode("BIT 1, C"     , bit::bit_1::c,        2, 2),
+    OpCode("BIT 1, D"     , bit::bit_1::d,        2, 2),
+    OpCode("BIT 1, E"     , bit::bit_1::e,        2, 2),
+    OpCode("BIT 1, H"     , bit::bit_1::h,        2, 2),
+    OpCode("BIT 1, L"     , bit::bit_1::l,        2, 2),
+    OpCode("BIT 1, (HL)"  , bit::bit_1::hl_ind,   2, 3),
+    OpCode("BIT 1, A"     , bit::bit_1::a,        2, 2),
+
+    // 0x5_
+    OpCode("BIT 2, B"     , bit::bit_2::b,        2, 2),
+    OpCode("BIT 2, C"     , bit::bit_2::c,        2, 2),
+    OpCode("BIT 2, D"     , bit::bit_2::d,        2, 2),
+    OpCode("BIT 2, E"     , bit::bit_2::e,        2, 2),
+    OpCode("BIT 2, H"     , bit::bit_2::h,        2, 2),
+    OpCode("BIT 2, L"     , bit::bit_2::l,        2, 2),
+    OpCode("BIT 2, (HL)"  , bit::bit_2::hl_ind,   2, 3),
+    OpCode("BIT 2, A"     , bit::bit_2::a,        2, 2),
+    OpCode("BIT 3, B"     , bit::bit_3::b,        2, 2),
+    OpCode("BIT 3, C"     , bit::bit_3::c,        2, 2),
+    OpCode("BIT 3, D"     , bit::bit_3::d,        2, 2),
+    OpCode("BIT 3, E"     , bit::bit_3::e,        2, 2),
+    OpCode("BIT 3, H"     , bit::bit_3::h,        2, 2),
+    OpCode("BIT 3, L"     , bit::bit_3::l,        2, 2),
+    OpCode("BIT 3, (HL)"  , bit::bit_3::hl_ind,   2, 3),
+    OpCode("BIT 3, A"     , bit::bit_3::a,        2, 2),
+
+    // 0x6_
+    OpCode("BIT 4, B"     , bit::bit_4::b,        2, 2),
+    OpCode("BIT 4, C"     , bit::bit_4::c,        2, 2),
+    OpCode("BIT 4, D"     , bit::bit_4::d,        2, 2),
+    OpCode("BIT 4, E"     , bit::bit_4::e,        2, 2),
+    OpCode("BIT 4, H"     , bit::bit_4::h,        2, 2),
+    OpCode("BIT 4, L"     , bit::bit_4::l,        2, 2),
+    OpCode("BIT 4, (HL)"  , bit::bit_4::hl_ind,   2, 3),
+    OpCode("BIT 4, A"     , bit::bit_4::a,        2, 2),
+    OpCode("BIT 5, B"     , bit::bit_5::b,        2, 2),
+    OpCode("BIT 5, C"     , bit::bit_5::c,        2, 2),
+    OpCode("BIT 5, D"     , bit::bit_5::d,        2, 2),
+    OpCode("BIT 5, E"     , bit::bit_5::e,        2, 2),
+    OpCode("BIT 5, H"     , bit::bit_5::h,        2, 2),
+    OpCode("BIT 5, L"     , bit::bit_5::l,        2, 2),
+    OpCode("BIT 5, (HL)"  , bit::bit_5::hl_ind,   2, 3),
+    OpCode("BIT 5, A"     , bit::bit_5::a,        2, 2),
+
+    // 0x7_
+    OpCode("BIT 6, B"     , bit::bit_6::b,        2, 2),
+    OpCode("BIT 6, C"     , bit::bit_6::c,        2, 2),
+    OpCode("BIT 6, D"     , bit::bit_6::d,        2, 2),
+    OpCode("BIT 6, E"     , bit::bit_6::e,        2, 2),
+    OpCode("BIT 6, H"     , bit::bit_6::h,        2, 2),
+    OpCode("BIT 6, L"     , bit::bit_6::l,        2, 2),
+    OpCode("BIT 6, (HL)"  , bit::bit_6::hl_ind,   2, 3),
+    OpCode("BIT 6, A"     , bit::bit_6::a,        2, 2),
+    OpCode("BIT 7, B"     , bit::bit_7::b,        2, 2),
+    OpCode("BIT 7, C"     , bit::bit_7::c,        2, 2),
+    OpCode("BIT 7, D"     , bit::bit_7::d,        2, 2),
+    OpCode("BIT 7, E"     , bit::bit_7::e,        2, 2),
+    OpCode("BIT 7, H"     , bit::bit_7::h,        2, 2),
+    OpCode("BIT 7, L"     , bit::bit_7::l,        2, 2),
+    OpCode("BIT 7, (HL)"  , bit::bit_7::hl_ind,   2, 3),
+    OpCode("BIT 7, A"     , bit::bit_7::a,        2, 2),
+
+    // 0x8_
+    OpCode("RES 0, B"     , bit::res_0::b,        2, 2),
+    OpCode("RES 0, C"     , bit::res_0::c,        2, 2),
+    OpCode("RES 0, D"     , bit::res_0::d,        2, 2),
+    OpCode("RES 0, E"     , bit::res_0::e,        2, 2),
+    OpCode("RES 0, H"     , bit::res_0::h,        2, 2),
+    OpCode("RES 0, L"     , bit::res_0::l,        2, 2),
+    OpCode("RES 0, (HL)"  , bit::res_0::hl_ind,   2, 4),
+    OpCode("RES 0, A"     , bit::res_0::a,        2, 2),
+    OpCode("RES 1, B"     , bit::res_1::b,        2, 2),
+    OpCode("RES 1, C"     , bit::res_1::c,        2, 2),
+    OpCode("RES 1, D"     , bit::res_1::d,        2, 2),
+    OpCode("RES 1, E"     , bit::res_1::e,        2, 2),
+    OpCode("RES 1, H"     , bit::res_1::h,        2, 2),
+    OpCode("RES 1, L"     , bit::res_1::l,        2, 2),
+    OpCode("RES 1, (HL)"  , bit::res_1::hl_ind,   2, 4),
+    OpCode("RES 1, A"     , bit::res_1::a,        2, 2),
+
+    // 0x9_
+    OpCode("RES 2, B"     , bit::res_2::b,        2, 2),
+    OpCode("RES 2, C"     , bit::res_2::c,        2, 2),
+    OpCode("RES 2, D"     , bit::res_2::d,        2, 2),
+    OpCode("RES 2, E"     , bit::res_2::e,        2, 2),
+    OpCode("RES 2, H"     , bit::res_2::h,        2, 2),
+    OpCode("RES 2, L"     , bit::res_2::l,        2, 2),
+    OpCode("RES 2, (HL)"  , bit::res_2::hl_ind,   2, 4),
+    OpCode("RES 2, A"     , bit::res_2::a,        2, 2),
+    OpCode("RES 3, B"     , bit::res_3::b,        2, 2),
+    OpCode("RES 3, C"     , bit::res_3::c,        2, 2),
+    OpCode("RES 3, D"     , bit::res_3::d,        2, 2),
+    OpCode("RES 3, E"     , bit::res_3::e,        2, 2),
+    OpCode("RES 3, H"     , bit::res_3::h,        2, 2),
+    OpCode("RES 3, L"     , bit::res_3::l,        2, 2),
+    OpCode("RES 3, (HL)"  , bit::res_3::hl_ind,   2, 4),
+    OpCode("RES 3, A"     , bit::res_3::a,        2, 2),
+
+    // 0xA_
+    OpCode("RES 4, B"     , bit::res_4::b,        2, 2),
+    OpCode("RES 4, C"     , bit::res_4::c,        2, 2),
+    OpCode("RES 4, D"     , bit::res_4::d,        2, 2),
+    OpCode("RES 4, E"     , bit::res_4::e,        2, 2),
+    OpCode("RES 4, H"     , bit::res_4::h,        2, 2),
+    OpCode("RES 4, L"     , bit::res_4::l,        2, 2),
+    OpCode("RES 4, (HL)"  , bit::res_4::hl_ind,   2, 4),
+    OpCode("RES 4, A"     , bit::res_4::a,        2, 2),
+    OpCode("RES 5, B"     , bit::res_5::b,        2, 2),
+    OpCode("RES 5, C"     , bit::res_5::c,        2, 2),
+    OpCode("RES 5, D"     , bit::res_5::d,        2, 2),
+    OpCode("RES 5, E"     , bit::res_5::e,        2, 2),
+    OpCode("RES 5, H"     , bit::res_5::h,        2, 2),
+    OpCode("RES 5, L"     , bit::res_5::l,        2, 2),
+    OpCode("RES 5, (HL)"  , bit::res_5::hl_ind,   2, 4),
+    OpCode("RES 5, A"     , bit::res_5::a,        2, 2),
+
+    // 0xB_
+    OpCode("RES 6, B"     , bit::res_6::b,        2, 2),
+    OpCode("RES 6, C"     , bit::res_6::c,        2, 2),
+    OpCode("RES 6, D"     , bit::res_6::d,        2, 2),
+    OpCode("RES 6, E"     , bit::res_6::e,        2, 2),
+    OpCode("RES 6, H"     , bit::res_6::h,        2, 2),
+    OpCode("RES 6, L"     , bit::res_6::l,        2, 2),
+    OpCode("RES 6, (HL)"  , bit::res_6::hl_ind,   2, 4),
+    OpCode("RES 6, A"     , bit::res_6::a,        2, 2),
+    OpCode("RES 7, B"     , bit::res_7::b,        2, 2),
+    OpCode("RES 7, C"     , bit::res_7::c,        2, 2),
+    OpCode("RES 7, D"     , bit::res_7::d,        2, 2),
+    OpCode("RES 7, E"     , bit::res_7::e,        2, 2),
+    OpCode("RES 7, H"     , bit::res_7::h,        2, 2),
+    OpCode("RES 7, L"     , bit::res_7::l,        2, 2),
+    OpCode("RES 7, (HL)"  , bit::res_7::hl_ind,   2, 4),
+    OpCode("RES 7, A"     , bit::res_7::a,        2, 2),
+
+    // 0xC_
+    OpCode("SET 0, B"     , bit::set_0::b,        2, 2),
+    OpCode("SET 0, C"     , bit::set_0::c,        2, 2),
+    OpCode("SET 0, D"     , bit::set_0::d,        2, 2),
+    OpCode("SET 0, E"     , bit::set_0::e,        2, 2),
+    OpCode("SET 0, H"     , bit::set_0::h,        2, 2),
+    OpCode("SET 0, L"     , bit::set_0::l,        2, 2),
+    OpCode("SET 0, (HL)"  , bit::set_0::hl_ind,   2, 4),
+    OpCode("SET 0, A"     , bit::set_0::a,        2, 2),
+    OpCode("SET 1, B"     , bit::set_1::b,        2, 2),
+    OpCode("SET 1, C"     , bit::set_1::c,        2, 2),
+    OpCode("SET 1, D"     , bit::set_1::d,        2, 2),
+    OpCode("SET 1, E"     , bit::set_1::e,        2, 2),
+    OpCode("SET 1, H"     , bit::set_1::h,        2, 2),
+    OpCode("SET 1, L"     , bit::set_1::l,        2, 2),
+    OpCode("SET 1, (HL)"  , bit::set_1::hl_ind,   2, 4),
+    OpCode("SET 1, A"     , bit::set_1::a,        2, 2),
+
+    // 0xD_
+    OpCode("SET 2, B"     , bit::set_2::b,        2, 2),
+    OpCode("SET 2, C"     , bit::set_2::c,        2, 2),
+    OpCode("SET 2, D"     , bit::set_2::d,        2, 2),
+    OpCode("SET 2, E"     , bit::set_2::e,        2, 2),
+    OpCode("SET 2, H"     , bit::set_2::h,        2, 2),
+    OpCode("SET 2, L"     , bit::set_2::l,        2, 2),
+    OpCode("SET 2, (HL)"  , bit::set_2::hl_ind,   2, 4),
+    OpCode("SET 2, A"     , bit::set_2::a,        2, 2),
+    OpCode("SET 3, B"     , bit::set_3::b,        2, 2),
+    OpCode("SET 3, C"     , bit::set_3::c,        2, 2),
+    OpCode("SET 3, D"     , bit::set_3::d,        2, 2),
+    OpCode("SET 3, E"     , bit::set_3::e,        2, 2),
+    OpCode("SET 3, H"     , bit::set_3::h,        2, 2),
+    OpCode("SET 3, L"     , bit::set_3::l,        2, 2),
+    OpCode("SET 3, (HL)"  , bit::set_3::hl_ind,   2, 4),
+    OpCode("SET 3, A"     , bit::set_3::a,        2, 2),
+
+    // 0xE_
+    OpCode("SET 4, B"     , bit::set_4::b,        2, 2),
+    OpCode("SET 4, C"     , bit::set_4::c,        2, 2),
+    OpCode("SET 4, D"     , bit::set_4::d,        2, 2),
+    OpCode("SET 4, E"     , bit::set_4::e,        2, 2),
+    OpCode("SET 4, H"     , bit::set_4::h,        2, 2),
+    OpCode("SET 4, L"     , bit::set_4::l,        2, 2),
+    OpCode("SET 4, (HL)"  , bit::set_4::hl_ind,   2, 4),
+    OpCode("SET 4, A"     , bit::set_4::a,        2, 2),
+    OpCode("SET 5, B"     , bit::set_5::b,        2, 2),
+    OpCode("SET 5, C"     , bit::set_5::c,        2, 2),
+    OpCode("SET 5, D"     , bit::set_5::d,        2, 2),
+    OpCode("SET 5, E"     , bit::set_5::e,        2, 2),
+    OpCode("SET 5, H"     , bit::set_5::h,        2, 2),
+    OpCode("SET 5, L"     , bit::set_5::l,        2, 2),
+    OpCode("SET 5, (HL)"  , bit::set_5::hl_ind,   2, 4),
+    OpCode("SET 5, A"     , bit::set_5::a,        2, 2),
+
+    // 0xF_
+    OpCode("SET 6, B"     , bit::set_6::b,        2, 2),
+    OpCode("SET 6, C"     , bit::set_6::c,        2, 2),
+    OpCode("SET 6, D"     , bit::set_6::d,        2, 2),
+    OpCode("SET 6, E"     , bit::set_6::e,        2, 2),
+    OpCode("SET 6, H"     , bit::set_6::h,        2, 2),
+    OpCode("SET 6, L"     , bit::set_6::l,        2, 2),
+    OpCode("SET 6, (HL)"  , bit::set_6::hl_ind,   2, 4),
+    OpCode("SET 6, A"     , bit::set_6::a,        2, 2),
+    OpCode("SET 7, B"     , bit::set_7::b,        2, 2),
+    OpCode("SET 7, C"     , bit::set_7::c,        2, 2),
+    OpCode("SET 7, D"     , bit::set_7::d,        2, 2),
+    OpCode("SET 7, E"     , bit::set_7::e,        2, 2),
+    OpCode("SET 7, H"     , bit::set_7::h,        2, 2),
+    OpCode("SET 7, L"     , bit::set_7::l,        2, 2),
+    OpCode("SET 7, (HL)"  , bit::set_7::hl_ind,   2, 4),
+    OpCode("SET 7, A"     , bit::set_7::a,        2, 2),
+];
+
 fn undefined(_: &mut Cpu) {
     unimplemented!();
 }
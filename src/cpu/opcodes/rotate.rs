@@ -46,6 +46,79 @@ pub fn rra(cpu: &mut crate::cpu::Cpu) {
     cpu.registers.f.h = false;
 }
 
+/// Rotate value left, storing old high bit in carry flag
+/// Z 0 0 C
+fn rlc_value(cpu: &mut crate::cpu::Cpu, value: u8) -> u8 {
+    let result = value.rotate_left(1);
+    cpu.registers.f.z = result == 0;
+    cpu.registers.f.n = false;
+    cpu.registers.f.h = false;
+    cpu.registers.f.c = value & 0x80 > 0;
+    result
+}
+
+/// Rotate value left, using carry flag as 9th bit
+/// Z 0 0 C
+fn rl_value(cpu: &mut crate::cpu::Cpu, value: u8) -> u8 {
+    let result = value << 1 | cpu.registers.f.c as u8;
+    cpu.registers.f.z = result == 0;
+    cpu.registers.f.n = false;
+    cpu.registers.f.h = false;
+    cpu.registers.f.c = value & 0x80 > 0;
+    result
+}
+
+/// Rotate value right, storing old low bit in carry flag
+/// Z 0 0 C
+fn rrc_value(cpu: &mut crate::cpu::Cpu, value: u8) -> u8 {
+    let result = value.rotate_right(1);
+    cpu.registers.f.z = result == 0;
+    cpu.registers.f.n = false;
+    cpu.registers.f.h = false;
+    cpu.registers.f.c = value & 0x01 > 0;
+    result
+}
+
+/// Rotate value right, using carry flag as 9th bit
+/// Z 0 0 C
+fn rr_value(cpu: &mut crate::cpu::Cpu, value: u8) -> u8 {
+    let result = value >> 1 | (cpu.registers.f.c as u8) << 7;
+    cpu.registers.f.z = result == 0;
+    cpu.registers.f.n = false;
+    cpu.registers.f.h = false;
+    cpu.registers.f.c = value & 0x01 > 0;
+    result
+}
+
+/// Generate the 0xCB-prefixed rotate instructions for registers and (HL)
+macro_rules! rotate {
+    ($name:ident, $func:ident; $( $reg:ident ),+) => {
+        pub mod $name {
+            $(
+                /// Rotate a register
+                /// Z 0 0 C
+                pub fn $reg(cpu: &mut crate::cpu::Cpu) {
+                    let value = cpu.registers.$reg;
+                    cpu.registers.$reg = super::$func(cpu, value);
+                }
+             )+
+
+                /// Rotate value in memory pointed to by HL
+                /// Z 0 0 C
+                pub fn hl_ind(cpu: &mut crate::cpu::Cpu) {
+                    let value = cpu.read_byte(cpu.registers.hl());
+                    let result = super::$func(cpu, value);
+                    cpu.write_byte(result, cpu.registers.hl());
+                }
+        }
+    };
+}
+
+rotate!(rlc, rlc_value; b, c, d, e, h, l, a);
+rotate!(rrc, rrc_value; b, c, d, e, h, l, a);
+rotate!(rl, rl_value; b, c, d, e, h, l, a);
+rotate!(rr, rr_value; b, c, d, e, h, l, a);
+
 #[cfg(test)]
 mod test {
 
@@ -87,4 +160,66 @@ mod test {
         assert!(!cpu.registers.f.c);
         assert_eq!(cpu.registers.a, 0b1101_0101);
     }
+
+    macro_rules! gen_rotate_tests {
+        ($operation:ident, $input:literal, $carry_in:literal, $output:literal, $carry_out:literal; $( $reg:ident ),+) => {
+            mod $operation {
+                $(
+                    #[test]
+                    fn $reg() {
+                        let mut cpu = crate::cpu::Cpu::reset();
+                        cpu.registers.f.set(0);
+                        cpu.registers.f.c = $carry_in;
+                        cpu.registers.$reg = $input;
+                        super::super::$operation::$reg(&mut cpu);
+                        assert_eq!(cpu.registers.$reg, $output);
+                        assert_eq!(cpu.registers.f.c, $carry_out);
+                        assert!(!cpu.registers.f.z);
+                        assert!(!cpu.registers.f.n);
+                        assert!(!cpu.registers.f.h);
+                    }
+                 )+
+
+                    #[test]
+                    fn hl_ind() {
+                        let mut cpu = crate::cpu::Cpu::reset();
+                        cpu.registers.f.set(0);
+                        cpu.registers.f.c = $carry_in;
+                        cpu.registers.put_hl(0xC000);
+                        cpu.write_byte($input, cpu.registers.hl());
+                        super::super::$operation::hl_ind(&mut cpu);
+                        assert_eq!(cpu.read_byte(0xC000), $output);
+                        assert_eq!(cpu.registers.f.c, $carry_out);
+                        assert!(!cpu.registers.f.z);
+                    }
+
+                    #[test]
+                    fn zero() {
+                        let mut cpu = crate::cpu::Cpu::reset();
+                        cpu.registers.f.set(0);
+                        cpu.registers.b = 0;
+                        super::super::$operation::b(&mut cpu);
+                        assert_eq!(cpu.registers.b, 0);
+                        assert!(cpu.registers.f.z);
+                        assert!(!cpu.registers.f.c);
+                    }
+            }
+        };
+    }
+
+    gen_rotate_tests!(rlc, 0b1010_1010, false, 0b0101_0101, true; b, c, d, e, h, l, a);
+    gen_rotate_tests!(rrc, 0b0101_0101, false, 0b1010_1010, true; b, c, d, e, h, l, a);
+    gen_rotate_tests!(rl, 0b0010_1010, true, 0b0101_0101, false; b, c, d, e, h, l, a);
+    gen_rotate_tests!(rr, 0b1010_1010, true, 0b1101_0101, false; b, c, d, e, h, l, a);
+
+    #[test]
+    fn rl_zero_with_carry_out() {
+        let mut cpu = crate::cpu::Cpu::reset();
+        cpu.registers.f.set(0);
+        cpu.registers.c = 0x80;
+        super::rl::c(&mut cpu);
+        assert_eq!(cpu.registers.c, 0);
+        assert!(cpu.registers.f.z);
+        assert!(cpu.registers.f.c);
+    }
 }
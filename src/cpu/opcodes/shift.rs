@@ -0,0 +1,165 @@
+/// Shift value left into carry flag, clearing the low bit
+/// Z 0 0 C
+fn sla_value(cpu: &mut crate::cpu::Cpu, value: u8) -> u8 {
+    let result = value << 1;
+    cpu.registers.f.z = result == 0;
+    cpu.registers.f.n = false;
+    cpu.registers.f.h = false;
+    cpu.registers.f.c = value & 0x80 > 0;
+    result
+}
+
+/// Shift value right into carry flag, keeping the high (sign) bit
+/// Z 0 0 C
+fn sra_value(cpu: &mut crate::cpu::Cpu, value: u8) -> u8 {
+    let result = value >> 1 | value & 0x80;
+    cpu.registers.f.z = result == 0;
+    cpu.registers.f.n = false;
+    cpu.registers.f.h = false;
+    cpu.registers.f.c = value & 0x01 > 0;
+    result
+}
+
+/// Shift value right into carry flag, clearing the high bit
+/// Z 0 0 C
+fn srl_value(cpu: &mut crate::cpu::Cpu, value: u8) -> u8 {
+    let result = value >> 1;
+    cpu.registers.f.z = result == 0;
+    cpu.registers.f.n = false;
+    cpu.registers.f.h = false;
+    cpu.registers.f.c = value & 0x01 > 0;
+    result
+}
+
+/// Swap upper and lower nybble of value
+/// Z 0 0 0
+fn swap_value(cpu: &mut crate::cpu::Cpu, value: u8) -> u8 {
+    let result = value.rotate_left(4);
+    cpu.registers.f.z = result == 0;
+    cpu.registers.f.n = false;
+    cpu.registers.f.h = false;
+    cpu.registers.f.c = false;
+    result
+}
+
+/// Generate the 0xCB-prefixed shift instructions for registers and (HL)
+macro_rules! shift {
+    ($name:ident, $func:ident; $( $reg:ident ),+) => {
+        pub mod $name {
+            $(
+                /// Shift a register
+                /// Z 0 0 C
+                pub fn $reg(cpu: &mut crate::cpu::Cpu) {
+                    let value = cpu.registers.$reg;
+                    cpu.registers.$reg = super::$func(cpu, value);
+                }
+             )+
+
+                /// Shift value in memory pointed to by HL
+                /// Z 0 0 C
+                pub fn hl_ind(cpu: &mut crate::cpu::Cpu) {
+                    let value = cpu.read_byte(cpu.registers.hl());
+                    let result = super::$func(cpu, value);
+                    cpu.write_byte(result, cpu.registers.hl());
+                }
+        }
+    };
+}
+
+shift!(sla, sla_value; b, c, d, e, h, l, a);
+shift!(sra, sra_value; b, c, d, e, h, l, a);
+shift!(srl, srl_value; b, c, d, e, h, l, a);
+shift!(swap, swap_value; b, c, d, e, h, l, a);
+
+#[cfg(test)]
+mod test {
+    macro_rules! gen_shift_tests {
+        ($operation:ident, $input:literal, $output:literal, $carry:literal; $( $reg:ident ),+) => {
+            mod $operation {
+                $(
+                    #[test]
+                    fn $reg() {
+                        let mut cpu = crate::cpu::Cpu::reset();
+                        cpu.registers.f.set(0xF0);
+                        cpu.registers.$reg = $input;
+                        super::super::$operation::$reg(&mut cpu);
+                        assert_eq!(cpu.registers.$reg, $output);
+                        assert_eq!(cpu.registers.f.c, $carry);
+                        assert!(!cpu.registers.f.z);
+                        assert!(!cpu.registers.f.n);
+                        assert!(!cpu.registers.f.h);
+                    }
+                 )+
+
+                    #[test]
+                    fn hl_ind() {
+                        let mut cpu = crate::cpu::Cpu::reset();
+                        cpu.registers.f.set(0xF0);
+                        cpu.registers.put_hl(0xC000);
+                        cpu.write_byte($input, cpu.registers.hl());
+                        super::super::$operation::hl_ind(&mut cpu);
+                        assert_eq!(cpu.read_byte(0xC000), $output);
+                        assert_eq!(cpu.registers.f.c, $carry);
+                        assert!(!cpu.registers.f.z);
+                    }
+
+                    #[test]
+                    fn zero() {
+                        let mut cpu = crate::cpu::Cpu::reset();
+                        cpu.registers.f.set(0);
+                        cpu.registers.a = 0;
+                        super::super::$operation::a(&mut cpu);
+                        assert_eq!(cpu.registers.a, 0);
+                        assert!(cpu.registers.f.z);
+                    }
+            }
+        };
+    }
+
+    gen_shift_tests!(sla, 0b1010_1011, 0b0101_0110, true; b, c, d, e, h, l, a);
+    gen_shift_tests!(sra, 0b1010_1011, 0b1101_0101, true; b, c, d, e, h, l, a);
+    gen_shift_tests!(srl, 0b1010_1010, 0b0101_0101, false; b, c, d, e, h, l, a);
+    gen_shift_tests!(swap, 0xAB, 0xBA, false; b, c, d, e, h, l, a);
+
+    #[test]
+    fn sla_zero_with_carry() {
+        let mut cpu = crate::cpu::Cpu::reset();
+        cpu.registers.b = 0x80;
+        super::sla::b(&mut cpu);
+        assert_eq!(cpu.registers.b, 0);
+        assert!(cpu.registers.f.z);
+        assert!(cpu.registers.f.c);
+    }
+
+    #[test]
+    fn srl_zero_with_carry() {
+        let mut cpu = crate::cpu::Cpu::reset();
+        cpu.registers.b = 0x01;
+        super::srl::b(&mut cpu);
+        assert_eq!(cpu.registers.b, 0);
+        assert!(cpu.registers.f.z);
+        assert!(cpu.registers.f.c);
+    }
+
+    #[test]
+    fn prefixed_step() {
+        let mut cpu = crate::cpu::Cpu::reset();
+        cpu.registers.a = 0xAB;
+        cpu.registers.put_hl(0xC000);
+        cpu.write_byte(0xF0, 0xC000);
+        cpu.write_byte(0xCB, 0x100); // SWAP A
+        cpu.write_byte(0x37, 0x101);
+        cpu.write_byte(0xCB, 0x102); // SWAP (HL)
+        cpu.write_byte(0x36, 0x103);
+
+        cpu.step();
+        assert_eq!(cpu.registers.a, 0xBA);
+        assert_eq!(cpu.registers.pc, 0x102);
+        assert_eq!(cpu.machine_cycles, 2);
+
+        cpu.step();
+        assert_eq!(cpu.read_byte(0xC000), 0x0F);
+        assert_eq!(cpu.registers.pc, 0x104);
+        assert_eq!(cpu.machine_cycles, 4);
+    }
+}
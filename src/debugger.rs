@@ -0,0 +1,690 @@
+use std::collections::{BTreeSet, VecDeque};
+use std::io::{self, BufRead, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::str::SplitWhitespace;
+
+use crate::cpu::{Cpu, Register};
+use crate::memory::joypad::Button;
+use crate::memory::map::usable;
+use crate::memory::watch::{Access, Condition, WatchHit, Watchpoint};
+
+const PROMPT: &str = "(gb) ";
+
+/// Executed instructions shown before PC when listing around it
+const HISTORY: usize = 3;
+/// Instructions shown after PC when listing around it
+const LIST_AFTER: usize = 6;
+/// Instructions shown when listing from an address
+const LIST_LENGTH: usize = 10;
+/// Bytes shown by a hexdump unless given
+const DUMP_LENGTH: usize = 64;
+/// Bytes per hexdump row
+const DUMP_ROW: usize = 16;
+
+const HELP: &str = "\
+Addresses and values are hex, counts are decimal.
+  s, step [COUNT]        Run COUNT instructions, 1 by default
+  n, next                Run an instruction, stepping over calls
+  c, continue            Run until a breakpoint is hit
+  f, finish              Run until the current function returns
+  b, break [ADDR]        Set a breakpoint, or list breakpoints
+  d, delete ADDR         Remove a breakpoint
+  watch [WATCH]          Set a watchpoint, or list watchpoints
+  unwatch WATCH          Remove a watchpoint
+  r, regs                Show registers and flags
+  x ADDR [COUNT]         Show COUNT bytes of memory, 64 by default
+  l, list [ADDR]         Disassemble around PC, or from ADDR
+  set REG VALUE          Set a register, e.g. `set hl c000`
+  w, write ADDR BYTE...  Write bytes to memory
+  press BUTTON           Hold a joypad button down
+  release BUTTON         Let go of a joypad button
+  h, help                Show this help
+  q, quit                Quit
+WATCH is ADDR[-END] [r|w|rw] [OP VALUE], watching for writes unless given.
+OP is one of ==, !=, < and >, comparing the byte read or written.
+BUTTON is one of right, left, up, down, a, b, select and start.
+An empty line repeats the last command.";
+
+#[derive(Debug, Clone, PartialEq)]
+enum Command {
+    Step(usize),
+    Next,
+    Continue,
+    Finish,
+    Break(Option<u16>),
+    Delete(u16),
+    Watch(Option<Watchpoint>),
+    Unwatch(Watchpoint),
+    Registers,
+    Dump(u16, usize),
+    List(Option<u16>),
+    Set(Register, u16),
+    Write(u16, Vec<u8>),
+    Press(Button),
+    Release(Button),
+    Help,
+    Quit,
+}
+
+/// Parse a hex number, optionally prefixed with `0x` or `$`
+fn parse_hex(text: &str) -> Result<u16, String> {
+    let digits = text
+        .strip_prefix("0x")
+        .or_else(|| text.strip_prefix('$'))
+        .unwrap_or(text);
+    u16::from_str_radix(digits, 16).map_err(|_| format!("Invalid hex number: {}", text))
+}
+
+fn parse_byte(text: &str) -> Result<u8, String> {
+    let value = parse_hex(text)?;
+    u8::try_from(value).map_err(|_| format!("Not a byte: {}", text))
+}
+
+fn parse_count(text: Option<&str>, default: usize) -> Result<usize, String> {
+    text.map_or(Ok(default), |text| {
+        text.parse().map_err(|_| format!("Invalid count: {}", text))
+    })
+}
+
+/// Parse a watchpoint like `c000-c00f rw != 00` from the rest of a command
+fn parse_watchpoint(words: &mut SplitWhitespace) -> Result<Watchpoint, String> {
+    let range = words.next().ok_or("Missing address to watch")?;
+    let range = match range.split_once('-') {
+        Some((start, end)) => parse_hex(start)?..=parse_hex(end)?,
+        None => parse_hex(range)?..=parse_hex(range)?,
+    };
+    if range.is_empty() {
+        return Err("Watched range ends before it starts".to_owned());
+    }
+
+    let mut words = words.peekable();
+    let access = match words.peek().copied() {
+        Some("r") => Some(Access::Read),
+        Some("w") => Some(Access::Write),
+        Some("rw") => Some(Access::ReadWrite),
+        _ => None,
+    };
+    if access.is_some() {
+        words.next();
+    }
+
+    let condition = match words.next() {
+        Some(op) => {
+            let value = parse_byte(words.next().ok_or("Missing value to compare with")?)?;
+            Some(match op {
+                "==" => Condition::Equal(value),
+                "!=" => Condition::NotEqual(value),
+                "<" => Condition::Less(value),
+                ">" => Condition::Greater(value),
+                _ => return Err(format!("Unknown comparison: {}", op)),
+            })
+        }
+        None => None,
+    };
+
+    Ok(Watchpoint {
+        range,
+        access: access.unwrap_or(Access::Write),
+        condition,
+    })
+}
+
+fn parse(line: &str) -> Result<Command, String> {
+    let mut words = line.split_whitespace();
+    let Some(name) = words.next() else {
+        return Err("No command given".to_owned());
+    };
+    let address = |words: &mut SplitWhitespace| {
+        words
+            .next()
+            .ok_or_else(|| format!("{} needs an address", name))
+            .and_then(parse_hex)
+    };
+    let button = |words: &mut SplitWhitespace| {
+        let button = words
+            .next()
+            .ok_or_else(|| format!("{} needs a button", name))?;
+        Button::parse(button).ok_or_else(|| format!("Unknown button: {}", button))
+    };
+
+    let command = match name {
+        "s" | "step" => Command::Step(parse_count(words.next(), 1)?),
+        "n" | "next" => Command::Next,
+        "c" | "continue" => Command::Continue,
+        "f" | "finish" => Command::Finish,
+        "b" | "break" => Command::Break(words.next().map(parse_hex).transpose()?),
+        "d" | "delete" => Command::Delete(address(&mut words)?),
+        "watch" if words.clone().next().is_none() => Command::Watch(None),
+        "watch" => Command::Watch(Some(parse_watchpoint(&mut words)?)),
+        "unwatch" => Command::Unwatch(parse_watchpoint(&mut words)?),
+        "r" | "regs" => Command::Registers,
+        "x" => {
+            let start = address(&mut words)?;
+            Command::Dump(start, parse_count(words.next(), DUMP_LENGTH)?)
+        }
+        "l" | "list" => Command::List(words.next().map(parse_hex).transpose()?),
+        "set" => {
+            let name = words.next().ok_or("set needs a register")?;
+            let register =
+                Register::parse(name).ok_or_else(|| format!("Unknown register: {}", name))?;
+            let value = words.next().ok_or("set needs a value")?;
+            Command::Set(register, parse_hex(value)?)
+        }
+        "w" | "write" => {
+            let start = address(&mut words)?;
+            let bytes = words
+                .by_ref()
+                .map(parse_byte)
+                .collect::<Result<Vec<_>, _>>()?;
+            if bytes.is_empty() {
+                return Err("write needs bytes to write".to_owned());
+            }
+            Command::Write(start, bytes)
+        }
+        "press" => Command::Press(button(&mut words)?),
+        "release" => Command::Release(button(&mut words)?),
+        "h" | "help" => Command::Help,
+        "q" | "quit" => Command::Quit,
+        _ => return Err(format!("Unknown command: {}, try help", name)),
+    };
+
+    if words.next().is_some() {
+        return Err(format!("Too many arguments to {}", name));
+    }
+    Ok(command)
+}
+
+/// Why running stopped before it was done
+enum Stop {
+    Stopped,
+    Breakpoint(u16),
+    Watchpoint(WatchHit),
+}
+
+/// An interactive debugger, reading commands line by line and running the
+/// CPU as told. A crash while running is reported instead of ending the
+/// session, so the state that led to it can be inspected.
+#[derive(Debug, Clone, Default)]
+pub struct Debugger {
+    breakpoints: BTreeSet<u16>,
+    /// Addresses of the last executed instructions, oldest first
+    history: VecDeque<u16>,
+    /// Last command, repeated on an empty line
+    last: Option<Command>,
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read commands from `input` until it ends or the user quits
+    pub fn run(
+        &mut self,
+        cpu: &mut Cpu,
+        input: impl BufRead,
+        output: &mut impl Write,
+    ) -> io::Result<()> {
+        writeln!(output, "Type help for a list of commands")?;
+        self.show_location(cpu, output)?;
+
+        let mut lines = input.lines();
+        loop {
+            write!(output, "{}", PROMPT)?;
+            output.flush()?;
+            let Some(line) = lines.next().transpose()? else {
+                writeln!(output)?;
+                return Ok(());
+            };
+
+            let command = if line.trim().is_empty() {
+                match &self.last {
+                    Some(command) => command.clone(),
+                    None => continue,
+                }
+            } else {
+                match parse(&line) {
+                    Ok(command) => command,
+                    Err(error) => {
+                        writeln!(output, "{}", error)?;
+                        continue;
+                    }
+                }
+            };
+
+            if command == Command::Quit {
+                return Ok(());
+            }
+            self.execute(cpu, &command, output)?;
+            self.last = Some(command);
+        }
+    }
+
+    fn execute(
+        &mut self,
+        cpu: &mut Cpu,
+        command: &Command,
+        output: &mut impl Write,
+    ) -> io::Result<()> {
+        match command {
+            Command::Step(count) => {
+                let mut steps = 0;
+                self.run_until(cpu, output, |_| {
+                    steps += 1;
+                    steps >= *count
+                })?;
+            }
+            Command::Next => {
+                let pc = cpu.register(Register::PC);
+                let instruction = cpu.disassemble(pc);
+                if instruction.text.starts_with("CALL") || instruction.text.starts_with("RST") {
+                    let after = pc.wrapping_add(instruction.size as u16);
+                    let sp = cpu.register(Register::SP);
+                    self.run_until(cpu, output, |cpu| {
+                        cpu.register(Register::PC) == after && cpu.register(Register::SP) >= sp
+                    })?;
+                } else {
+                    self.run_until(cpu, output, |_| true)?;
+                }
+            }
+            Command::Continue => self.run_until(cpu, output, |_| false)?,
+            Command::Finish => {
+                let sp = cpu.register(Register::SP);
+                let mut returning = false;
+                self.run_until(cpu, output, |cpu| {
+                    // Set for the instruction about to run, checked after it ran
+                    let returned = returning && cpu.register(Register::SP) > sp;
+                    let pc = cpu.register(Register::PC);
+                    returning = cpu.disassemble(pc).text.starts_with("RET");
+                    returned
+                })?;
+            }
+            Command::Break(Some(addr)) => {
+                self.breakpoints.insert(*addr);
+                writeln!(output, "Breakpoint at {:04x}", addr)?;
+            }
+            Command::Break(None) if self.breakpoints.is_empty() => {
+                writeln!(output, "No breakpoints")?;
+            }
+            Command::Break(None) => {
+                for addr in &self.breakpoints {
+                    writeln!(output, "Breakpoint at {:04x}", addr)?;
+                }
+            }
+            Command::Delete(addr) => {
+                if !self.breakpoints.remove(addr) {
+                    writeln!(output, "No breakpoint at {:04x}", addr)?;
+                }
+            }
+            Command::Watch(Some(watchpoint)) => {
+                cpu.add_watchpoint(watchpoint.clone());
+                writeln!(output, "Watching {}", watchpoint)?;
+            }
+            Command::Watch(None) if cpu.watchpoints().is_empty() => {
+                writeln!(output, "No watchpoints")?;
+            }
+            Command::Watch(None) => {
+                for watchpoint in cpu.watchpoints() {
+                    writeln!(output, "Watching {}", watchpoint)?;
+                }
+            }
+            Command::Unwatch(watchpoint) => {
+                if !cpu.remove_watchpoint(watchpoint) {
+                    writeln!(output, "Not watching {}", watchpoint)?;
+                }
+            }
+            Command::Registers => writeln!(output, "{}", cpu.registers())?,
+            Command::Dump(start, length) => self.dump(cpu, *start, *length, output)?,
+            Command::List(Some(start)) => {
+                let mut addr = *start;
+                for _ in 0..LIST_LENGTH {
+                    addr = self.list_instruction(cpu, addr, output)?;
+                }
+            }
+            Command::List(None) => {
+                for addr in self.history.clone() {
+                    self.list_instruction(cpu, addr, output)?;
+                }
+                let mut addr = cpu.register(Register::PC);
+                for _ in 0..=LIST_AFTER {
+                    addr = self.list_instruction(cpu, addr, output)?;
+                }
+            }
+            Command::Set(register, value) => {
+                cpu.set_register(*register, *value);
+                if *register == Register::PC {
+                    self.history.clear();
+                }
+            }
+            Command::Write(start, bytes) => {
+                for (addr, byte) in (*start..=u16::MAX).zip(bytes) {
+                    if !usable(addr) {
+                        writeln!(output, "Can't write to {:04x}", addr)?;
+                        return Ok(());
+                    }
+                    cpu.write_byte(*byte, addr);
+                }
+                let room = (*start..=u16::MAX).len();
+                if bytes.len() > room {
+                    let rest: Vec<_> = bytes[room..].iter().map(|b| format!("{:02x}", b)).collect();
+                    writeln!(output, "Can't write {} past ffff", rest.join(" "))?;
+                }
+            }
+            Command::Press(button) => cpu.press(*button),
+            Command::Release(button) => cpu.release(*button),
+            Command::Help => writeln!(output, "{}", HELP)?,
+            Command::Quit => {}
+        }
+        Ok(())
+    }
+
+    /// Run the CPU until `done` says so, the CPU stops, or a breakpoint or
+    /// watchpoint is hit. At least one instruction is run, so that running
+    /// from a breakpoint doesn't stop right away.
+    fn run_until(
+        &mut self,
+        cpu: &mut Cpu,
+        output: &mut impl Write,
+        mut done: impl FnMut(&Cpu) -> bool,
+    ) -> io::Result<()> {
+        let result = panic::catch_unwind(AssertUnwindSafe(|| loop {
+            if cpu.is_stopped() {
+                return Some(Stop::Stopped);
+            }
+
+            let pc = cpu.register(Register::PC);
+            cpu.step();
+            if cpu.register(Register::PC) != pc {
+                self.history.push_back(pc);
+                if self.history.len() > HISTORY {
+                    self.history.pop_front();
+                }
+            }
+
+            if let Some(hit) = cpu.take_watch_hit() {
+                return Some(Stop::Watchpoint(hit));
+            }
+            let pc = cpu.register(Register::PC);
+            if self.breakpoints.contains(&pc) {
+                return Some(Stop::Breakpoint(pc));
+            }
+            if done(cpu) {
+                return None;
+            }
+        }));
+
+        match result {
+            Ok(Some(Stop::Stopped)) => writeln!(output, "CPU stopped")?,
+            Ok(Some(Stop::Breakpoint(pc))) => writeln!(output, "Breakpoint at {:04x}", pc)?,
+            Ok(Some(Stop::Watchpoint(hit))) => {
+                let (action, preposition) = match hit.access {
+                    Access::Read => ("Read", "from"),
+                    _ => ("Wrote", "to"),
+                };
+                writeln!(
+                    output,
+                    "Watchpoint: {} {:02x} {} {:04x} at",
+                    action, hit.value, preposition, hit.addr
+                )?;
+                self.list_instruction(cpu, hit.pc, output)?;
+            }
+            Ok(None) => {}
+            Err(_) => writeln!(
+                output,
+                "Emulation crashed at {:04x}",
+                cpu.register(Register::PC)
+            )?,
+        }
+        self.show_location(cpu, output)
+    }
+
+    fn show_location(&self, cpu: &Cpu, output: &mut impl Write) -> io::Result<()> {
+        self.list_instruction(cpu, cpu.register(Register::PC), output)?;
+        Ok(())
+    }
+
+    /// Print the instruction at `addr`, marking it if it's at PC or has a
+    /// breakpoint.
+    ///
+    /// # Returns
+    /// * The address of the next instruction.
+    fn list_instruction(&self, cpu: &Cpu, addr: u16, output: &mut impl Write) -> io::Result<u16> {
+        if !usable(addr) {
+            writeln!(output, "   {:04x}: --", addr)?;
+            return Ok(addr.wrapping_add(1));
+        }
+
+        let instruction = cpu.disassemble(addr);
+        let bytes: Vec<String> = (0..instruction.size as u16)
+            .map(|i| format!("{:02x}", cpu.peek_byte(addr.wrapping_add(i))))
+            .collect();
+        let marker = if addr == cpu.register(Register::PC) {
+            "=>"
+        } else if self.breakpoints.contains(&addr) {
+            " *"
+        } else {
+            "  "
+        };
+        writeln!(
+            output,
+            "{} {:04x}: {:9} {}",
+            marker,
+            addr,
+            bytes.join(" "),
+            instruction.text
+        )?;
+        Ok(addr.wrapping_add(instruction.size as u16))
+    }
+
+    fn dump(
+        &self,
+        cpu: &Cpu,
+        start: u16,
+        length: usize,
+        output: &mut impl Write,
+    ) -> io::Result<()> {
+        let addresses: Vec<u16> = (start..=u16::MAX).take(length).collect();
+        for row in addresses.chunks(DUMP_ROW) {
+            let bytes: Vec<String> = row
+                .iter()
+                .map(|addr| match usable(*addr) {
+                    true => format!("{:02x}", cpu.peek_byte(*addr)),
+                    false => "--".to_owned(),
+                })
+                .collect();
+            writeln!(output, "{:04x}: {}", row[0], bytes.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cpu::test::setup;
+
+    /// Run a debugging session with `commands`, returning what was printed
+    fn session(cpu: &mut Cpu, commands: &str) -> String {
+        let mut output = Vec::new();
+        Debugger::new()
+            .run(cpu, commands.as_bytes(), &mut output)
+            .unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn parse_commands() {
+        assert_eq!(parse("s"), Ok(Command::Step(1)));
+        assert_eq!(parse("step 10"), Ok(Command::Step(10)));
+        assert_eq!(parse("b $0150"), Ok(Command::Break(Some(0x0150))));
+        assert_eq!(parse("x 0xc000 4"), Ok(Command::Dump(0xC000, 4)));
+        assert_eq!(parse("set SP dffe"), Ok(Command::Set(Register::SP, 0xDFFE)));
+        assert_eq!(
+            parse("w c000 1 ff"),
+            Ok(Command::Write(0xC000, vec![0x01, 0xFF]))
+        );
+        assert!(parse("w c000 100").is_err());
+        assert!(parse("set ix 0").is_err());
+        assert!(parse("regs now").is_err());
+        assert_eq!(parse("press Start"), Ok(Command::Press(Button::Start)));
+        assert_eq!(parse("release a"), Ok(Command::Release(Button::A)));
+        assert!(parse("press x").is_err());
+        assert!(parse("release").is_err());
+
+        let watchpoint = Watchpoint {
+            range: 0xC000..=0xC00F,
+            access: Access::ReadWrite,
+            condition: Some(Condition::NotEqual(0)),
+        };
+        assert_eq!(
+            parse("watch c000-c00f rw != 0"),
+            Ok(Command::Watch(Some(watchpoint)))
+        );
+        let watchpoint = Watchpoint {
+            range: 0xFF80..=0xFF80,
+            access: Access::Write,
+            condition: None,
+        };
+        assert_eq!(parse("unwatch ff80"), Ok(Command::Unwatch(watchpoint)));
+        assert_eq!(parse("watch"), Ok(Command::Watch(None)));
+        assert!(parse("watch c00f-c000").is_err());
+        assert!(parse("watch c000 >= 1").is_err());
+        assert!(parse("watch c000 r ==").is_err());
+        assert!(parse("jump").is_err());
+    }
+
+    #[test]
+    fn step_and_registers() {
+        // LD A, $42; LD B, A
+        let mut cpu = setup(&[0x3E, 0x42, 0x47]);
+        let output = session(&mut cpu, "s\n\nregs\n");
+        assert!(output.contains("=> 0100: 3e 42     LD A, $42\n"));
+        assert!(output.contains("=> 0102: 47        LD B, A\n"));
+        assert!(output.contains("A: 42  F: b0 [Z-HC]\nB: 42  C: 13\n"));
+        assert_eq!(cpu.register(Register::PC), 0x0103);
+    }
+
+    #[test]
+    fn breakpoints() {
+        let mut cpu = setup(&[0x00; 16]);
+        let output = session(&mut cpu, "b 108\nb 104\nd 108\nb\nc\n");
+        assert!(output.contains("(gb) Breakpoint at 0104\n(gb) Breakpoint at 0104\n=> 0104"));
+        assert_eq!(cpu.register(Register::PC), 0x0104);
+
+        // Continuing from a breakpoint runs past it
+        let output = session(&mut cpu, "b 104\nb 10a\nc 2\nc\n");
+        assert!(output.contains("Too many arguments to c"));
+        assert_eq!(cpu.register(Register::PC), 0x010A);
+    }
+
+    #[test]
+    fn next_steps_over_calls() {
+        // CALL $0110; NOP, with INC B; RET at $0110
+        let mut cpu = setup(&[0xCD, 0x10, 0x01, 0x00]);
+        cpu.write_byte(0x04, 0x0110);
+        cpu.write_byte(0xC9, 0x0111);
+        cpu.set_register(Register::B, 0);
+
+        session(&mut cpu, "n\n");
+        assert_eq!(cpu.register(Register::PC), 0x0103);
+        assert_eq!(cpu.register(Register::B), 1);
+
+        // A breakpoint inside still stops
+        cpu.set_register(Register::PC, 0x0100);
+        session(&mut cpu, "b 111\nn\n");
+        assert_eq!(cpu.register(Register::PC), 0x0111);
+    }
+
+    #[test]
+    fn finish() {
+        // CALL $0110 from $0100, with CALL $0120; INC B; RET at $0110 and
+        // RET at $0120
+        let mut cpu = setup(&[0xCD, 0x10, 0x01, 0x00]);
+        for (i, byte) in [0xCD, 0x20, 0x01, 0x04, 0xC9].iter().enumerate() {
+            cpu.write_byte(*byte, 0x0110 + i as u16);
+        }
+        cpu.write_byte(0xC9, 0x0120);
+        cpu.set_register(Register::B, 0);
+
+        session(&mut cpu, "s\nf\n");
+        assert_eq!(cpu.register(Register::PC), 0x0103);
+        assert_eq!(cpu.register(Register::B), 1);
+    }
+
+    #[test]
+    fn watchpoints() {
+        // LD A, $01; LD ($c000), A; LD A, $42; LD ($c000), A; LD A, ($c000)
+        let mut cpu = setup(&[
+            0x3E, 0x01, 0xEA, 0x00, 0xC0, 0x3E, 0x42, 0xEA, 0x00, 0xC0, 0xFA, 0x00, 0xC0,
+        ]);
+        let output = session(&mut cpu, "watch c000 w > 10\nwatch c000-c001 r\nwatch\nc\n");
+        assert!(output.contains("Watching c000 w > 10\nWatching c000-c001 r\n"));
+        assert!(output.contains(
+            "Watchpoint: Wrote 42 to c000 at\n   0107: ea 00 c0  LD ($c000), A\n=> 010a"
+        ));
+        assert_eq!(cpu.register(Register::PC), 0x010A);
+
+        let output = session(&mut cpu, "c\nunwatch c000-c001 r\nunwatch c000 r\n");
+        assert!(
+            output.contains("Watchpoint: Read 42 from c000 at\n   010a: fa 00 c0  LD A, ($c000)\n")
+        );
+        assert!(output.contains("Not watching c000 r\n"));
+        assert_eq!(cpu.watchpoints().len(), 1);
+    }
+
+    #[test]
+    fn memory() {
+        let mut cpu = setup(&[]);
+        let output = session(&mut cpu, "w c000 12 34\nset hl c000\nx c000 2\nx fe9f 2\n");
+        assert!(output.contains("c000: 12 34\n"));
+        assert!(output.contains("fe9f: 00 --\n"));
+        assert_eq!(cpu.register(Register::HL), 0xC000);
+    }
+
+    #[test]
+    fn write_past_end_of_memory() {
+        let mut cpu = setup(&[]);
+        let output = session(
+            &mut cpu,
+            "w ffff 01 02 03
+",
+        );
+        assert!(output.contains("Can't write 02 03 past ffff\n"));
+        assert_eq!(cpu.peek_byte(0xFFFF), 0x01);
+    }
+
+    #[test]
+    fn list_next_to_unusable() {
+        let mut cpu = setup(&[]);
+        // Operands running into the unusable area read as 0xff
+        let output = session(&mut cpu, "w fe9f 01\nl fe9f\nw fe9f cb\nl fe9f\n");
+        assert!(output.contains("   fe9f: 01 ff ff  LD BC, $ffff\n   fea2: --\n"));
+        assert!(output.contains("   fe9f: cb ff     SET 7, A\n   fea1: --\n"));
+    }
+
+    #[test]
+    fn joypad() {
+        let mut cpu = setup(&[]);
+        session(&mut cpu, "press start\npress a\nrelease a\nw ff00 10\n");
+        // Action buttons selected, only start held down
+        assert_eq!(cpu.peek_byte(0xFF00), 0xD7);
+    }
+
+    #[test]
+    fn list_around_pc() {
+        let mut cpu = setup(&[0x00; 16]);
+        let output = session(&mut cpu, "s 5\nl\n");
+        assert!(output.contains("(gb)    0102: 00        NOP\n   0103"));
+        assert!(output.contains("=> 0105: 00        NOP\n   0106"));
+        assert!(output.contains("   010b: 00        NOP\n"));
+        assert!(!output.contains("   010c"));
+    }
+
+    #[test]
+    fn crash_is_reported() {
+        let mut cpu = setup(&[0x00, 0xD3]);
+        let output = session(&mut cpu, "c\nregs\n");
+        assert!(output.contains("Emulation crashed at 0101"));
+        assert!(output.contains("SP: fffe  PC: 0101"));
+    }
+}
@@ -0,0 +1,113 @@
+#[cfg(feature = "sdl")]
+pub mod sdl;
+
+use std::time::{Duration, Instant};
+
+use crate::apu::CLOCK_RATE;
+
+/// Machine cycles per second
+const MACHINE_CLOCK_RATE: u64 = CLOCK_RATE as u64 / 4;
+
+/// How far emulation may fall behind before it stops trying to catch up, e.g.
+/// after the window was dragged around
+const MAX_LAG: Duration = Duration::from_millis(100);
+
+/// Keeps emulation running at the speed of a Game Boy, by waiting until the
+/// emulated time has passed in real time as well. Waiting once per frame
+/// gives a frame rate of about 59.73 Hz.
+#[cfg_attr(not(feature = "sdl"), allow(dead_code))]
+#[derive(Debug, Clone)]
+pub struct FramePacer {
+    /// Real time at which emulation was at `start_cycles`
+    start: Instant,
+    start_cycles: u64,
+}
+
+#[cfg_attr(not(feature = "sdl"), allow(dead_code))]
+impl FramePacer {
+    /// Start pacing from `cycles` machine cycles
+    pub fn new(cycles: u64) -> Self {
+        Self {
+            start: Instant::now(),
+            start_cycles: cycles,
+        }
+    }
+
+    /// How long to wait at `now` for real time to catch up with emulation
+    /// having run `cycles` machine cycles. Starts over from `now` if
+    /// emulation has fallen too far behind.
+    fn delay(&mut self, now: Instant, cycles: u64) -> Duration {
+        let emulated = cycles - self.start_cycles;
+        let target = self.start
+            + Duration::from_secs(emulated / MACHINE_CLOCK_RATE)
+            + Duration::from_nanos(
+                emulated % MACHINE_CLOCK_RATE * 1_000_000_000 / MACHINE_CLOCK_RATE,
+            );
+
+        if now > target + MAX_LAG {
+            self.start = now;
+            self.start_cycles = cycles;
+        }
+        target.saturating_duration_since(now)
+    }
+
+    /// Wait until real time catches up with emulation having run `cycles`
+    /// machine cycles
+    pub fn wait(&mut self, cycles: u64) {
+        let delay = self.delay(Instant::now(), cycles);
+        if !delay.is_zero() {
+            std::thread::sleep(delay);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ppu::MACHINE_CYCLES_PER_FRAME;
+
+    #[test]
+    fn frame_rate() {
+        let rate = MACHINE_CLOCK_RATE as f64 / MACHINE_CYCLES_PER_FRAME as f64;
+        assert!((rate - 59.73).abs() < 0.005);
+    }
+
+    #[test]
+    fn waits_for_real_time() {
+        let mut pacer = FramePacer::new(1000);
+        let start = pacer.start;
+
+        let delay = pacer.delay(start, 1000 + MACHINE_CYCLES_PER_FRAME);
+        assert_eq!(delay.as_micros(), 16_742);
+        let delay = pacer.delay(
+            start + Duration::from_millis(10),
+            1000 + MACHINE_CYCLES_PER_FRAME,
+        );
+        assert_eq!(delay.as_micros(), 6_742);
+
+        // A second of emulation
+        let delay = pacer.delay(
+            start + Duration::from_millis(500),
+            1000 + MACHINE_CLOCK_RATE,
+        );
+        assert_eq!(delay, Duration::from_millis(500));
+    }
+
+    #[test]
+    fn running_behind() {
+        let mut pacer = FramePacer::new(0);
+        let start = pacer.start;
+
+        // Slightly late frames are made up for by the next ones
+        let late = start + Duration::from_millis(20);
+        assert!(pacer.delay(late, MACHINE_CYCLES_PER_FRAME).is_zero());
+        let delay = pacer.delay(late, 2 * MACHINE_CYCLES_PER_FRAME);
+        assert_eq!(delay.as_micros(), 13_485);
+
+        // Far behind, the pacer starts over instead of rushing to catch up
+        let later = start + Duration::from_secs(1);
+        assert!(pacer.delay(later, 3 * MACHINE_CYCLES_PER_FRAME).is_zero());
+        let delay = pacer.delay(later, 4 * MACHINE_CYCLES_PER_FRAME);
+        assert_eq!(delay.as_micros(), 16_742);
+    }
+}
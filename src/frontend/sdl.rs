@@ -0,0 +1,195 @@
+use sdl2::controller::GameController;
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::render::{Canvas, Texture};
+use sdl2::video::Window;
+use sdl2::EventPump;
+
+use super::FramePacer;
+use crate::cpu::Cpu;
+use crate::memory::joypad::Button;
+use crate::ppu::{GRAYS, MACHINE_CYCLES_PER_FRAME, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+/// Bytes per pixel of the texture the frame is drawn to
+const BYTES_PER_PIXEL: usize = 3;
+
+/// Longest a game controller rumbles for, in milliseconds. The motor is
+/// switched off explicitly long before this in practice.
+const RUMBLE_DURATION: u32 = 0xFFFF;
+
+/// Button a key on the keyboard is mapped to
+fn button(key: Keycode) -> Option<Button> {
+    match key {
+        Keycode::Right => Some(Button::Right),
+        Keycode::Left => Some(Button::Left),
+        Keycode::Up => Some(Button::Up),
+        Keycode::Down => Some(Button::Down),
+        Keycode::X => Some(Button::A),
+        Keycode::Z => Some(Button::B),
+        Keycode::Backspace => Some(Button::Select),
+        Keycode::Return => Some(Button::Start),
+        _ => None,
+    }
+}
+
+/// Open the first game controller connected, if there is one
+fn open_controller(sdl: &sdl2::Sdl) -> Result<Option<GameController>, String> {
+    let controllers = sdl.game_controller()?;
+    Ok((0..controllers.num_joysticks()?)
+        .filter(|&index| controllers.is_game_controller(index))
+        .find_map(|index| controllers.open(index).ok()))
+}
+
+/// A window showing the LCD, scaled up by a whole number so pixels stay
+/// square, and taking joypad input from the keyboard. A cartridge's rumble
+/// motor rumbles the first game controller, if one is connected.
+pub struct SdlFrontend {
+    canvas: Canvas<Window>,
+    /// The frame as drawn to the window, updated whenever one is shown
+    texture: Texture,
+    events: EventPump,
+    controller: Option<GameController>,
+    pacer: FramePacer,
+    /// Machine cycle count when the last frame was shown
+    last_frame: u64,
+    /// The frame converted to RGB
+    pixels: Vec<u8>,
+}
+
+impl SdlFrontend {
+    pub fn new(sdl: &sdl2::Sdl, scale: u32, cpu: &Cpu) -> Result<Self, String> {
+        let video = sdl.video()?;
+        let window = video
+            .window(
+                "gibberish",
+                SCREEN_WIDTH as u32 * scale,
+                SCREEN_HEIGHT as u32 * scale,
+            )
+            .position_centered()
+            .resizable()
+            .build()
+            .map_err(|error| error.to_string())?;
+
+        let mut canvas = window
+            .into_canvas()
+            .build()
+            .map_err(|error| error.to_string())?;
+        // Keep the scale a whole number when the window is resized as well
+        canvas
+            .set_logical_size(SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32)
+            .map_err(|error| error.to_string())?;
+        canvas.set_integer_scale(true)?;
+        let texture = canvas
+            .create_texture_streaming(
+                PixelFormatEnum::RGB24,
+                SCREEN_WIDTH as u32,
+                SCREEN_HEIGHT as u32,
+            )
+            .map_err(|error| error.to_string())?;
+
+        // Rumble is only a nicety, so a missing controller isn't an error
+        let controller = open_controller(sdl).unwrap_or_else(|error| {
+            eprintln!("Failed to look for a game controller: {}", error);
+            None
+        });
+
+        Ok(Self {
+            canvas,
+            texture,
+            events: sdl.event_pump()?,
+            controller,
+            pacer: FramePacer::new(cpu.cycles()),
+            last_frame: cpu.cycles(),
+            pixels: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT * BYTES_PER_PIXEL],
+        })
+    }
+
+    /// Call after every step. Once the PPU completes a frame, show it, pass
+    /// on keyboard input and wait to keep emulation at the speed of a Game
+    /// Boy.
+    ///
+    /// # Returns
+    /// * `false` if the window was closed, otherwise `true`.
+    pub fn update(&mut self, cpu: &mut Cpu) -> bool {
+        // No frames are completed with the LCD off, but the window still has
+        // to respond and emulation has to be paced
+        let frame = cpu.poll_frame();
+        if !frame && cpu.cycles() - self.last_frame < 2 * MACHINE_CYCLES_PER_FRAME {
+            return true;
+        }
+        self.last_frame = cpu.cycles();
+
+        if let Err(error) = self.present(cpu.frame_buffer()) {
+            eprintln!("Failed to draw frame: {}", error);
+        }
+        let running = self.handle_events(cpu);
+        self.pacer.wait(cpu.cycles());
+        running
+    }
+
+    /// Switch rumbling of the game controller on or off
+    pub fn set_rumble(&mut self, rumble: bool) {
+        let Some(controller) = &mut self.controller else {
+            return;
+        };
+        let (strength, duration) = if rumble {
+            (u16::MAX, RUMBLE_DURATION)
+        } else {
+            (0, 0)
+        };
+        if let Err(error) = controller.set_rumble(strength, strength, duration) {
+            eprintln!("Failed to rumble controller: {}", error);
+        }
+    }
+
+    /// Draw a frame of shades to the window
+    fn present(&mut self, frame: &[u8]) -> Result<(), String> {
+        for (pixel, shade) in self.pixels.chunks_exact_mut(BYTES_PER_PIXEL).zip(frame) {
+            pixel.fill(GRAYS[*shade as usize]);
+        }
+
+        self.texture
+            .update(None, &self.pixels, SCREEN_WIDTH * BYTES_PER_PIXEL)
+            .map_err(|error| error.to_string())?;
+
+        self.canvas.clear();
+        self.canvas.copy(&self.texture, None, None)?;
+        self.canvas.present();
+        Ok(())
+    }
+
+    /// Press and release buttons for keys going down and up. Escape quits.
+    ///
+    /// # Returns
+    /// * `false` if the window was closed, otherwise `true`.
+    fn handle_events(&mut self, cpu: &mut Cpu) -> bool {
+        for event in self.events.poll_iter() {
+            match event {
+                Event::Quit { .. }
+                | Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                } => return false,
+                Event::KeyDown {
+                    keycode: Some(key),
+                    repeat: false,
+                    ..
+                } => {
+                    if let Some(button) = button(key) {
+                        cpu.press(button);
+                    }
+                }
+                Event::KeyUp {
+                    keycode: Some(key), ..
+                } => {
+                    if let Some(button) = button(key) {
+                        cpu.release(button);
+                    }
+                }
+                _ => {}
+            }
+        }
+        true
+    }
+}
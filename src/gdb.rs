@@ -0,0 +1,576 @@
+use std::collections::BTreeSet;
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::cpu::{Cpu, Register};
+use crate::memory::map::usable;
+use crate::memory::watch::{Access, WatchHit, Watchpoint};
+
+/// Registers in the order GDB numbers them, following its Z80 target
+const REGISTERS: [Register; 6] = [
+    Register::AF,
+    Register::BC,
+    Register::DE,
+    Register::HL,
+    Register::SP,
+    Register::PC,
+];
+
+/// Sent by GDB on its own, outside of a packet, to interrupt the target
+const INTERRUPT: u8 = 0x03;
+/// Instructions run between checks for an interrupt from GDB
+const INTERRUPT_POLL: u32 = 4096;
+
+const SIGINT: u8 = 2;
+const SIGTRAP: u8 = 5;
+/// Reported when emulation crashes
+const SIGSEGV: u8 = 11;
+
+const OK: &str = "OK";
+const ERROR: &str = "E01";
+
+#[derive(Debug, PartialEq)]
+enum Packet {
+    Command(String),
+    Interrupt,
+}
+
+/// Accept a single connection from GDB on `listener`, and let it control the
+/// CPU until it detaches, kills the target or disconnects
+///
+/// # Returns
+/// * `true` if GDB detached, leaving the CPU to keep running on its own,
+///   otherwise `false`.
+pub fn serve(cpu: &mut Cpu, listener: &TcpListener) -> io::Result<bool> {
+    let (stream, _) = listener.accept()?;
+    // Packets are small and answered one at a time
+    stream.set_nodelay(true)?;
+    let mut session = Session {
+        cpu,
+        reader: BufReader::new(stream.try_clone()?),
+        writer: stream,
+        breakpoints: BTreeSet::new(),
+    };
+    session.run()
+}
+
+fn checksum(data: &str) -> u8 {
+    data.bytes().fold(0, |sum, byte| sum.wrapping_add(byte))
+}
+
+fn parse_hex(text: &str) -> Option<u16> {
+    u16::from_str_radix(text, 16).ok()
+}
+
+fn parse_length(text: &str) -> Option<usize> {
+    usize::from_str_radix(text, 16).ok()
+}
+
+fn decode_bytes(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Registers are sent as little endian hex, like the target stores them
+fn encode_register(value: u16) -> String {
+    let [lo, hi] = value.to_le_bytes();
+    format!("{:02x}{:02x}", lo, hi)
+}
+
+fn decode_register(text: &str) -> Option<u16> {
+    match decode_bytes(text)?.as_slice() {
+        [lo, hi] => Some(u16::from_le_bytes([*lo, *hi])),
+        _ => None,
+    }
+}
+
+fn stop_reply(signal: u8) -> String {
+    format!("S{:02x}", signal)
+}
+
+fn watch_reply(hit: WatchHit) -> String {
+    let kind = match hit.watched {
+        Access::Write => "watch",
+        Access::Read => "rwatch",
+        Access::ReadWrite => "awatch",
+    };
+    format!("T{:02x}{}:{:x};", SIGTRAP, kind, hit.addr)
+}
+
+struct Session<'a> {
+    cpu: &'a mut Cpu,
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    breakpoints: BTreeSet<u16>,
+}
+
+impl Session<'_> {
+    /// Handle packets until GDB is done
+    ///
+    /// # Returns
+    /// * `true` if GDB detached, otherwise `false`.
+    fn run(&mut self) -> io::Result<bool> {
+        while let Some(packet) = self.read_packet()? {
+            let command = match packet {
+                Packet::Command(command) => command,
+                Packet::Interrupt => {
+                    self.send(&stop_reply(SIGINT))?;
+                    continue;
+                }
+            };
+
+            match command.as_str() {
+                "k" => return Ok(false),
+                "D" => {
+                    self.send(OK)?;
+                    return Ok(true);
+                }
+                _ => {
+                    let reply = self.reply(&command)?;
+                    self.send(&reply)?;
+                }
+            }
+        }
+        Ok(false)
+    }
+
+    /// Handle a packet from GDB. Anything unsupported gets an empty reply.
+    fn reply(&mut self, command: &str) -> io::Result<String> {
+        let Some(kind) = command.chars().next() else {
+            return Ok(String::new());
+        };
+        let args = &command[1..];
+
+        let reply = match kind {
+            '?' => Some(stop_reply(SIGTRAP)),
+            'g' => Some(self.read_registers()),
+            'G' => self.write_registers(args),
+            'p' => self.read_register(args),
+            'P' => self.write_register(args),
+            'm' => self.read_memory(args),
+            'M' => self.write_memory(args),
+            'c' | 's' => {
+                if !args.is_empty() {
+                    let Some(addr) = parse_hex(args) else {
+                        return Ok(ERROR.to_owned());
+                    };
+                    self.cpu.set_register(Register::PC, addr);
+                }
+                return self.resume(kind == 's');
+            }
+            'Z' | 'z' => self.breakpoint(kind == 'Z', args),
+            'H' => Some(OK.to_owned()),
+            'q' if args.starts_with("Supported") => Some("PacketSize=1000".to_owned()),
+            'q' if args == "Attached" => Some("1".to_owned()),
+            _ => Some(String::new()),
+        };
+        Ok(reply.unwrap_or_else(|| ERROR.to_owned()))
+    }
+
+    fn read_registers(&self) -> String {
+        REGISTERS
+            .iter()
+            .map(|register| encode_register(self.cpu.register(*register)))
+            .collect()
+    }
+
+    fn write_registers(&mut self, args: &str) -> Option<String> {
+        // GDB may send registers of the Z80 the Game Boy doesn't have
+        let values = (0..REGISTERS.len())
+            .map(|i| decode_register(args.get(i * 4..i * 4 + 4)?))
+            .collect::<Option<Vec<_>>>()?;
+        for (register, value) in REGISTERS.iter().zip(values) {
+            self.cpu.set_register(*register, value);
+        }
+        Some(OK.to_owned())
+    }
+
+    fn read_register(&self, args: &str) -> Option<String> {
+        let register = REGISTERS.get(parse_length(args)?)?;
+        Some(encode_register(self.cpu.register(*register)))
+    }
+
+    fn write_register(&mut self, args: &str) -> Option<String> {
+        let (number, value) = args.split_once('=')?;
+        let register = REGISTERS.get(parse_length(number)?)?;
+        self.cpu.set_register(*register, decode_register(value)?);
+        Some(OK.to_owned())
+    }
+
+    /// Read memory up to the first unusable address. GDB asks again for
+    /// whatever is missing, and gets an error.
+    fn read_memory(&self, args: &str) -> Option<String> {
+        let (addr, length) = args.split_once(',')?;
+        let bytes: String = (parse_hex(addr)?..=u16::MAX)
+            .take(parse_length(length)?)
+            .take_while(|addr| usable(*addr))
+            .map(|addr| format!("{:02x}", self.cpu.peek_byte(addr)))
+            .collect();
+        (!bytes.is_empty()).then_some(bytes)
+    }
+
+    /// Write memory as the CPU would, so writes to the ROM area control the
+    /// cartridge's memory bank controller
+    fn write_memory(&mut self, args: &str) -> Option<String> {
+        let (location, data) = args.split_once(':')?;
+        let (addr, length) = location.split_once(',')?;
+        let addr = parse_hex(addr)?;
+        let bytes = decode_bytes(data)?;
+        if bytes.len() != parse_length(length)? {
+            return None;
+        }
+
+        let addresses = (addr..=u16::MAX).take(bytes.len());
+        if addresses.len() != bytes.len() || !addresses.clone().all(usable) {
+            return None;
+        }
+        for (addr, byte) in addresses.zip(bytes) {
+            self.cpu.write_byte(byte, addr);
+        }
+        Some(OK.to_owned())
+    }
+
+    /// Insert or remove a breakpoint or watchpoint. Software and hardware
+    /// breakpoints are the same thing here, since the emulator checks PC
+    /// after every instruction either way.
+    fn breakpoint(&mut self, insert: bool, args: &str) -> Option<String> {
+        let mut fields = args.split(',');
+        let kind = fields.next()?;
+        let addr = parse_hex(fields.next()?)?;
+        // Breakpoint conditions may follow the length
+        let length = parse_hex(fields.next()?.split(';').next()?)?;
+
+        let access = match kind {
+            "0" | "1" => {
+                if insert {
+                    self.breakpoints.insert(addr);
+                } else {
+                    self.breakpoints.remove(&addr);
+                }
+                return Some(OK.to_owned());
+            }
+            "2" => Access::Write,
+            "3" => Access::Read,
+            "4" => Access::ReadWrite,
+            _ => return Some(String::new()),
+        };
+
+        // GDB evaluates conditions on watchpoints itself
+        let watchpoint = Watchpoint {
+            range: addr..=addr.saturating_add(length.max(1) - 1),
+            access,
+            condition: None,
+        };
+        if insert {
+            self.cpu.add_watchpoint(watchpoint);
+        } else {
+            self.cpu.remove_watchpoint(&watchpoint);
+        }
+        Some(OK.to_owned())
+    }
+
+    /// Run the CPU until a breakpoint or watchpoint is hit, GDB interrupts,
+    /// or after a single instruction when stepping. At least one instruction
+    /// is run, so that continuing from a breakpoint doesn't stop right away.
+    ///
+    /// # Returns
+    /// * The stop reply for GDB.
+    fn resume(&mut self, single_step: bool) -> io::Result<String> {
+        let mut steps = 0;
+        let result = panic::catch_unwind(AssertUnwindSafe(|| loop {
+            // Nothing can wake a stopped CPU from here, so it's as good as
+            // exited
+            if self.cpu.is_stopped() {
+                return Ok("W00".to_owned());
+            }
+
+            self.cpu.step();
+            if let Some(hit) = self.cpu.take_watch_hit() {
+                return Ok(watch_reply(hit));
+            }
+            let pc = self.cpu.register(Register::PC);
+            if single_step || self.breakpoints.contains(&pc) {
+                return Ok(stop_reply(SIGTRAP));
+            }
+
+            steps += 1;
+            if steps % INTERRUPT_POLL == 0 && self.interrupted()? {
+                return Ok(stop_reply(SIGINT));
+            }
+        }));
+        result.unwrap_or_else(|_| Ok(stop_reply(SIGSEGV)))
+    }
+
+    /// Check whether GDB sent something while the CPU was running, without
+    /// waiting for it. Acknowledgements are skipped, anything else stops the
+    /// CPU.
+    fn interrupted(&mut self) -> io::Result<bool> {
+        self.reader.get_ref().set_nonblocking(true)?;
+        let interrupted = self.check_interrupt();
+        self.reader.get_ref().set_nonblocking(false)?;
+        interrupted
+    }
+
+    /// The part of `interrupted` that runs with the socket non-blocking
+    fn check_interrupt(&mut self) -> io::Result<bool> {
+        loop {
+            let buffer = match self.reader.fill_buf() {
+                Ok(buffer) => buffer,
+                Err(error) if error.kind() == ErrorKind::WouldBlock => return Ok(false),
+                Err(error) => return Err(error),
+            };
+            match buffer.first().copied() {
+                // Disconnected
+                None => return Ok(true),
+                Some(INTERRUPT) => {
+                    self.reader.consume(1);
+                    return Ok(true);
+                }
+                Some(b'+' | b'-') => self.reader.consume(1),
+                Some(_) => return Ok(true),
+            }
+        }
+    }
+
+    /// Read the next packet, acknowledging it. Packets with a bad checksum
+    /// are asked to be sent again.
+    ///
+    /// # Returns
+    /// * `None` if GDB disconnected.
+    fn read_packet(&mut self) -> io::Result<Option<Packet>> {
+        loop {
+            let mut byte = [0];
+            if self.reader.read(&mut byte)? == 0 {
+                return Ok(None);
+            }
+            match byte[0] {
+                INTERRUPT => return Ok(Some(Packet::Interrupt)),
+                b'$' => {}
+                // Acknowledgements, and anything else outside of a packet
+                _ => continue,
+            }
+
+            let mut data = Vec::new();
+            self.reader.read_until(b'#', &mut data)?;
+            if data.pop() != Some(b'#') {
+                return Ok(None);
+            }
+            let mut sum = [0; 2];
+            self.reader.read_exact(&mut sum)?;
+
+            let data = String::from_utf8_lossy(&data).into_owned();
+            let expected = std::str::from_utf8(&sum)
+                .ok()
+                .and_then(|sum| u8::from_str_radix(sum, 16).ok());
+            if expected == Some(checksum(&data)) {
+                self.writer.write_all(b"+")?;
+                return Ok(Some(Packet::Command(data)));
+            }
+            self.writer.write_all(b"-")?;
+        }
+    }
+
+    fn send(&mut self, data: &str) -> io::Result<()> {
+        write!(self.writer, "${}#{:02x}", data, checksum(data))?;
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cpu::test::setup;
+    use std::thread;
+    use std::time::Duration;
+
+    /// A scripted GDB
+    struct Client {
+        reader: BufReader<TcpStream>,
+        writer: TcpStream,
+    }
+
+    impl Client {
+        fn send(&mut self, data: &str) {
+            write!(self.writer, "${}#{:02x}", data, checksum(data)).unwrap();
+            let mut ack = [0];
+            self.reader.read_exact(&mut ack).unwrap();
+            assert_eq!(ack[0], b'+');
+        }
+
+        fn receive(&mut self) -> String {
+            let mut packet = Vec::new();
+            self.reader.read_until(b'#', &mut packet).unwrap();
+            let mut sum = [0; 2];
+            self.reader.read_exact(&mut sum).unwrap();
+            self.writer.write_all(b"+").unwrap();
+
+            assert_eq!(packet.remove(0), b'$');
+            packet.pop();
+            let data = String::from_utf8(packet).unwrap();
+            let sum = u8::from_str_radix(std::str::from_utf8(&sum).unwrap(), 16);
+            assert_eq!(sum, Ok(checksum(&data)));
+            data
+        }
+
+        fn command(&mut self, data: &str) -> String {
+            self.send(data);
+            self.receive()
+        }
+    }
+
+    /// Serve `cpu` to a client running `script`, returning whether it
+    /// detached
+    fn session(cpu: &mut Cpu, script: impl FnOnce(&mut Client) + Send + 'static) -> bool {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = thread::spawn(move || {
+            let stream = TcpStream::connect(addr).unwrap();
+            stream
+                .set_read_timeout(Some(Duration::from_secs(10)))
+                .unwrap();
+            let mut client = Client {
+                reader: BufReader::new(stream.try_clone().unwrap()),
+                writer: stream,
+            };
+            script(&mut client);
+        });
+        let detached = serve(cpu, &listener).unwrap();
+        client.join().unwrap();
+        detached
+    }
+
+    #[test]
+    fn packets() {
+        let mut cpu = setup(&[]);
+        let detached = session(&mut cpu, |client| {
+            // A corrupted packet is asked for again
+            client.writer.write_all(b"$?#00").unwrap();
+            let mut nak = [0];
+            client.reader.read_exact(&mut nak).unwrap();
+            assert_eq!(nak[0], b'-');
+
+            assert_eq!(client.command("?"), "S05");
+            assert_eq!(client.command("qSupported:swbreak+"), "PacketSize=1000");
+            assert_eq!(client.command("vMustReplyEmpty"), "");
+            assert_eq!(client.command("D"), "OK");
+        });
+        assert!(detached);
+    }
+
+    #[test]
+    fn registers() {
+        let mut cpu = setup(&[]);
+        cpu.set_register(Register::AF, 0x01B0);
+        cpu.set_register(Register::SP, 0xFFFE);
+        let detached = session(&mut cpu, |client| {
+            let registers = client.command("g");
+            assert!(registers.starts_with("b001"));
+            assert!(registers.ends_with("feff0001"));
+
+            assert_eq!(client.command("P5=5001"), "OK");
+            assert_eq!(client.command("p5"), "5001");
+            assert_eq!(client.command("p6"), ERROR);
+            assert_eq!(client.command("G0000341200000000fedf0002"), "OK");
+            client.send("k");
+        });
+        assert!(!detached);
+        assert_eq!(cpu.register(Register::BC), 0x1234);
+        assert_eq!(cpu.register(Register::SP), 0xDFFE);
+        assert_eq!(cpu.register(Register::PC), 0x0200);
+    }
+
+    #[test]
+    fn memory() {
+        let mut cpu = setup(&[]);
+        session(&mut cpu, |client| {
+            assert_eq!(client.command("Mc000,2:12ab"), "OK");
+            assert_eq!(client.command("mc000,3"), "12ab00");
+            assert_eq!(client.command("mfe9f,2"), "00");
+            assert_eq!(client.command("mfea0,1"), ERROR);
+            assert_eq!(client.command("Mfe9f,2:0000"), ERROR);
+            assert_eq!(client.command("Mc000,2:12"), ERROR);
+        });
+        assert_eq!(cpu.read_word(0xC000), 0xAB12);
+    }
+
+    #[test]
+    fn breakpoints_and_steps() {
+        let mut cpu = setup(&[0x00; 16]);
+        session(&mut cpu, |client| {
+            assert_eq!(client.command("Z0,104,1"), "OK");
+            assert_eq!(client.command("Z1,108,1"), "OK");
+            assert_eq!(client.command("c"), "S05");
+            assert_eq!(client.command("p5"), "0401");
+            assert_eq!(client.command("s"), "S05");
+            assert_eq!(client.command("p5"), "0501");
+            assert_eq!(client.command("z0,104,1"), "OK");
+            assert_eq!(client.command("c"), "S05");
+            assert_eq!(client.command("p5"), "0801");
+
+            // Continuing from an address
+            assert_eq!(client.command("c102"), "S05");
+            assert_eq!(client.command("p5"), "0801");
+        });
+    }
+
+    #[test]
+    fn watchpoints() {
+        // LD A, $42; LD ($c000), A; LD A, ($c001); NOP
+        let mut cpu = setup(&[0x3E, 0x42, 0xEA, 0x00, 0xC0, 0xFA, 0x01, 0xC0, 0x00]);
+        session(&mut cpu, |client| {
+            assert_eq!(client.command("Z2,c000,2"), "OK");
+            assert_eq!(client.command("Z3,c001,1"), "OK");
+            assert_eq!(client.command("c"), "T05watch:c000;");
+            assert_eq!(client.command("p5"), "0501");
+            assert_eq!(client.command("c"), "T05rwatch:c001;");
+            assert_eq!(client.command("p5"), "0801");
+
+            assert_eq!(client.command("z2,c000,2"), "OK");
+            assert_eq!(client.command("z3,c001,1"), "OK");
+            assert_eq!(client.command("Z4,c000,1"), "OK");
+            assert_eq!(client.command("c100"), "T05awatch:c000;");
+        });
+    }
+
+    #[test]
+    fn interrupt() {
+        // JP $0100
+        let mut cpu = setup(&[0xC3, 0x00, 0x01]);
+        session(&mut cpu, |client| {
+            client.send("c");
+            client.writer.write_all(&[INTERRUPT]).unwrap();
+            assert_eq!(client.receive(), "S02");
+            assert_eq!(client.command("p5"), "0001");
+        });
+    }
+
+    #[test]
+    fn stray_acknowledgement() {
+        let mut cpu = setup(&[]);
+        session(&mut cpu, |client| {
+            assert_eq!(client.command("Z0,3000,1"), "OK");
+            // An acknowledgement arriving while running doesn't stop the CPU,
+            // or keep it waiting for more
+            client.writer.write_all(b"$c#63+").unwrap();
+            let mut ack = [0];
+            client.reader.read_exact(&mut ack).unwrap();
+            assert_eq!(client.receive(), "S05");
+            assert_eq!(client.command("p5"), "0030");
+        });
+    }
+
+    #[test]
+    fn crash() {
+        let mut cpu = setup(&[0x00, 0xD3]);
+        session(&mut cpu, |client| {
+            assert_eq!(client.command("c"), "S0b");
+            assert_eq!(client.command("p5"), "0101");
+        });
+    }
+}
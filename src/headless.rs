@@ -0,0 +1,159 @@
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+
+use crate::cpu::Cpu;
+use crate::png;
+use crate::ppu::{MACHINE_CYCLES_PER_FRAME, SCREEN_WIDTH};
+
+/// Run `frames` frames' worth of machine cycles without showing anything, or
+/// until the CPU stops. A crash is caught instead of taking the process down,
+/// so the state it left behind can still be looked at.
+///
+/// # Arguments
+/// * `cpu` - The CPU to run.
+/// * `frames` - Number of frames to run for.
+/// * `each_step` - Called after every step, e.g. to collect audio.
+///
+/// # Returns
+/// * `false` if emulation crashed, otherwise `true`.
+pub fn run_frames(cpu: &mut Cpu, frames: u64, mut each_step: impl FnMut(&mut Cpu)) -> bool {
+    let end = cpu.cycles() + frames * MACHINE_CYCLES_PER_FRAME;
+    panic::catch_unwind(AssertUnwindSafe(|| {
+        while cpu.cycles() < end && !cpu.is_stopped() {
+            cpu.step();
+            each_step(cpu);
+        }
+    }))
+    .is_ok()
+}
+
+/// Save the last frame drawn by the PPU to a PNG file
+pub fn save_screenshot(cpu: &Cpu, path: &Path) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    png::write_shades(&mut writer, SCREEN_WIDTH, cpu.frame_buffer())?;
+    writer.into_inner()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ppu::SCREEN_HEIGHT;
+
+    #[test]
+    fn runs_whole_frames() {
+        let mut cpu = Cpu::reset();
+        // JP 0x0100, looping forever
+        cpu.write_byte(0xC3, 0x0100);
+        cpu.write_word(0x0100, 0x0101);
+        let mut steps = 0;
+        assert!(run_frames(&mut cpu, 3, |_| steps += 1));
+
+        let end = 3 * MACHINE_CYCLES_PER_FRAME;
+        assert_eq!(cpu.cycles(), end.next_multiple_of(4));
+        assert_eq!(steps, end.div_ceil(4));
+
+        assert!(run_frames(&mut cpu, 1, |_| {}));
+        assert!(cpu.cycles() >= end + MACHINE_CYCLES_PER_FRAME);
+    }
+
+    #[test]
+    fn reports_crash() {
+        let mut cpu = Cpu::reset();
+        // Undefined opcode
+        cpu.write_byte(0xD3, 0x0110);
+        assert!(!run_frames(&mut cpu, 1, |_| {}));
+        assert!(cpu.cycles() < MACHINE_CYCLES_PER_FRAME);
+    }
+
+    /// Size and pixels of a PNG as written by [`png::write_shades`], which
+    /// only uses stored deflate blocks and no filtering
+    fn decode_png(png: &[u8]) -> (u32, u32, Vec<u8>) {
+        assert_eq!(png[..8], *b"\x89PNG\r\n\x1a\n");
+        let mut header = None;
+        let mut stream = Vec::new();
+        let mut rest = &png[8..];
+        while !rest.is_empty() {
+            let length = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+            let data = &rest[8..8 + length];
+            match &rest[4..8] {
+                b"IHDR" => header = Some(data.to_vec()),
+                b"IDAT" => stream.extend_from_slice(data),
+                _ => {}
+            }
+            rest = &rest[12 + length..];
+        }
+
+        let header = header.unwrap();
+        let width = u32::from_be_bytes(header[..4].try_into().unwrap());
+        let height = u32::from_be_bytes(header[4..8].try_into().unwrap());
+        assert_eq!(header[8..], [8, 0, 0, 0, 0]);
+
+        // Skip the zlib header, then unwrap each stored block
+        let mut rows = Vec::new();
+        let mut block = &stream[2..];
+        loop {
+            let last = block[0] & 1 != 0;
+            let length = u16::from_le_bytes([block[1], block[2]]) as usize;
+            rows.extend_from_slice(&block[5..5 + length]);
+            block = &block[5 + length..];
+            if last {
+                break;
+            }
+        }
+
+        let pixels = rows
+            .chunks_exact(width as usize + 1)
+            .flat_map(|row| {
+                assert_eq!(row[0], 0);
+                row[1..].to_vec()
+            })
+            .collect();
+        (width, height, pixels)
+    }
+
+    #[test]
+    fn screenshot() {
+        let mut cpu = Cpu::reset();
+        // JP 0x0100, looping forever
+        cpu.write_byte(0xC3, 0x0100);
+        cpu.write_word(0x0100, 0x0101);
+
+        // Tile 1 is solid color 3, tile 2 solid color 1
+        for row in 0..8 {
+            cpu.write_word(0xFFFF, 0x8010 + row * 2);
+            cpu.write_word(0x00FF, 0x8020 + row * 2);
+        }
+        // Tile 1 in the second column of the background, with tile 2 as a
+        // sprite at 24, 16
+        cpu.write_byte(1, 0x9801);
+        cpu.write_byte(16 + 16, 0xFE00);
+        cpu.write_byte(24 + 8, 0xFE01);
+        cpu.write_byte(2, 0xFE02);
+        cpu.write_byte(0xE4, 0xFF48); // OBP0
+        cpu.write_byte(0x93, 0xFF40); // LCDC, with sprites
+        assert!(run_frames(&mut cpu, 2, |_| {}));
+
+        let path =
+            std::env::temp_dir().join(format!("gibberish-{}-screenshot.png", std::process::id()));
+        save_screenshot(&cpu, &path).unwrap();
+        let png = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let (width, height, pixels) = decode_png(&png);
+        assert_eq!((width, height), (SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32));
+        assert_eq!(pixels.len(), SCREEN_WIDTH * SCREEN_HEIGHT);
+        let pixel = |x: usize, y: usize| pixels[y * SCREEN_WIDTH + x];
+        assert_eq!(pixel(0, 0), 0xFF);
+        assert_eq!(pixel(8, 0), 0x00);
+        assert_eq!(pixel(15, 7), 0x00);
+        assert_eq!(pixel(16, 0), 0xFF);
+        assert_eq!(pixel(8, 8), 0xFF);
+        assert_eq!(pixel(24, 16), 0xAA);
+        assert_eq!(pixel(31, 23), 0xAA);
+        assert_eq!(pixel(32, 16), 0xFF);
+        assert_eq!(pixel(159, 143), 0xFF);
+    }
+}
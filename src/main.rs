@@ -1,24 +1,421 @@
+mod apu;
+mod audio;
 mod cpu;
+mod debugger;
+mod frontend;
+mod gdb;
+mod headless;
 mod memory;
+mod png;
+mod ppu;
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use apu::Sample;
+use audio::Recorder;
+use debugger::Debugger;
+#[cfg(feature = "sdl")]
+use frontend::sdl::SdlFrontend;
+use memory::cartridge::SaveFile;
+use memory::link::{Address, SocketLink};
+use memory::printer::Printer;
+use memory::serial::{Capture, Loopback, SerialDevice};
+use ppu::Renderer;
+
+/// How often battery-backed RAM is written to the save file while running
+const SAVE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Samples to collect from the APU before passing them on, about a frame's
+/// worth
+const AUDIO_CHUNK: usize = (audio::APU_SAMPLE_RATE / 60) as usize;
+
+/// How many times larger than the LCD the window is, unless given with
+/// `--scale`
+#[cfg(feature = "sdl")]
+const DEFAULT_SCALE: u32 = 3;
 
 fn main() {
-    let mut args = std::env::args();
-    if args.len() < 2 {
+    let mut force = false;
+    let mut renderer = Renderer::Scanline;
+    let mut record_path = None;
+    let mut record_channels = false;
+    let mut link = None;
+    let mut printer = None;
+    let mut serial: Option<Box<dyn SerialDevice>> = None;
+    let mut capture = None;
+    let mut headless = false;
+    let mut debug = false;
+    let mut gdb_port = None;
+    let mut frames = None;
+    let mut screenshot = None;
+    #[cfg(feature = "sdl")]
+    let mut scale = DEFAULT_SCALE;
+    let mut rom_path = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--force" => force = true,
+            "--fifo" => renderer = Renderer::Fifo,
+            "--record-audio" => match args.next() {
+                Some(path) => record_path = Some(PathBuf::from(path)),
+                None => {
+                    eprintln!("--record-audio needs a file name");
+                    std::process::exit(1);
+                }
+            },
+            "--record-channels" => record_channels = true,
+            "--link-listen" | "--link-connect" => match args.next() {
+                Some(address) => link = Some((arg == "--link-listen", Address::parse(&address))),
+                None => {
+                    eprintln!("{} needs an address", arg);
+                    std::process::exit(1);
+                }
+            },
+            "--printer" => match args.next() {
+                Some(directory) => printer = Some(PathBuf::from(directory)),
+                None => {
+                    eprintln!("--printer needs a directory");
+                    std::process::exit(1);
+                }
+            },
+            "--serial" => match args.next().as_deref() {
+                Some("loopback") => serial = Some(Box::new(Loopback)),
+                Some("capture") => {
+                    let device = Capture::new();
+                    capture = Some(device.clone());
+                    serial = Some(Box::new(device));
+                }
+                _ => {
+                    eprintln!("--serial needs a device, loopback or capture");
+                    std::process::exit(1);
+                }
+            },
+            "--headless" => headless = true,
+            "--debug" => debug = true,
+            "--gdb" => match args.next().and_then(|port| port.parse::<u16>().ok()) {
+                Some(port) => gdb_port = Some(port),
+                None => {
+                    eprintln!("--gdb needs a port number");
+                    std::process::exit(1);
+                }
+            },
+            "--frames" => match args.next().and_then(|frames| frames.parse().ok()) {
+                Some(count) => frames = Some(count),
+                None => {
+                    eprintln!("--frames needs a number of frames");
+                    std::process::exit(1);
+                }
+            },
+            "--screenshot" => match args.next() {
+                Some(path) => screenshot = Some(PathBuf::from(path)),
+                None => {
+                    eprintln!("--screenshot needs a file name");
+                    std::process::exit(1);
+                }
+            },
+            #[cfg(feature = "sdl")]
+            "--scale" => match args.next().and_then(|scale| scale.parse().ok()) {
+                Some(factor) if factor > 0 => scale = factor,
+                _ => {
+                    eprintln!("--scale needs a positive whole number");
+                    std::process::exit(1);
+                }
+            },
+            _ => rom_path = Some(arg),
+        }
+    }
+
+    let Some(rom_path) = rom_path else {
         println!("No ROM file provided");
         return;
+    };
+
+    if [link.is_some(), printer.is_some(), serial.is_some()]
+        .iter()
+        .filter(|device| **device)
+        .count()
+        > 1
+    {
+        eprintln!("Only one of the link cable, the printer and --serial can be connected");
+        std::process::exit(1);
+    }
+    if headless && frames.is_none() {
+        eprintln!("--headless needs --frames");
+        std::process::exit(1);
+    }
+    if [headless, debug, gdb_port.is_some()]
+        .iter()
+        .filter(|mode| **mode)
+        .count()
+        > 1
+    {
+        eprintln!("Only one of --headless, --debug and --gdb can be used");
+        std::process::exit(1);
+    }
+    if !headless && (frames.is_some() || screenshot.is_some()) {
+        eprintln!("--frames and --screenshot only work with --headless");
+        std::process::exit(1);
+    }
+    // Without a window to close, nothing would end the recording
+    if cfg!(not(feature = "sdl")) && !headless && record_path.is_some() {
+        eprintln!("--record-audio needs --headless when built without SDL");
+        std::process::exit(1);
+    }
+
+    let mut cpu = cpu::Cpu::reset().with_renderer(renderer);
+    let rom = std::fs::read(&rom_path).unwrap();
+    let mut cartridge = match memory::cartridge::load(rom.clone()) {
+        Ok((cartridge, warning)) => {
+            if let Some(warning) = warning {
+                eprintln!("Warning: {}", warning);
+            }
+            cartridge
+        }
+        Err(error) if force => {
+            eprintln!("Warning: {}", error);
+            memory::cartridge::load_unchecked(rom)
+        }
+        Err(error) => {
+            eprintln!("Invalid cartridge header: {}", error);
+            eprintln!("Use --force to load it anyway");
+            std::process::exit(1);
+        }
+    };
+
+    // Headless runs always start from the same state, and leave the save file
+    // alone
+    let mut save_file = SaveFile::for_rom(&rom_path);
+    if !headless {
+        if let Err(error) = save_file.load(cartridge.as_mut()) {
+            eprintln!("Failed to load {}: {}", save_file.path().display(), error);
+        }
     }
+    cpu.load_cartridge(cartridge);
 
-    let _ = args.next();
+    if let Some((listen, address)) = link {
+        connect_link(&mut cpu, listen, &address);
+    }
+    if let Some(directory) = printer {
+        cpu.connect_serial(Box::new(Printer::new(directory)));
+    }
+    if let Some(device) = serial {
+        cpu.connect_serial(device);
+    }
+
+    let mut recorder = record_path.map(|path| {
+        let recorder = Recorder::create(&path, record_channels).unwrap_or_else(|error| {
+            eprintln!("Failed to create {}: {}", path.display(), error);
+            std::process::exit(1);
+        });
+        cpu.apu_mut().set_sample_rate(Some(audio::APU_SAMPLE_RATE));
+        cpu.apu_mut().set_channel_samples(record_channels);
+        recorder
+    });
 
-    let mut cpu = cpu::Cpu::reset();
-    let rom = std::fs::read(args.next().unwrap()).unwrap();
+    if debug {
+        let stdin = std::io::stdin();
+        if let Err(error) = Debugger::new().run(&mut cpu, stdin.lock(), &mut std::io::stdout()) {
+            eprintln!("Debugger failed: {}", error);
+        }
+        flush_save(&mut save_file, &cpu);
+        finish_recording(recorder, &mut cpu);
+        print_capture(capture);
+        return;
+    }
+
+    if let Some(port) = gdb_port {
+        let address = ("127.0.0.1", port);
+        let served = std::net::TcpListener::bind(address).and_then(|listener| {
+            println!("Waiting for GDB on 127.0.0.1:{}", port);
+            gdb::serve(&mut cpu, &listener)
+        });
+        match served {
+            // Keep running on our own, like the program would without GDB
+            Ok(true) => println!("GDB detached"),
+            result => {
+                if let Err(error) = result {
+                    eprintln!("GDB connection failed: {}", error);
+                }
+                flush_save(&mut save_file, &cpu);
+                finish_recording(recorder, &mut cpu);
+                print_capture(capture);
+                return;
+            }
+        }
+    }
 
-    for (i, b) in rom.iter().enumerate() {
-        cpu.write_byte(*b, i.try_into().unwrap());
+    if let Some(frames) = frames {
+        let success = run_headless(&mut cpu, frames, screenshot.as_deref(), &mut recorder);
+        finish_recording(recorder, &mut cpu);
+        print_capture(capture);
+        std::process::exit(if success { 0 } else { 1 });
     }
 
+    #[cfg(feature = "sdl")]
+    let sdl = sdl2::init().unwrap_or_else(|error| {
+        eprintln!("Failed to initialize SDL: {}", error);
+        std::process::exit(1);
+    });
+    #[cfg(feature = "sdl")]
+    let mut frontend = SdlFrontend::new(&sdl, scale, &cpu).unwrap_or_else(|error| {
+        eprintln!("Failed to open window: {}", error);
+        std::process::exit(1);
+    });
+    #[cfg(feature = "sdl")]
+    let mut audio = start_audio(&sdl, &mut cpu);
+
+    let mut last_save = Instant::now();
     while !cpu.is_stopped() {
+        #[cfg(not(feature = "sdl"))]
         cpu.print_status();
         cpu.step();
+
+        if cpu.apu_mut().sample_count() >= AUDIO_CHUNK {
+            let samples = cpu.apu_mut().take_samples();
+            record_audio(&mut recorder, &samples, &mut cpu);
+
+            #[cfg(feature = "sdl")]
+            if let Some(audio) = &mut audio {
+                queue_audio(audio, &samples);
+            }
+        }
+
+        if let Some(rumble) = cpu.poll_rumble() {
+            #[cfg(feature = "sdl")]
+            frontend.set_rumble(rumble);
+            #[cfg(not(feature = "sdl"))]
+            println!("Rumble {}", if rumble { "on" } else { "off" });
+        }
+
+        #[cfg(feature = "sdl")]
+        if !frontend.update(&mut cpu) {
+            break;
+        }
+
+        if last_save.elapsed() >= SAVE_INTERVAL {
+            flush_save(&mut save_file, &cpu);
+            last_save = Instant::now();
+        }
+    }
+
+    flush_save(&mut save_file, &cpu);
+    finish_recording(recorder, &mut cpu);
+    print_capture(capture);
+}
+
+/// Run `frames` frames without a window, recording audio if asked to, and
+/// save the LCD to `screenshot` at the end. The screenshot is saved even if
+/// emulation crashed.
+///
+/// # Returns
+/// * `false` if emulation crashed or the screenshot couldn't be saved,
+///   otherwise `true`.
+fn run_headless(
+    cpu: &mut cpu::Cpu,
+    frames: u64,
+    screenshot: Option<&Path>,
+    recorder: &mut Option<Recorder>,
+) -> bool {
+    let mut success = headless::run_frames(cpu, frames, |cpu| {
+        if cpu.apu_mut().sample_count() >= AUDIO_CHUNK {
+            let samples = cpu.apu_mut().take_samples();
+            record_audio(recorder, &samples, cpu);
+        }
+    });
+    if !success {
+        eprintln!("Emulation crashed after {} machine cycles", cpu.cycles());
+    }
+
+    if let Some(path) = screenshot {
+        if let Err(error) = headless::save_screenshot(cpu, path) {
+            eprintln!("Failed to save {}: {}", path.display(), error);
+            success = false;
+        }
+    }
+    success
+}
+
+fn flush_save(save_file: &mut SaveFile, cpu: &cpu::Cpu) {
+    if let Err(error) = save_file.flush(cpu.cartridge()) {
+        eprintln!("Failed to write {}: {}", save_file.path().display(), error);
+    }
+}
+
+/// Plug a link cable to another instance into the serial port, either
+/// waiting for the other instance to connect or connecting to it
+fn connect_link(cpu: &mut cpu::Cpu, listen: bool, address: &Address) {
+    let link = if listen {
+        println!("Waiting for link partner on {}", address);
+        SocketLink::listen(address)
+    } else {
+        SocketLink::connect(address)
+    };
+    match link {
+        Ok(link) => cpu.connect_serial(Box::new(link)),
+        Err(error) => {
+            eprintln!("Failed to link with {}: {}", address, error);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Print what was sent to the serial capture, if there is one
+fn print_capture(capture: Option<Capture>) {
+    if let Some(capture) = capture {
+        println!("{}", String::from_utf8_lossy(&capture.bytes()));
+    }
+}
+
+/// Write samples collected by the APU to the recording, along with the
+/// separate channels if those are recorded. Recording stops on errors.
+fn record_audio(recorder: &mut Option<Recorder>, samples: &[Sample], cpu: &mut cpu::Cpu) {
+    let Some(active) = recorder else {
+        return;
+    };
+
+    let channel_samples = cpu.apu_mut().take_channel_samples();
+    if let Err(error) = active.write(samples, &channel_samples) {
+        eprintln!("Failed to record audio: {}", error);
+        *recorder = None;
+    }
+}
+
+/// Record what's left of the audio and finish the recording
+fn finish_recording(mut recorder: Option<Recorder>, cpu: &mut cpu::Cpu) {
+    let samples = cpu.apu_mut().take_samples();
+    record_audio(&mut recorder, &samples, cpu);
+    if let Some(recorder) = recorder {
+        if let Err(error) = recorder.finish() {
+            eprintln!("Failed to finish audio recording: {}", error);
+        }
+    }
+}
+
+/// Open the audio device and start collecting samples from the APU. Runs
+/// without sound if there's no audio device.
+#[cfg(feature = "sdl")]
+fn start_audio(sdl: &sdl2::Sdl, cpu: &mut cpu::Cpu) -> Option<audio::sdl::SdlAudio> {
+    match audio::sdl::SdlAudio::new(sdl) {
+        Ok(audio) => {
+            cpu.apu_mut().set_sample_rate(Some(audio::APU_SAMPLE_RATE));
+            Some(audio)
+        }
+        Err(error) => {
+            eprintln!("Failed to open audio device: {}", error);
+            None
+        }
+    }
+}
+
+/// Send samples collected by the APU to the audio device, and wait if
+/// emulation is running ahead of the audio
+#[cfg(feature = "sdl")]
+fn queue_audio(audio: &mut audio::sdl::SdlAudio, samples: &[Sample]) {
+    if let Err(error) = audio.queue(samples) {
+        eprintln!("Failed to queue audio: {}", error);
+    }
+    while audio.ahead() {
+        std::thread::sleep(Duration::from_millis(1));
     }
 }
@@ -0,0 +1,472 @@
+use std::fmt;
+
+use super::{RAM_BANK_SIZE, ROM_BANK_SIZE};
+
+/// Location of the title in the header
+const TITLE: std::ops::Range<usize> = 0x134..0x144;
+/// Location of the manufacturer code in newer cartridges, overlapping the end
+/// of the title
+const MANUFACTURER_CODE: std::ops::Range<usize> = 0x13F..0x143;
+/// Location of the CGB flag, overlapping the last byte of the title
+const CGB_FLAG: usize = 0x143;
+/// Location of the new licensee code
+const NEW_LICENSEE_CODE: std::ops::Range<usize> = 0x144..0x146;
+/// Location of the SGB flag
+const SGB_FLAG: usize = 0x146;
+/// Location of the cartridge type
+const CARTRIDGE_TYPE: usize = 0x147;
+/// Location of the ROM size code
+const ROM_SIZE: usize = 0x148;
+/// Location of the RAM size code
+const RAM_SIZE: usize = 0x149;
+/// Location of the old licensee code
+const OLD_LICENSEE_CODE: usize = 0x14B;
+/// Location of the header checksum
+const HEADER_CHECKSUM: usize = 0x14D;
+/// Location of the global checksum (big endian)
+const GLOBAL_CHECKSUM: usize = 0x14E;
+/// Bytes covered by the header checksum
+const HEADER_CHECKSUM_RANGE: std::ops::RangeInclusive<usize> = 0x134..=0x14C;
+/// Size of a ROM image that contains the whole header
+const HEADER_END: usize = 0x150;
+
+/// Old licensee code meaning that the new licensee code should be used
+const USE_NEW_LICENSEE_CODE: u8 = 0x33;
+
+/// Memory bank controller on the cartridge
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mapper {
+    None,
+    Mbc1,
+    Mbc2,
+    Mbc3,
+    Mbc5,
+}
+
+/// Hardware on the cartridge, decoded from the cartridge type byte
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CartridgeType {
+    pub code: u8,
+    pub mapper: Mapper,
+    pub ram: bool,
+    pub battery: bool,
+    pub timer: bool,
+    pub rumble: bool,
+}
+
+impl CartridgeType {
+    fn new(code: u8, mapper: Mapper) -> Self {
+        Self {
+            code,
+            mapper,
+            ram: false,
+            battery: false,
+            timer: false,
+            rumble: false,
+        }
+    }
+
+    fn ram(self) -> Self {
+        Self { ram: true, ..self }
+    }
+
+    fn battery(self) -> Self {
+        Self {
+            battery: true,
+            ..self
+        }
+    }
+
+    fn timer(self) -> Self {
+        Self {
+            timer: true,
+            ..self
+        }
+    }
+
+    fn rumble(self) -> Self {
+        Self {
+            rumble: true,
+            ..self
+        }
+    }
+}
+
+impl TryFrom<u8> for CartridgeType {
+    type Error = HeaderError;
+
+    fn try_from(code: u8) -> Result<Self, Self::Error> {
+        let cartridge_type = |mapper| CartridgeType::new(code, mapper);
+        Ok(match code {
+            0x00 => cartridge_type(Mapper::None),
+            0x01 => cartridge_type(Mapper::Mbc1),
+            0x02 => cartridge_type(Mapper::Mbc1).ram(),
+            0x03 => cartridge_type(Mapper::Mbc1).ram().battery(),
+            0x05 => cartridge_type(Mapper::Mbc2),
+            0x06 => cartridge_type(Mapper::Mbc2).battery(),
+            0x08 => cartridge_type(Mapper::None).ram(),
+            0x09 => cartridge_type(Mapper::None).ram().battery(),
+            0x0F => cartridge_type(Mapper::Mbc3).timer().battery(),
+            0x10 => cartridge_type(Mapper::Mbc3).timer().ram().battery(),
+            0x11 => cartridge_type(Mapper::Mbc3),
+            0x12 => cartridge_type(Mapper::Mbc3).ram(),
+            0x13 => cartridge_type(Mapper::Mbc3).ram().battery(),
+            0x19 => cartridge_type(Mapper::Mbc5),
+            0x1A => cartridge_type(Mapper::Mbc5).ram(),
+            0x1B => cartridge_type(Mapper::Mbc5).ram().battery(),
+            0x1C => cartridge_type(Mapper::Mbc5).rumble(),
+            0x1D => cartridge_type(Mapper::Mbc5).rumble().ram(),
+            0x1E => cartridge_type(Mapper::Mbc5).rumble().ram().battery(),
+            _ => return Err(HeaderError::UnsupportedCartridgeType(code)),
+        })
+    }
+}
+
+/// Game Boy Color support, from the CGB flag
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CgbSupport {
+    /// Made for the original Game Boy
+    None,
+    /// Runs on both the original Game Boy and the Game Boy Color
+    Enhanced,
+    /// Only runs on the Game Boy Color
+    Only,
+}
+
+/// Publisher of the game
+#[derive(Debug, Clone, PartialEq)]
+pub enum Licensee {
+    /// Code used by older cartridges
+    Old(u8),
+    /// Two character code used by newer cartridges
+    New(String),
+}
+
+/// Errors found while parsing or validating a cartridge header
+#[derive(Debug, Clone, PartialEq)]
+pub enum HeaderError {
+    /// The ROM is too small to contain a header
+    TooSmall(usize),
+    UnsupportedCartridgeType(u8),
+    InvalidRomSize(u8),
+    InvalidRamSize(u8),
+    /// The ROM image does not match the size given in the header
+    RomSizeMismatch {
+        header: usize,
+        actual: usize,
+    },
+    HeaderChecksum {
+        header: u8,
+        actual: u8,
+    },
+    GlobalChecksum {
+        header: u16,
+        actual: u16,
+    },
+}
+
+impl fmt::Display for HeaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooSmall(size) => write!(f, "ROM is too small for a header ({size} bytes)"),
+            Self::UnsupportedCartridgeType(code) => {
+                write!(f, "Unsupported cartridge type: {code:#04x}")
+            }
+            Self::InvalidRomSize(code) => write!(f, "Invalid ROM size code: {code:#04x}"),
+            Self::InvalidRamSize(code) => write!(f, "Invalid RAM size code: {code:#04x}"),
+            Self::RomSizeMismatch { header, actual } => write!(
+                f,
+                "ROM size mismatch: header says {header} bytes, image is {actual} bytes"
+            ),
+            Self::HeaderChecksum { header, actual } => write!(
+                f,
+                "Header checksum mismatch: header says {header:#04x}, calculated {actual:#04x}"
+            ),
+            Self::GlobalChecksum { header, actual } => write!(
+                f,
+                "Global checksum mismatch: header says {header:#06x}, calculated {actual:#06x}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for HeaderError {}
+
+/// The cartridge header at 0x0100-0x014F of the ROM
+#[derive(Debug, Clone, PartialEq)]
+pub struct CartridgeHeader {
+    pub title: String,
+    /// Manufacturer code of newer cartridges, [`None`] for older ones
+    pub manufacturer_code: Option<String>,
+    pub cgb: CgbSupport,
+    /// Set if the game supports Super Game Boy functions
+    pub sgb: bool,
+    pub licensee: Licensee,
+    pub cartridge_type: CartridgeType,
+    /// ROM size in bytes
+    pub rom_size: usize,
+    /// External RAM size in bytes. For MBC2 this is 0, since the RAM is
+    /// built into the controller.
+    pub ram_size: usize,
+    pub header_checksum: u8,
+    pub global_checksum: u16,
+}
+
+impl CartridgeHeader {
+    /// Parse the header of `rom`
+    ///
+    /// # Returns
+    /// The parsed header, or a [`HeaderError`] if any of the fields can't be
+    /// decoded. Checksums are not verified, see
+    /// [`validate`](CartridgeHeader::validate).
+    pub fn parse(rom: &[u8]) -> Result<Self, HeaderError> {
+        if rom.len() < HEADER_END {
+            return Err(HeaderError::TooSmall(rom.len()));
+        }
+
+        let cgb = match rom[CGB_FLAG] {
+            0x80 => CgbSupport::Enhanced,
+            0xC0 => CgbSupport::Only,
+            _ => CgbSupport::None,
+        };
+
+        // The CGB flag and manufacturer code took over the end of the title
+        let (title, manufacturer_code) = match cgb {
+            CgbSupport::None => (ascii(&rom[TITLE]), None),
+            _ => {
+                let code = &rom[MANUFACTURER_CODE];
+                let manufacturer_code = code
+                    .iter()
+                    .all(u8::is_ascii_alphanumeric)
+                    .then(|| ascii(code));
+                let title_end = match manufacturer_code {
+                    Some(_) => MANUFACTURER_CODE.start,
+                    None => CGB_FLAG,
+                };
+                (ascii(&rom[TITLE.start..title_end]), manufacturer_code)
+            }
+        };
+
+        let licensee = match rom[OLD_LICENSEE_CODE] {
+            USE_NEW_LICENSEE_CODE => Licensee::New(ascii(&rom[NEW_LICENSEE_CODE])),
+            code => Licensee::Old(code),
+        };
+
+        let rom_size = match rom[ROM_SIZE] {
+            code @ 0x00..=0x08 => (2 * ROM_BANK_SIZE) << code,
+            code => return Err(HeaderError::InvalidRomSize(code)),
+        };
+
+        let ram_size = match rom[RAM_SIZE] {
+            0x00 => 0,
+            0x01 => RAM_BANK_SIZE / 4,
+            0x02 => RAM_BANK_SIZE,
+            0x03 => 4 * RAM_BANK_SIZE,
+            0x04 => 16 * RAM_BANK_SIZE,
+            0x05 => 8 * RAM_BANK_SIZE,
+            code => return Err(HeaderError::InvalidRamSize(code)),
+        };
+
+        Ok(Self {
+            title,
+            manufacturer_code,
+            cgb,
+            sgb: rom[SGB_FLAG] == 0x03,
+            licensee,
+            cartridge_type: CartridgeType::try_from(rom[CARTRIDGE_TYPE])?,
+            rom_size,
+            ram_size,
+            header_checksum: rom[HEADER_CHECKSUM],
+            global_checksum: u16::from_be_bytes([rom[GLOBAL_CHECKSUM], rom[GLOBAL_CHECKSUM + 1]]),
+        })
+    }
+
+    /// Check that the header is consistent with the rest of `rom`: the header
+    /// checksum and the size of the image must match. The global checksum is
+    /// checked separately by [`validate_global_checksum`](Self::validate_global_checksum).
+    pub fn validate(&self, rom: &[u8]) -> Result<(), HeaderError> {
+        let actual = header_checksum(rom);
+        if actual != self.header_checksum {
+            return Err(HeaderError::HeaderChecksum {
+                header: self.header_checksum,
+                actual,
+            });
+        }
+
+        if rom.len() != self.rom_size {
+            return Err(HeaderError::RomSizeMismatch {
+                header: self.rom_size,
+                actual: rom.len(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Check the global checksum of `rom`. The Game Boy never checks it, and
+    /// patched or homebrew ROMs often get it wrong, so a mismatch is no reason
+    /// to refuse a ROM.
+    pub fn validate_global_checksum(&self, rom: &[u8]) -> Result<(), HeaderError> {
+        let actual = global_checksum(rom);
+        if actual != self.global_checksum {
+            return Err(HeaderError::GlobalChecksum {
+                header: self.global_checksum,
+                actual,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Checksum over the header bytes, as verified by the boot ROM
+pub fn header_checksum(rom: &[u8]) -> u8 {
+    rom[HEADER_CHECKSUM_RANGE]
+        .iter()
+        .fold(0u8, |sum, byte| sum.wrapping_sub(*byte).wrapping_sub(1))
+}
+
+/// Sum of every byte in the ROM except the global checksum itself
+pub fn global_checksum(rom: &[u8]) -> u16 {
+    rom.iter()
+        .enumerate()
+        .filter(|(i, _)| *i != GLOBAL_CHECKSUM && *i != GLOBAL_CHECKSUM + 1)
+        .fold(0u16, |sum, (_, byte)| sum.wrapping_add(*byte as u16))
+}
+
+/// Decode a NUL padded ASCII string
+fn ascii(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .take_while(|byte| **byte != 0)
+        .map(|byte| *byte as char)
+        .collect()
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    /// Build a ROM of `banks` banks with a valid header
+    pub fn build_rom(banks: usize, cartridge_type: u8, ram_size: u8) -> Vec<u8> {
+        let mut rom = vec![0; banks * ROM_BANK_SIZE];
+        rom[TITLE.start..TITLE.start + 9].copy_from_slice(b"GIBBERISH");
+        rom[CARTRIDGE_TYPE] = cartridge_type;
+        rom[ROM_SIZE] = (banks / 2).trailing_zeros() as u8;
+        rom[RAM_SIZE] = ram_size;
+        fix_checksums(&mut rom);
+        rom
+    }
+
+    /// Create a ROM of `banks` banks where the first two bytes of every bank
+    /// are the bank number, without a header
+    pub fn numbered_rom(banks: usize) -> Vec<u8> {
+        let mut rom = vec![0; banks * ROM_BANK_SIZE];
+        for bank in 0..banks {
+            let [lo, hi] = (bank as u16).to_le_bytes();
+            rom[bank * ROM_BANK_SIZE] = lo;
+            rom[bank * ROM_BANK_SIZE + 1] = hi;
+        }
+        rom
+    }
+
+    pub fn fix_checksums(rom: &mut [u8]) {
+        rom[HEADER_CHECKSUM] = header_checksum(rom);
+        let [hi, lo] = global_checksum(rom).to_be_bytes();
+        rom[GLOBAL_CHECKSUM] = hi;
+        rom[GLOBAL_CHECKSUM + 1] = lo;
+    }
+
+    #[test]
+    fn parse() {
+        let mut rom = build_rom(8, 0x13, 0x03);
+        rom[OLD_LICENSEE_CODE] = 0x01;
+        rom[SGB_FLAG] = 0x03;
+        fix_checksums(&mut rom);
+
+        let header = CartridgeHeader::parse(&rom).unwrap();
+        assert_eq!(header.title, "GIBBERISH");
+        assert_eq!(header.manufacturer_code, None);
+        assert_eq!(header.cgb, CgbSupport::None);
+        assert!(header.sgb);
+        assert_eq!(header.licensee, Licensee::Old(0x01));
+        assert_eq!(header.cartridge_type.mapper, Mapper::Mbc3);
+        assert!(header.cartridge_type.ram);
+        assert!(header.cartridge_type.battery);
+        assert!(!header.cartridge_type.timer);
+        assert_eq!(header.rom_size, 128 * 1024);
+        assert_eq!(header.ram_size, 32 * 1024);
+        assert_eq!(header.validate(&rom), Ok(()));
+    }
+
+    #[test]
+    fn parse_cgb_header() {
+        let mut rom = build_rom(2, 0x00, 0x00);
+        rom[TITLE].copy_from_slice(b"CGBTITLE\0\0\0ABCD\x80");
+        rom[OLD_LICENSEE_CODE] = USE_NEW_LICENSEE_CODE;
+        rom[NEW_LICENSEE_CODE].copy_from_slice(b"01");
+
+        let header = CartridgeHeader::parse(&rom).unwrap();
+        assert_eq!(header.title, "CGBTITLE");
+        assert_eq!(header.manufacturer_code.as_deref(), Some("ABCD"));
+        assert_eq!(header.cgb, CgbSupport::Enhanced);
+        assert_eq!(header.licensee, Licensee::New("01".into()));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(
+            CartridgeHeader::parse(&[0; 0x100]),
+            Err(HeaderError::TooSmall(0x100))
+        );
+
+        let mut rom = build_rom(2, 0xFC, 0x00);
+        assert_eq!(
+            CartridgeHeader::parse(&rom),
+            Err(HeaderError::UnsupportedCartridgeType(0xFC))
+        );
+
+        rom[CARTRIDGE_TYPE] = 0x00;
+        rom[ROM_SIZE] = 0x52;
+        assert_eq!(
+            CartridgeHeader::parse(&rom),
+            Err(HeaderError::InvalidRomSize(0x52))
+        );
+
+        rom[ROM_SIZE] = 0x00;
+        rom[RAM_SIZE] = 0x06;
+        assert_eq!(
+            CartridgeHeader::parse(&rom),
+            Err(HeaderError::InvalidRamSize(0x06))
+        );
+    }
+
+    #[test]
+    fn validate_errors() {
+        let mut rom = build_rom(4, 0x01, 0x00);
+        let header = CartridgeHeader::parse(&rom).unwrap();
+
+        rom[0x200] = 0x01;
+        assert_eq!(header.validate(&rom), Ok(()));
+        assert!(matches!(
+            header.validate_global_checksum(&rom),
+            Err(HeaderError::GlobalChecksum { .. })
+        ));
+
+        rom[TITLE.start] = b'X';
+        assert!(matches!(
+            header.validate(&rom),
+            Err(HeaderError::HeaderChecksum { .. })
+        ));
+
+        let mut rom = build_rom(4, 0x01, 0x00);
+        rom.truncate(2 * ROM_BANK_SIZE);
+        assert_eq!(
+            header.validate(&rom),
+            Err(HeaderError::RomSizeMismatch {
+                header: 4 * ROM_BANK_SIZE,
+                actual: 2 * ROM_BANK_SIZE
+            })
+        );
+    }
+}
@@ -0,0 +1,261 @@
+use super::{read_rom, restore_ram, Cartridge, RAM_BANK_SIZE, ROM_BANK_SIZE};
+use crate::memory::map::{CART_END, CART_START, ERAM_END, ERAM_START};
+
+/// Cartridge with the MBC1 memory bank controller. Supports up to 2 MiB of
+/// ROM and 32 KiB of external RAM.
+///
+/// The controller is configured by writing to the ROM area:
+/// ```text
+/// 0x0000-0x1FFF: RAM enable. 0x_A in the low nybble enables, anything else
+///                disables.
+/// 0x2000-0x3FFF: ROM bank number, lower 5 bits. 0 is treated as 1.
+/// 0x4000-0x5FFF: RAM bank number, or upper 2 bits of the ROM bank number.
+/// 0x6000-0x7FFF: Banking mode select.
+///                  0: 0x0000-0x3FFF and 0xA000-0xBFFF are locked to bank 0
+///                  1: 0x0000-0x3FFF and 0xA000-0xBFFF use the upper bank
+///                     register
+/// ```
+#[derive(Debug, Clone)]
+pub struct Mbc1 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    /// Set if the RAM is battery-backed
+    battery: bool,
+    /// Set if the external RAM is enabled
+    ram_enabled: bool,
+    /// Lower 5 bits of the ROM bank number
+    rom_bank: u8,
+    /// 2-bit register used as either RAM bank number or upper bits of the
+    /// ROM bank number
+    upper_bank: u8,
+    /// Banking mode select. When set, the upper bank register also applies to
+    /// 0x0000-0x3FFF and external RAM.
+    advanced_banking: bool,
+}
+
+impl Mbc1 {
+    pub fn new(rom: Vec<u8>, ram_size: usize) -> Self {
+        Self {
+            rom,
+            ram: vec![0; ram_size],
+            battery: false,
+            ram_enabled: false,
+            rom_bank: 1,
+            upper_bank: 0,
+            advanced_banking: false,
+        }
+    }
+
+    /// Set whether the external RAM is kept alive by a battery
+    pub fn with_battery(self, battery: bool) -> Self {
+        Self { battery, ..self }
+    }
+
+    /// Number of ROM banks, rounded up to a power of two so it can be used to
+    /// mask out unused bank bits
+    fn rom_banks(&self) -> usize {
+        (self.rom.len() / ROM_BANK_SIZE).max(2).next_power_of_two()
+    }
+
+    /// Bank mapped at 0x0000-0x3FFF
+    fn low_rom_bank(&self) -> usize {
+        if self.advanced_banking {
+            ((self.upper_bank as usize) << 5) & (self.rom_banks() - 1)
+        } else {
+            0
+        }
+    }
+
+    /// Bank mapped at 0x4000-0x7FFF
+    fn high_rom_bank(&self) -> usize {
+        ((self.upper_bank as usize) << 5 | self.rom_bank as usize) & (self.rom_banks() - 1)
+    }
+
+    /// Offset into [`ram`](#structfield.ram) for an address in external RAM
+    fn ram_offset(&self, addr: u16) -> usize {
+        let bank = if self.advanced_banking {
+            self.upper_bank as usize
+        } else {
+            0
+        };
+        (bank * RAM_BANK_SIZE + (addr - ERAM_START) as usize) % self.ram.len()
+    }
+}
+
+impl Cartridge for Mbc1 {
+    fn read_byte(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x3FFF => read_rom(
+                &self.rom,
+                self.low_rom_bank() * ROM_BANK_SIZE + addr as usize,
+            ),
+            0x4000..=CART_END => read_rom(
+                &self.rom,
+                self.high_rom_bank() * ROM_BANK_SIZE + (addr - 0x4000) as usize,
+            ),
+            ERAM_START..=ERAM_END => {
+                if self.ram_enabled && !self.ram.is_empty() {
+                    self.ram[self.ram_offset(addr)]
+                } else {
+                    0xFF
+                }
+            }
+            _ => panic!("Invalid cartridge address: {:x}", addr),
+        }
+    }
+
+    fn write_byte(&mut self, byte: u8, addr: u16) {
+        match addr {
+            CART_START..=0x1FFF => self.ram_enabled = byte & 0x0F == 0x0A,
+            0x2000..=0x3FFF => {
+                self.rom_bank = byte & 0x1F;
+                if self.rom_bank == 0 {
+                    self.rom_bank = 1;
+                }
+            }
+            0x4000..=0x5FFF => self.upper_bank = byte & 0x03,
+            0x6000..=CART_END => self.advanced_banking = byte & 0x01 != 0,
+            ERAM_START..=ERAM_END => {
+                if self.ram_enabled && !self.ram.is_empty() {
+                    let offset = self.ram_offset(addr);
+                    self.ram[offset] = byte;
+                }
+            }
+            _ => panic!("Invalid cartridge address: {:x}", addr),
+        }
+    }
+
+    fn save_data(&self) -> Option<Vec<u8>> {
+        self.battery.then(|| self.ram.clone())
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        if self.battery {
+            restore_ram(&mut self.ram, data);
+        }
+    }
+
+    fn box_clone(&self) -> Box<dyn Cartridge> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::header::test::numbered_rom;
+    use super::*;
+
+    #[test]
+    fn rom_bank_switching() {
+        let mut mbc = Mbc1::new(numbered_rom(32), 0);
+        assert_eq!(mbc.read_byte(0x0000), 0);
+        assert_eq!(mbc.read_byte(0x4000), 1);
+
+        for bank in 1..32 {
+            mbc.write_byte(bank, 0x2000);
+            assert_eq!(mbc.read_byte(0x4000), bank);
+            assert_eq!(mbc.read_byte(0x0000), 0);
+        }
+    }
+
+    #[test]
+    fn rom_bank_zero_maps_to_one() {
+        let mut mbc = Mbc1::new(numbered_rom(32), 0);
+        mbc.write_byte(0x00, 0x2000);
+        assert_eq!(mbc.read_byte(0x4000), 1);
+
+        // Only the lower 5 bits are compared against 0
+        mbc.write_byte(0x20, 0x2000);
+        assert_eq!(mbc.read_byte(0x4000), 1);
+    }
+
+    #[test]
+    fn rom_bank_masked_to_rom_size() {
+        let mut mbc = Mbc1::new(numbered_rom(8), 0);
+        mbc.write_byte(0x09, 0x2000);
+        assert_eq!(mbc.read_byte(0x4000), 1);
+        mbc.write_byte(0x10, 0x2000);
+        assert_eq!(mbc.read_byte(0x4000), 0);
+    }
+
+    #[test]
+    fn rom_writes_do_not_modify_rom() {
+        let mut mbc = Mbc1::new(numbered_rom(4), 0);
+        mbc.write_byte(0xAB, 0x7FFF);
+        assert_eq!(mbc.read_byte(0x7FFF), 0);
+    }
+
+    #[test]
+    fn ram_enable() {
+        let mut mbc = Mbc1::new(numbered_rom(4), RAM_BANK_SIZE);
+        mbc.write_byte(0x12, ERAM_START);
+        assert_eq!(mbc.read_byte(ERAM_START), 0xFF);
+
+        mbc.write_byte(0x0A, 0x0000);
+        mbc.write_byte(0x12, ERAM_START);
+        assert_eq!(mbc.read_byte(ERAM_START), 0x12);
+
+        mbc.write_byte(0x00, 0x1FFF);
+        assert_eq!(mbc.read_byte(ERAM_START), 0xFF);
+
+        // Only the lower nybble matters
+        mbc.write_byte(0xFA, 0x1000);
+        assert_eq!(mbc.read_byte(ERAM_START), 0x12);
+    }
+
+    #[test]
+    fn no_ram() {
+        let mut mbc = Mbc1::new(numbered_rom(4), 0);
+        mbc.write_byte(0x0A, 0x0000);
+        mbc.write_byte(0x12, ERAM_START);
+        assert_eq!(mbc.read_byte(ERAM_START), 0xFF);
+    }
+
+    #[test]
+    fn ram_banking() {
+        let mut mbc = Mbc1::new(numbered_rom(4), 4 * RAM_BANK_SIZE);
+        mbc.write_byte(0x0A, 0x0000);
+        mbc.write_byte(0x01, 0x6000);
+
+        for bank in 0..4 {
+            mbc.write_byte(bank, 0x4000);
+            mbc.write_byte(0x10 + bank, ERAM_START);
+        }
+
+        for bank in 0..4 {
+            mbc.write_byte(bank, 0x4000);
+            assert_eq!(mbc.read_byte(ERAM_START), 0x10 + bank);
+        }
+
+        // Mode 0 locks external RAM to bank 0
+        mbc.write_byte(0x00, 0x6000);
+        assert_eq!(mbc.read_byte(ERAM_START), 0x10);
+    }
+
+    #[test]
+    fn large_rom_upper_bits() {
+        let mut mbc = Mbc1::new(numbered_rom(128), 0);
+        mbc.write_byte(0x02, 0x4000);
+        mbc.write_byte(0x05, 0x2000);
+        assert_eq!(mbc.read_byte(0x4000), 0x45);
+
+        // Bank 0x40 can't be selected in the upper area, 0x41 is used instead
+        mbc.write_byte(0x00, 0x2000);
+        assert_eq!(mbc.read_byte(0x4000), 0x41);
+
+        // Upper bits only apply to 0x0000-0x3FFF in mode 1
+        assert_eq!(mbc.read_byte(0x0000), 0x00);
+        mbc.write_byte(0x01, 0x6000);
+        assert_eq!(mbc.read_byte(0x0000), 0x40);
+    }
+
+    #[test]
+    fn large_rom_upper_bits_ignored_for_small_rom() {
+        let mut mbc = Mbc1::new(numbered_rom(32), 0);
+        mbc.write_byte(0x01, 0x6000);
+        mbc.write_byte(0x03, 0x4000);
+        mbc.write_byte(0x05, 0x2000);
+        assert_eq!(mbc.read_byte(0x4000), 0x05);
+        assert_eq!(mbc.read_byte(0x0000), 0x00);
+    }
+}
@@ -0,0 +1,182 @@
+use super::{read_rom, restore_ram, Cartridge, ROM_BANK_SIZE};
+use crate::memory::map::{CART_END, CART_START, ERAM_END, ERAM_START};
+
+/// Size of the RAM built into the MBC2 chip, in 4-bit cells
+const MBC2_RAM_SIZE: usize = 512;
+
+/// Cartridge with the MBC2 memory bank controller. Supports up to 256 KiB of
+/// ROM and has 512x4 bits of RAM built in. Only the lower nybble of each RAM
+/// cell is stored, and the 512 cells are mirrored across 0xA000-0xBFFF.
+///
+/// The controller is configured by writing to 0x0000-0x3FFF, where bit 8 of
+/// the address selects the register:
+/// ```text
+/// Bit 8 clear: RAM enable. 0x_A in the low nybble enables, anything else
+///              disables.
+/// Bit 8 set:   ROM bank number, lower 4 bits. 0 is treated as 1.
+/// ```
+#[derive(Debug, Clone)]
+pub struct Mbc2 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    /// Set if the RAM is battery-backed
+    battery: bool,
+    /// Set if the built-in RAM is enabled
+    ram_enabled: bool,
+    /// ROM bank mapped at 0x4000-0x7FFF
+    rom_bank: u8,
+}
+
+impl Mbc2 {
+    pub fn new(rom: Vec<u8>) -> Self {
+        Self {
+            rom,
+            ram: vec![0; MBC2_RAM_SIZE],
+            battery: false,
+            ram_enabled: false,
+            rom_bank: 1,
+        }
+    }
+
+    /// Set whether the built-in RAM is kept alive by a battery
+    pub fn with_battery(self, battery: bool) -> Self {
+        Self { battery, ..self }
+    }
+
+    fn rom_banks(&self) -> usize {
+        (self.rom.len() / ROM_BANK_SIZE).max(2)
+    }
+
+    /// Index into [`ram`](#structfield.ram) for an address in external RAM.
+    /// Only the lower 9 bits of the address are decoded.
+    fn ram_index(addr: u16) -> usize {
+        (addr - ERAM_START) as usize % MBC2_RAM_SIZE
+    }
+}
+
+impl Cartridge for Mbc2 {
+    fn read_byte(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x3FFF => read_rom(&self.rom, addr as usize),
+            0x4000..=CART_END => {
+                let bank = self.rom_bank as usize % self.rom_banks();
+                read_rom(&self.rom, bank * ROM_BANK_SIZE + (addr - 0x4000) as usize)
+            }
+            ERAM_START..=ERAM_END => {
+                if self.ram_enabled {
+                    // Upper nybble is not connected and reads as 1s
+                    0xF0 | self.ram[Self::ram_index(addr)]
+                } else {
+                    0xFF
+                }
+            }
+            _ => panic!("Invalid cartridge address: {:x}", addr),
+        }
+    }
+
+    fn write_byte(&mut self, byte: u8, addr: u16) {
+        match addr {
+            CART_START..=0x3FFF => {
+                if addr & 0x0100 == 0 {
+                    self.ram_enabled = byte & 0x0F == 0x0A;
+                } else {
+                    self.rom_bank = (byte & 0x0F).max(1);
+                }
+            }
+            0x4000..=CART_END => {}
+            ERAM_START..=ERAM_END => {
+                if self.ram_enabled {
+                    self.ram[Self::ram_index(addr)] = byte & 0x0F;
+                }
+            }
+            _ => panic!("Invalid cartridge address: {:x}", addr),
+        }
+    }
+
+    fn save_data(&self) -> Option<Vec<u8>> {
+        self.battery.then(|| self.ram.clone())
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        if self.battery {
+            restore_ram(&mut self.ram, data);
+            self.ram.iter_mut().for_each(|cell| *cell &= 0x0F);
+        }
+    }
+
+    fn box_clone(&self) -> Box<dyn Cartridge> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::header::test::numbered_rom;
+    use super::*;
+
+    #[test]
+    fn register_select_by_address_bit_8() {
+        let mut mbc = Mbc2::new(numbered_rom(16));
+
+        // Bit 8 clear: RAM enable, the ROM bank is left alone
+        mbc.write_byte(0x0A, 0x0000);
+        assert_eq!(mbc.read_byte(0x4000), 1);
+        mbc.write_byte(0x05, ERAM_START);
+        assert_eq!(mbc.read_byte(ERAM_START), 0xF5);
+
+        // Bit 8 set: ROM bank, RAM enable is left alone
+        mbc.write_byte(0x03, 0x0100);
+        assert_eq!(mbc.read_byte(0x4000), 3);
+        assert_eq!(mbc.read_byte(ERAM_START), 0xF5);
+
+        // Any address in 0x0000-0x3FFF works, as long as bit 8 matches
+        mbc.write_byte(0x0F, 0x3F00);
+        assert_eq!(mbc.read_byte(0x4000), 15);
+        mbc.write_byte(0x00, 0x30FF);
+        assert_eq!(mbc.read_byte(ERAM_START), 0xFF);
+        assert_eq!(mbc.read_byte(0x4000), 15);
+    }
+
+    #[test]
+    fn rom_bank_zero_maps_to_one() {
+        let mut mbc = Mbc2::new(numbered_rom(16));
+        mbc.write_byte(0x00, 0x2100);
+        assert_eq!(mbc.read_byte(0x4000), 1);
+
+        // Only the lower 4 bits are used
+        mbc.write_byte(0x12, 0x2100);
+        assert_eq!(mbc.read_byte(0x4000), 2);
+    }
+
+    #[test]
+    fn nybble_ram() {
+        let mut mbc = Mbc2::new(numbered_rom(2));
+        mbc.write_byte(0x0A, 0x0000);
+        mbc.write_byte(0xAB, ERAM_START + 0x10);
+        assert_eq!(mbc.read_byte(ERAM_START + 0x10), 0xFB);
+    }
+
+    #[test]
+    fn ram_mirroring() {
+        let mut mbc = Mbc2::new(numbered_rom(2));
+        mbc.write_byte(0x0A, 0x0000);
+        mbc.write_byte(0x07, ERAM_START + 0x123);
+
+        for mirror in 0..16 {
+            let addr = ERAM_START + mirror * 0x200 + 0x123;
+            assert_eq!(mbc.read_byte(addr), 0xF7);
+        }
+
+        mbc.write_byte(0x02, ERAM_END);
+        assert_eq!(mbc.read_byte(ERAM_START + 0x1FF), 0xF2);
+    }
+
+    #[test]
+    fn ram_disabled() {
+        let mut mbc = Mbc2::new(numbered_rom(2));
+        mbc.write_byte(0x05, ERAM_START);
+        assert_eq!(mbc.read_byte(ERAM_START), 0xFF);
+        mbc.write_byte(0x0A, 0x0000);
+        assert_eq!(mbc.read_byte(ERAM_START), 0xF0);
+    }
+}
@@ -0,0 +1,238 @@
+use super::rtc::{Clock, Rtc, RTC_DH, RTC_S};
+use super::{read_rom, restore_ram, Cartridge, RAM_BANK_SIZE, ROM_BANK_SIZE};
+use crate::memory::map::{CART_END, CART_START, ERAM_END, ERAM_START};
+
+/// Cartridge with the MBC3 memory bank controller. Supports up to 2 MiB of
+/// ROM, 32 KiB of external RAM and an optional real-time clock.
+///
+/// The controller is configured by writing to the ROM area:
+/// ```text
+/// 0x0000-0x1FFF: RAM and RTC enable. 0x0A enables, anything else disables.
+/// 0x2000-0x3FFF: ROM bank number, 7 bits. 0 is treated as 1.
+/// 0x4000-0x5FFF: 0x00-0x03 selects a RAM bank, 0x08-0x0C maps an RTC
+///                register into 0xA000-0xBFFF instead.
+/// 0x6000-0x7FFF: Latch clock data. Writing 0x00 and then 0x01 latches the
+///                RTC registers.
+/// ```
+#[derive(Debug, Clone)]
+pub struct Mbc3 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    /// Set if the RAM and RTC are battery-backed
+    battery: bool,
+    rtc: Option<Rtc>,
+    /// Set if the external RAM and RTC are enabled
+    ram_enabled: bool,
+    /// ROM bank mapped at 0x4000-0x7FFF
+    rom_bank: u8,
+    /// RAM bank or RTC register mapped at 0xA000-0xBFFF
+    ram_bank: u8,
+}
+
+impl Mbc3 {
+    pub fn new(rom: Vec<u8>, ram_size: usize) -> Self {
+        Self {
+            rom,
+            ram: vec![0; ram_size],
+            battery: false,
+            rtc: None,
+            ram_enabled: false,
+            rom_bank: 1,
+            ram_bank: 0,
+        }
+    }
+
+    /// Create a cartridge with a real-time clock following `clock`
+    pub fn with_rtc(rom: Vec<u8>, ram_size: usize, clock: Box<dyn Clock>) -> Self {
+        Self {
+            rtc: Some(Rtc::new(clock)),
+            ..Self::new(rom, ram_size)
+        }
+    }
+
+    /// Set whether the external RAM and RTC are kept alive by a battery
+    pub fn with_battery(self, battery: bool) -> Self {
+        Self { battery, ..self }
+    }
+
+    fn rom_banks(&self) -> usize {
+        (self.rom.len() / ROM_BANK_SIZE).max(2)
+    }
+
+    /// Offset into [`ram`](#structfield.ram) for an address in external RAM,
+    /// or [`None`] if there's no RAM in the selected bank
+    fn ram_offset(&self, addr: u16) -> Option<usize> {
+        let offset = self.ram_bank as usize * RAM_BANK_SIZE + (addr - ERAM_START) as usize;
+        (offset < self.ram.len()).then_some(offset)
+    }
+}
+
+impl Cartridge for Mbc3 {
+    fn read_byte(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x3FFF => read_rom(&self.rom, addr as usize),
+            0x4000..=CART_END => {
+                let bank = self.rom_bank as usize % self.rom_banks();
+                read_rom(&self.rom, bank * ROM_BANK_SIZE + (addr - 0x4000) as usize)
+            }
+            ERAM_START..=ERAM_END => {
+                if !self.ram_enabled {
+                    return 0xFF;
+                }
+
+                match (self.ram_bank, &self.rtc) {
+                    (RTC_S..=RTC_DH, Some(rtc)) => rtc.read(self.ram_bank),
+                    _ => self
+                        .ram_offset(addr)
+                        .map_or(0xFF, |offset| self.ram[offset]),
+                }
+            }
+            _ => panic!("Invalid cartridge address: {:x}", addr),
+        }
+    }
+
+    fn write_byte(&mut self, byte: u8, addr: u16) {
+        match addr {
+            CART_START..=0x1FFF => self.ram_enabled = byte & 0x0F == 0x0A,
+            0x2000..=0x3FFF => self.rom_bank = (byte & 0x7F).max(1),
+            0x4000..=0x5FFF => self.ram_bank = byte & 0x0F,
+            0x6000..=CART_END => {
+                if let Some(rtc) = &mut self.rtc {
+                    rtc.write_latch(byte);
+                }
+            }
+            ERAM_START..=ERAM_END => {
+                if !self.ram_enabled {
+                    return;
+                }
+
+                match (self.ram_bank, &mut self.rtc) {
+                    (RTC_S..=RTC_DH, Some(rtc)) => rtc.write(byte, self.ram_bank),
+                    _ => {
+                        if let Some(offset) = self.ram_offset(addr) {
+                            self.ram[offset] = byte;
+                        }
+                    }
+                }
+            }
+            _ => panic!("Invalid cartridge address: {:x}", addr),
+        }
+    }
+
+    /// The external RAM, followed by the RTC state if there is a clock
+    fn save_data(&self) -> Option<Vec<u8>> {
+        if !self.battery {
+            return None;
+        }
+
+        let mut data = self.ram.clone();
+        if let Some(rtc) = &self.rtc {
+            data.extend_from_slice(&rtc.save());
+        }
+        Some(data)
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        if !self.battery {
+            return;
+        }
+
+        restore_ram(&mut self.ram, data);
+        if let (Some(rtc), Some(rtc_data)) = (&mut self.rtc, data.get(self.ram.len()..)) {
+            rtc.load(rtc_data);
+        }
+    }
+
+    fn box_clone(&self) -> Box<dyn Cartridge> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::header::test::numbered_rom;
+    use super::super::rtc::{ManualClock, RTC_H, RTC_M};
+    use super::*;
+
+    #[test]
+    fn rom_bank_switching() {
+        let mut mbc = Mbc3::new(numbered_rom(128), 0);
+        assert_eq!(mbc.read_byte(0x4000), 1);
+
+        for bank in 1..128 {
+            mbc.write_byte(bank, 0x2000);
+            assert_eq!(mbc.read_byte(0x4000), bank);
+            assert_eq!(mbc.read_byte(0x0000), 0);
+        }
+
+        // Unlike MBC1, banks 0x20, 0x40 and 0x60 are reachable
+        mbc.write_byte(0x20, 0x2000);
+        assert_eq!(mbc.read_byte(0x4000), 0x20);
+
+        mbc.write_byte(0x00, 0x2000);
+        assert_eq!(mbc.read_byte(0x4000), 0x01);
+    }
+
+    #[test]
+    fn ram_banking() {
+        let mut mbc = Mbc3::new(numbered_rom(4), 4 * RAM_BANK_SIZE);
+        mbc.write_byte(0x0A, 0x0000);
+
+        for bank in 0..4 {
+            mbc.write_byte(bank, 0x4000);
+            mbc.write_byte(0x10 + bank, ERAM_START + 1);
+        }
+
+        for bank in 0..4 {
+            mbc.write_byte(bank, 0x4000);
+            assert_eq!(mbc.read_byte(ERAM_START + 1), 0x10 + bank);
+        }
+
+        mbc.write_byte(0x00, 0x0000);
+        assert_eq!(mbc.read_byte(ERAM_START + 1), 0xFF);
+    }
+
+    #[test]
+    fn rtc_registers() {
+        let clock = ManualClock::new(0);
+        let mut mbc = Mbc3::with_rtc(numbered_rom(4), RAM_BANK_SIZE, Box::new(clock.clone()));
+        mbc.write_byte(0x0A, 0x0000);
+
+        // Put something in RAM to make sure it's not clobbered by the RTC
+        mbc.write_byte(0x00, 0x4000);
+        mbc.write_byte(0x42, ERAM_START);
+
+        clock.advance(3600 + 60 + 1);
+        mbc.write_byte(0x00, 0x6000);
+        mbc.write_byte(0x01, 0x6000);
+
+        mbc.write_byte(RTC_S, 0x4000);
+        assert_eq!(mbc.read_byte(ERAM_START), 1);
+        mbc.write_byte(RTC_M, 0x4000);
+        assert_eq!(mbc.read_byte(ERAM_START), 1);
+        mbc.write_byte(RTC_H, 0x4000);
+        assert_eq!(mbc.read_byte(ERAM_START), 1);
+
+        // Registers can be set through the RAM area
+        mbc.write_byte(RTC_DH, 0x4000);
+        mbc.write_byte(0x40, ERAM_START);
+        mbc.write_byte(RTC_S, 0x4000);
+        mbc.write_byte(30, ERAM_START);
+        clock.advance(100);
+        mbc.write_byte(0x00, 0x6000);
+        mbc.write_byte(0x01, 0x6000);
+        assert_eq!(mbc.read_byte(ERAM_START), 30);
+
+        mbc.write_byte(0x00, 0x4000);
+        assert_eq!(mbc.read_byte(ERAM_START), 0x42);
+    }
+
+    #[test]
+    fn no_rtc() {
+        let mut mbc = Mbc3::new(numbered_rom(4), RAM_BANK_SIZE);
+        mbc.write_byte(0x0A, 0x0000);
+        mbc.write_byte(RTC_S, 0x4000);
+        mbc.write_byte(0x12, ERAM_START);
+        assert_eq!(mbc.read_byte(ERAM_START), 0xFF);
+    }
+}
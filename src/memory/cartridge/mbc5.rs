@@ -0,0 +1,228 @@
+use super::{read_rom, restore_ram, Cartridge, RAM_BANK_SIZE, ROM_BANK_SIZE};
+use crate::memory::map::{CART_END, CART_START, ERAM_END, ERAM_START};
+
+/// Cartridge with the MBC5 memory bank controller. Supports up to 8 MiB of
+/// ROM and 128 KiB of external RAM.
+///
+/// The controller is configured by writing to the ROM area:
+/// ```text
+/// 0x0000-0x1FFF: RAM enable. 0x_A in the low nybble enables, anything else
+///                disables.
+/// 0x2000-0x2FFF: Lower 8 bits of the ROM bank number. Bank 0 is allowed.
+/// 0x3000-0x3FFF: Bit 8 of the ROM bank number.
+/// 0x4000-0x5FFF: RAM bank number, 0x00-0x0F. On rumble cartridges, bit 3
+///                drives the rumble motor instead, leaving 8 RAM banks.
+/// ```
+#[derive(Debug, Clone)]
+pub struct Mbc5 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    /// Set if the RAM is battery-backed
+    battery: bool,
+    /// Set if bit 3 of the RAM bank register is wired to a rumble motor
+    has_rumble: bool,
+    /// Set if the external RAM is enabled
+    ram_enabled: bool,
+    /// 9-bit ROM bank mapped at 0x4000-0x7FFF
+    rom_bank: u16,
+    /// RAM bank mapped at 0xA000-0xBFFF
+    ram_bank: u8,
+    /// Set while the rumble motor is running
+    rumble: bool,
+}
+
+impl Mbc5 {
+    pub fn new(rom: Vec<u8>, ram_size: usize) -> Self {
+        Self {
+            rom,
+            ram: vec![0; ram_size],
+            battery: false,
+            has_rumble: false,
+            ram_enabled: false,
+            rom_bank: 1,
+            ram_bank: 0,
+            rumble: false,
+        }
+    }
+
+    /// Set whether the external RAM is kept alive by a battery
+    pub fn with_battery(self, battery: bool) -> Self {
+        Self { battery, ..self }
+    }
+
+    /// Create a cartridge with a rumble motor
+    pub fn with_rumble(rom: Vec<u8>, ram_size: usize) -> Self {
+        Self {
+            has_rumble: true,
+            ..Self::new(rom, ram_size)
+        }
+    }
+
+    fn rom_banks(&self) -> usize {
+        (self.rom.len() / ROM_BANK_SIZE).max(2)
+    }
+
+    /// Offset into [`ram`](#structfield.ram) for an address in external RAM,
+    /// or [`None`] if there's no RAM
+    fn ram_offset(&self, addr: u16) -> Option<usize> {
+        if self.ram.is_empty() {
+            return None;
+        }
+
+        let offset = self.ram_bank as usize * RAM_BANK_SIZE + (addr - ERAM_START) as usize;
+        Some(offset % self.ram.len())
+    }
+}
+
+impl Cartridge for Mbc5 {
+    fn read_byte(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x3FFF => read_rom(&self.rom, addr as usize),
+            0x4000..=CART_END => {
+                let bank = self.rom_bank as usize % self.rom_banks();
+                read_rom(&self.rom, bank * ROM_BANK_SIZE + (addr - 0x4000) as usize)
+            }
+            ERAM_START..=ERAM_END => match self.ram_offset(addr) {
+                Some(offset) if self.ram_enabled => self.ram[offset],
+                _ => 0xFF,
+            },
+            _ => panic!("Invalid cartridge address: {:x}", addr),
+        }
+    }
+
+    fn write_byte(&mut self, byte: u8, addr: u16) {
+        match addr {
+            CART_START..=0x1FFF => self.ram_enabled = byte & 0x0F == 0x0A,
+            0x2000..=0x2FFF => self.rom_bank = (self.rom_bank & 0x100) | byte as u16,
+            0x3000..=0x3FFF => self.rom_bank = (self.rom_bank & 0xFF) | (byte as u16 & 0x01) << 8,
+            0x4000..=0x5FFF => {
+                if self.has_rumble {
+                    self.rumble = byte & 0x08 != 0;
+                    self.ram_bank = byte & 0x07;
+                } else {
+                    self.ram_bank = byte & 0x0F;
+                }
+            }
+            0x6000..=CART_END => {}
+            ERAM_START..=ERAM_END => {
+                if let Some(offset) = self.ram_offset(addr).filter(|_| self.ram_enabled) {
+                    self.ram[offset] = byte;
+                }
+            }
+            _ => panic!("Invalid cartridge address: {:x}", addr),
+        }
+    }
+
+    fn rumble(&self) -> bool {
+        self.rumble
+    }
+
+    fn save_data(&self) -> Option<Vec<u8>> {
+        self.battery.then(|| self.ram.clone())
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        if self.battery {
+            restore_ram(&mut self.ram, data);
+        }
+    }
+
+    fn box_clone(&self) -> Box<dyn Cartridge> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::header::test::numbered_rom;
+    use super::*;
+
+    fn read_bank(mbc: &Mbc5) -> u16 {
+        u16::from_le_bytes([mbc.read_byte(0x4000), mbc.read_byte(0x4001)])
+    }
+
+    #[test]
+    fn nine_bit_rom_bank() {
+        let mut mbc = Mbc5::new(numbered_rom(512), 0);
+        assert_eq!(read_bank(&mbc), 1);
+
+        mbc.write_byte(0xFF, 0x2000);
+        assert_eq!(read_bank(&mbc), 0xFF);
+
+        mbc.write_byte(0x01, 0x3000);
+        assert_eq!(read_bank(&mbc), 0x1FF);
+
+        mbc.write_byte(0x23, 0x2FFF);
+        assert_eq!(read_bank(&mbc), 0x123);
+
+        mbc.write_byte(0x00, 0x3FFF);
+        assert_eq!(read_bank(&mbc), 0x23);
+    }
+
+    #[test]
+    fn rom_bank_zero() {
+        let mut mbc = Mbc5::new(numbered_rom(4), 0);
+        mbc.write_byte(0x00, 0x2000);
+        assert_eq!(read_bank(&mbc), 0);
+    }
+
+    #[test]
+    fn sixteen_ram_banks() {
+        let mut mbc = Mbc5::new(numbered_rom(4), 16 * RAM_BANK_SIZE);
+        mbc.write_byte(0x0A, 0x0000);
+
+        for bank in 0..16 {
+            mbc.write_byte(bank, 0x4000);
+            mbc.write_byte(0x20 + bank, ERAM_START);
+        }
+
+        for bank in 0..16 {
+            mbc.write_byte(bank, 0x4000);
+            assert_eq!(mbc.read_byte(ERAM_START), 0x20 + bank);
+        }
+
+        mbc.write_byte(0x00, 0x0000);
+        assert_eq!(mbc.read_byte(ERAM_START), 0xFF);
+        // Only the low nybble counts
+        mbc.write_byte(0x1A, 0x0000);
+        assert_eq!(mbc.read_byte(ERAM_START), 0x2F);
+    }
+
+    #[test]
+    fn rumble() {
+        let mut mbc = Mbc5::with_rumble(numbered_rom(4), 8 * RAM_BANK_SIZE);
+        mbc.write_byte(0x0A, 0x0000);
+        mbc.write_byte(0x01, 0x4000);
+        mbc.write_byte(0x11, ERAM_START);
+        assert!(!mbc.rumble());
+
+        // Motor on, RAM bank stays 1
+        mbc.write_byte(0x09, 0x4000);
+        assert!(mbc.rumble());
+        assert_eq!(mbc.read_byte(ERAM_START), 0x11);
+
+        mbc.write_byte(0x01, 0x4000);
+        assert!(!mbc.rumble());
+    }
+
+    #[test]
+    fn no_rumble_without_motor() {
+        let mut mbc = Mbc5::new(numbered_rom(4), 16 * RAM_BANK_SIZE);
+        mbc.write_byte(0x08, 0x4000);
+        assert!(!mbc.rumble());
+    }
+
+    #[test]
+    fn rumble_event() {
+        let mut cpu = crate::cpu::Cpu::reset();
+        cpu.load_cartridge(Box::new(Mbc5::with_rumble(numbered_rom(4), 0)));
+        assert_eq!(cpu.poll_rumble(), None);
+
+        cpu.write_byte(0x08, 0x4000);
+        assert_eq!(cpu.poll_rumble(), Some(true));
+        assert_eq!(cpu.poll_rumble(), None);
+
+        cpu.write_byte(0x00, 0x4000);
+        assert_eq!(cpu.poll_rumble(), Some(false));
+    }
+}
@@ -0,0 +1,296 @@
+mod header;
+mod mbc1;
+mod mbc2;
+mod mbc3;
+mod mbc5;
+mod rtc;
+mod save;
+
+use std::fmt::Debug;
+
+use super::map::{CART_END, CART_START, ERAM_END, ERAM_START};
+use super::ram::Ram;
+use header::{CartridgeHeader, HeaderError, Mapper};
+use rtc::SystemClock;
+
+pub use mbc1::Mbc1;
+pub use mbc2::Mbc2;
+pub use mbc3::Mbc3;
+pub use mbc5::Mbc5;
+pub use save::SaveFile;
+
+/// Size of one switchable ROM bank
+pub const ROM_BANK_SIZE: usize = 0x4000;
+/// Size of one switchable external RAM bank
+pub const RAM_BANK_SIZE: usize = 0x2000;
+
+/// A cartridge plugged into the Game Boy. The cartridge is responsible for
+/// the ROM area ([`CART_START`]..=[`CART_END`]) and the external RAM area
+/// ([`ERAM_START`]..=[`ERAM_END`]). Writes to the ROM area are used by
+/// cartridges with a memory bank controller to switch banks.
+pub trait Cartridge: Debug {
+    /// Read a byte from the ROM or external RAM area
+    fn read_byte(&self, addr: u16) -> u8;
+
+    /// Write a byte to the ROM or external RAM area
+    fn write_byte(&mut self, byte: u8, addr: u16);
+
+    /// Whether the cartridge's rumble motor is currently running. Always
+    /// false for cartridges without one.
+    fn rumble(&self) -> bool {
+        false
+    }
+
+    /// Contents of the battery-backed memory, to be persisted between runs.
+    /// [`None`] for cartridges without a battery.
+    fn save_data(&self) -> Option<Vec<u8>> {
+        None
+    }
+
+    /// Restore the battery-backed memory from data previously returned by
+    /// [`save_data`](Cartridge::save_data). Ignored by cartridges without a
+    /// battery.
+    fn load_save_data(&mut self, _data: &[u8]) {}
+
+    /// Clone the cartridge into a new box, so that the memory map stays
+    /// clonable
+    fn box_clone(&self) -> Box<dyn Cartridge>;
+}
+
+impl Clone for Box<dyn Cartridge> {
+    fn clone(&self) -> Self {
+        self.box_clone()
+    }
+}
+
+/// Parse and validate the header of `rom`, and create the cartridge it
+/// describes
+///
+/// # Returns
+/// * The cartridge, along with a [`HeaderError::GlobalChecksum`] to warn about
+///   if the global checksum doesn't match.
+/// * A [`HeaderError`] if the header is invalid or doesn't match the ROM
+///   image. Use [`load_unchecked`] to load such ROMs anyway.
+pub fn load(rom: Vec<u8>) -> Result<(Box<dyn Cartridge>, Option<HeaderError>), HeaderError> {
+    let header = CartridgeHeader::parse(&rom)?;
+    header.validate(&rom)?;
+    let warning = header.validate_global_checksum(&rom).err();
+    Ok((from_header(&header, rom), warning))
+}
+
+/// Create a cartridge without validating the header of `rom`. If the header
+/// can't be parsed at all, the ROM is loaded as a cartridge without a memory
+/// bank controller.
+pub fn load_unchecked(rom: Vec<u8>) -> Box<dyn Cartridge> {
+    match CartridgeHeader::parse(&rom) {
+        Ok(header) => from_header(&header, rom),
+        Err(_) => Box::new(RomOnly::new(rom, 0)),
+    }
+}
+
+/// Create the cartridge described by `header`, with the mapper and amount of
+/// external RAM given there
+pub fn from_header(header: &CartridgeHeader, rom: Vec<u8>) -> Box<dyn Cartridge> {
+    let cartridge_type = header.cartridge_type;
+    let battery = cartridge_type.battery;
+    let ram_size = if cartridge_type.ram {
+        header.ram_size
+    } else {
+        0
+    };
+
+    match cartridge_type.mapper {
+        Mapper::None => Box::new(RomOnly::new(rom, ram_size).with_battery(battery)),
+        Mapper::Mbc1 => Box::new(Mbc1::new(rom, ram_size).with_battery(battery)),
+        Mapper::Mbc2 => Box::new(Mbc2::new(rom).with_battery(battery)),
+        Mapper::Mbc3 if cartridge_type.timer => {
+            Box::new(Mbc3::with_rtc(rom, ram_size, Box::new(SystemClock)).with_battery(battery))
+        }
+        Mapper::Mbc3 => Box::new(Mbc3::new(rom, ram_size).with_battery(battery)),
+        Mapper::Mbc5 if cartridge_type.rumble => {
+            Box::new(Mbc5::with_rumble(rom, ram_size).with_battery(battery))
+        }
+        Mapper::Mbc5 => Box::new(Mbc5::new(rom, ram_size).with_battery(battery)),
+    }
+}
+
+/// Read a byte from `rom`, returning 0xFF for addresses past the end of the
+/// image, like an open bus would
+fn read_rom(rom: &[u8], addr: usize) -> u8 {
+    rom.get(addr).copied().unwrap_or(0xFF)
+}
+
+/// Copy as much of `data` as fits into `ram`, leaving the rest of `ram` as
+/// it was
+fn restore_ram(ram: &mut [u8], data: &[u8]) {
+    let len = ram.len().min(data.len());
+    ram[..len].copy_from_slice(&data[..len]);
+}
+
+/// Flat memory with no banking, where both the ROM and external RAM areas are
+/// writable. This is what the memory map starts out with before a cartridge
+/// is loaded, which lets programs be written straight into memory.
+#[derive(Debug, Clone)]
+pub struct FlatRam {
+    rom: Ram,
+    ram: Ram,
+}
+
+impl FlatRam {
+    pub fn new() -> Self {
+        Self {
+            rom: Ram::new(CART_START, CART_END),
+            ram: Ram::new(ERAM_START, ERAM_END),
+        }
+    }
+}
+
+impl Cartridge for FlatRam {
+    fn read_byte(&self, addr: u16) -> u8 {
+        match addr {
+            CART_START..=CART_END => self.rom.read_byte(addr),
+            ERAM_START..=ERAM_END => self.ram.read_byte(addr),
+            _ => panic!("Invalid cartridge address: {:x}", addr),
+        }
+    }
+
+    fn write_byte(&mut self, byte: u8, addr: u16) {
+        match addr {
+            CART_START..=CART_END => self.rom.write_byte(byte, addr),
+            ERAM_START..=ERAM_END => self.ram.write_byte(byte, addr),
+            _ => panic!("Invalid cartridge address: {:x}", addr),
+        }
+    }
+
+    fn box_clone(&self) -> Box<dyn Cartridge> {
+        Box::new(self.clone())
+    }
+}
+
+/// A 32 KiB cartridge without a memory bank controller. Writes to the ROM
+/// area are ignored. Some of these cartridges have up to 8 KiB of external
+/// RAM, which is always enabled.
+#[derive(Debug, Clone)]
+pub struct RomOnly {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    /// Set if the external RAM is battery-backed
+    battery: bool,
+}
+
+impl RomOnly {
+    pub fn new(rom: Vec<u8>, ram_size: usize) -> Self {
+        Self {
+            rom,
+            ram: vec![0; ram_size.min(RAM_BANK_SIZE)],
+            battery: false,
+        }
+    }
+
+    /// Set whether the external RAM is kept alive by a battery
+    pub fn with_battery(self, battery: bool) -> Self {
+        Self { battery, ..self }
+    }
+}
+
+impl Cartridge for RomOnly {
+    fn read_byte(&self, addr: u16) -> u8 {
+        match addr {
+            CART_START..=CART_END => read_rom(&self.rom, addr as usize),
+            ERAM_START..=ERAM_END => self
+                .ram
+                .get((addr - ERAM_START) as usize)
+                .copied()
+                .unwrap_or(0xFF),
+            _ => panic!("Invalid cartridge address: {:x}", addr),
+        }
+    }
+
+    fn write_byte(&mut self, byte: u8, addr: u16) {
+        match addr {
+            CART_START..=CART_END => {}
+            ERAM_START..=ERAM_END => {
+                if let Some(cell) = self.ram.get_mut((addr - ERAM_START) as usize) {
+                    *cell = byte;
+                }
+            }
+            _ => panic!("Invalid cartridge address: {:x}", addr),
+        }
+    }
+
+    fn save_data(&self) -> Option<Vec<u8>> {
+        self.battery.then(|| self.ram.clone())
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        if self.battery {
+            restore_ram(&mut self.ram, data);
+        }
+    }
+
+    fn box_clone(&self) -> Box<dyn Cartridge> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::header::test::{build_rom, fix_checksums};
+    use super::*;
+
+    #[test]
+    fn rom_only_ignores_writes() {
+        let mut rom = vec![0; 2 * ROM_BANK_SIZE];
+        rom[0x150] = 0xAB;
+        let mut cartridge = RomOnly::new(rom, 0);
+        cartridge.write_byte(0x12, 0x150);
+        assert_eq!(cartridge.read_byte(0x150), 0xAB);
+        assert_eq!(cartridge.read_byte(ERAM_START), 0xFF);
+    }
+
+    #[test]
+    fn load_selects_mapper() {
+        let mut rom = build_rom(4, 0x03, 0x02);
+        rom[2 * ROM_BANK_SIZE] = 0x42;
+        fix_checksums(&mut rom);
+        let (mut cartridge, _) = load(rom).unwrap();
+
+        // Switch to bank 2
+        cartridge.write_byte(0x02, 0x2000);
+        assert_eq!(cartridge.read_byte(0x4000), 0x42);
+
+        // External RAM is present
+        cartridge.write_byte(0x0A, 0x0000);
+        cartridge.write_byte(0x99, ERAM_START);
+        assert_eq!(cartridge.read_byte(ERAM_START), 0x99);
+    }
+
+    #[test]
+    fn rom_only_with_ram() {
+        let (mut cartridge, _) = load(build_rom(2, 0x08, 0x02)).unwrap();
+        cartridge.write_byte(0x34, ERAM_START + 0x1FFF);
+        assert_eq!(cartridge.read_byte(ERAM_START + 0x1FFF), 0x34);
+    }
+
+    #[test]
+    fn load_checks_header() {
+        let mut rom = build_rom(2, 0x00, 0x00);
+        rom[0x200] = 0x01;
+        let (cartridge, warning) = load(rom.clone()).unwrap();
+        assert_eq!(cartridge.read_byte(0x200), 0x01);
+        assert!(matches!(warning, Some(HeaderError::GlobalChecksum { .. })));
+
+        rom[0x134] = b'X';
+        assert!(matches!(
+            load(rom.clone()),
+            Err(HeaderError::HeaderChecksum { .. })
+        ));
+
+        let cartridge = load_unchecked(rom);
+        assert_eq!(cartridge.read_byte(0x134), b'X');
+
+        // Unparseable headers fall back to a plain ROM
+        let cartridge = load_unchecked(vec![0x12; 0x100]);
+        assert_eq!(cartridge.read_byte(0x0000), 0x12);
+    }
+}
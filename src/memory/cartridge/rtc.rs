@@ -0,0 +1,418 @@
+use std::fmt::Debug;
+#[cfg(test)]
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(test)]
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Source of wall clock time for the cartridge real-time clock
+pub trait Clock: Debug {
+    /// Seconds elapsed since a fixed point in time
+    fn now(&self) -> u64;
+
+    /// Clone the clock into a new box, so that cartridges stay clonable
+    fn box_clone(&self) -> Box<dyn Clock>;
+}
+
+impl Clone for Box<dyn Clock> {
+    fn clone(&self) -> Self {
+        self.box_clone()
+    }
+}
+
+/// Clock following the host system time
+#[derive(Debug, Clone, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0)
+    }
+
+    fn box_clone(&self) -> Box<dyn Clock> {
+        Box::new(self.clone())
+    }
+}
+
+/// Clock that only moves when told to. Clones share the same time, so a
+/// handle can be kept around to drive a clock that was given to a cartridge.
+#[cfg(test)]
+#[derive(Debug, Clone, Default)]
+pub struct ManualClock {
+    seconds: Arc<AtomicU64>,
+}
+
+#[cfg(test)]
+impl ManualClock {
+    pub fn new(seconds: u64) -> Self {
+        Self {
+            seconds: Arc::new(AtomicU64::new(seconds)),
+        }
+    }
+
+    /// Move the clock forward
+    pub fn advance(&self, seconds: u64) {
+        self.seconds.fetch_add(seconds, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+impl Clock for ManualClock {
+    fn now(&self) -> u64 {
+        self.seconds.load(Ordering::Relaxed)
+    }
+
+    fn box_clone(&self) -> Box<dyn Clock> {
+        Box::new(self.clone())
+    }
+}
+
+/// RAM bank number selecting the seconds register
+pub const RTC_S: u8 = 0x08;
+/// RAM bank number selecting the minutes register
+pub const RTC_M: u8 = 0x09;
+/// RAM bank number selecting the hours register
+pub const RTC_H: u8 = 0x0A;
+/// RAM bank number selecting the lower 8 bits of the day counter
+pub const RTC_DL: u8 = 0x0B;
+/// RAM bank number selecting the upper day counter bit and flags
+pub const RTC_DH: u8 = 0x0C;
+
+/// Size of the RTC state appended to save files, in the layout used by BGB
+/// and VBA-M: the five live registers and the five latched registers as
+/// 32-bit little endian values, followed by a 64-bit little endian UNIX
+/// timestamp of when the live registers were last valid.
+pub const RTC_SAVE_SIZE: usize = 48;
+/// Older versions of the format only had a 32-bit timestamp
+const RTC_SAVE_SIZE_SHORT: usize = 44;
+
+/// Halt flag in [`RTC_DH`]
+const DH_HALT: u8 = 1 << 6;
+/// Day counter carry flag in [`RTC_DH`]
+const DH_CARRY: u8 = 1 << 7;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RtcRegisters {
+    /// Seconds, 0-59
+    pub seconds: u8,
+    /// Minutes, 0-59
+    pub minutes: u8,
+    /// Hours, 0-23
+    pub hours: u8,
+    /// Lower 8 bits of the day counter
+    pub day_low: u8,
+    /// Upper day counter bit and flags.
+    /// ```text
+    /// xx00000x
+    /// ||     `- Bit 8 of the day counter
+    /// |`------- Halt (0: running, 1: stopped)
+    /// `-------- Day counter carry, set when the day counter overflows
+    /// ```
+    pub day_high: u8,
+}
+
+impl RtcRegisters {
+    fn read(&self, register: u8) -> u8 {
+        match register {
+            RTC_S => self.seconds & 0x3F,
+            RTC_M => self.minutes & 0x3F,
+            RTC_H => self.hours & 0x1F,
+            RTC_DL => self.day_low,
+            RTC_DH => self.day_high & (DH_CARRY | DH_HALT | 0x01),
+            _ => panic!("Invalid RTC register: {:x}", register),
+        }
+    }
+
+    fn to_words(self) -> [u32; 5] {
+        [
+            self.seconds,
+            self.minutes,
+            self.hours,
+            self.day_low,
+            self.day_high,
+        ]
+        .map(u32::from)
+    }
+
+    fn from_words(words: [u32; 5]) -> Self {
+        let [seconds, minutes, hours, day_low, day_high] = words.map(|word| word as u8);
+        Self {
+            seconds: seconds & 0x3F,
+            minutes: minutes & 0x3F,
+            hours: hours & 0x1F,
+            day_low,
+            day_high: day_high & (DH_CARRY | DH_HALT | 0x01),
+        }
+    }
+
+    fn days(&self) -> u64 {
+        ((self.day_high as u64 & 0x01) << 8) | self.day_low as u64
+    }
+
+    /// Count `seconds` forward, carrying into minutes, hours and days
+    fn advance(&mut self, seconds: u64) {
+        let seconds = self.seconds as u64 + seconds;
+        self.seconds = (seconds % 60) as u8;
+
+        let minutes = self.minutes as u64 + seconds / 60;
+        self.minutes = (minutes % 60) as u8;
+
+        let hours = self.hours as u64 + minutes / 60;
+        self.hours = (hours % 24) as u8;
+
+        let days = self.days() + hours / 24;
+        if days > 0x1FF {
+            self.day_high |= DH_CARRY;
+        }
+        self.day_low = days as u8;
+        self.day_high = (self.day_high & !0x01) | ((days >> 8) & 0x01) as u8;
+    }
+}
+
+/// The MBC3 real-time clock. The clock counts whenever it is not halted, and
+/// the game reads a snapshot of the counters taken by latching the clock.
+#[derive(Debug, Clone)]
+pub struct Rtc {
+    clock: Box<dyn Clock>,
+    /// Live counters
+    registers: RtcRegisters,
+    /// Counters as of the last latch, which is what the game reads
+    latched: RtcRegisters,
+    /// Time of the last update of the live counters
+    last_update: u64,
+    /// Set when 0x00 was written to the latch register, so that a following
+    /// 0x01 latches the counters
+    latch_armed: bool,
+}
+
+impl Rtc {
+    pub fn new(clock: Box<dyn Clock>) -> Self {
+        let last_update = clock.now();
+        Self {
+            clock,
+            registers: RtcRegisters::default(),
+            latched: RtcRegisters::default(),
+            last_update,
+            latch_armed: false,
+        }
+    }
+
+    fn halted(&self) -> bool {
+        self.registers.day_high & DH_HALT != 0
+    }
+
+    /// Bring the live counters up to date with the clock
+    fn update(&mut self) {
+        let now = self.clock.now();
+        let elapsed = now.saturating_sub(self.last_update);
+        self.last_update = now;
+        if !self.halted() {
+            self.registers.advance(elapsed);
+        }
+    }
+
+    /// Handle a write to the latch register. Writing 0x00 followed by 0x01
+    /// copies the live counters into the latched registers.
+    pub fn write_latch(&mut self, byte: u8) {
+        if self.latch_armed && byte == 0x01 {
+            self.update();
+            self.latched = self.registers;
+        }
+        self.latch_armed = byte == 0x00;
+    }
+
+    /// Read one of the latched registers, selected by RAM bank number
+    pub fn read(&self, register: u8) -> u8 {
+        self.latched.read(register)
+    }
+
+    /// Serialize the clock state to be appended to the save file. See
+    /// [`RTC_SAVE_SIZE`] for the layout.
+    pub fn save(&self) -> [u8; RTC_SAVE_SIZE] {
+        let mut data = [0; RTC_SAVE_SIZE];
+        let words = self.registers.to_words().into_iter();
+        let words = words.chain(self.latched.to_words());
+        for (chunk, word) in data.chunks_exact_mut(4).zip(words) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+        data[40..].copy_from_slice(&self.last_update.to_le_bytes());
+        data
+    }
+
+    /// Restore clock state written by [`save`](Rtc::save). The time that
+    /// passed since the state was saved is counted on the next update.
+    /// Data in an unknown format is ignored.
+    pub fn load(&mut self, data: &[u8]) {
+        let last_update = match data.len() {
+            RTC_SAVE_SIZE => u64::from_le_bytes(data[40..48].try_into().unwrap()),
+            RTC_SAVE_SIZE_SHORT => u32::from_le_bytes(data[40..44].try_into().unwrap()) as u64,
+            _ => return,
+        };
+
+        let word = |i: usize| u32::from_le_bytes(data[4 * i..4 * i + 4].try_into().unwrap());
+        self.registers = RtcRegisters::from_words([0, 1, 2, 3, 4].map(word));
+        self.latched = RtcRegisters::from_words([5, 6, 7, 8, 9].map(word));
+        self.last_update = last_update;
+    }
+
+    /// Write one of the live registers, selected by RAM bank number
+    pub fn write(&mut self, byte: u8, register: u8) {
+        self.update();
+        match register {
+            RTC_S => self.registers.seconds = byte & 0x3F,
+            RTC_M => self.registers.minutes = byte & 0x3F,
+            RTC_H => self.registers.hours = byte & 0x1F,
+            RTC_DL => self.registers.day_low = byte,
+            RTC_DH => self.registers.day_high = byte & (DH_CARRY | DH_HALT | 0x01),
+            _ => panic!("Invalid RTC register: {:x}", register),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn setup() -> (ManualClock, Rtc) {
+        let clock = ManualClock::new(1_000_000);
+        let rtc = Rtc::new(Box::new(clock.clone()));
+        (clock, rtc)
+    }
+
+    fn latch(rtc: &mut Rtc) {
+        rtc.write_latch(0x00);
+        rtc.write_latch(0x01);
+    }
+
+    #[test]
+    fn counts_with_clock() {
+        let (clock, mut rtc) = setup();
+        clock.advance(2 * 86400 + 3 * 3600 + 4 * 60 + 5);
+        latch(&mut rtc);
+        assert_eq!(rtc.read(RTC_S), 5);
+        assert_eq!(rtc.read(RTC_M), 4);
+        assert_eq!(rtc.read(RTC_H), 3);
+        assert_eq!(rtc.read(RTC_DL), 2);
+        assert_eq!(rtc.read(RTC_DH), 0);
+    }
+
+    #[test]
+    fn latched_value_is_stable() {
+        let (clock, mut rtc) = setup();
+        clock.advance(10);
+        latch(&mut rtc);
+        clock.advance(10);
+        assert_eq!(rtc.read(RTC_S), 10);
+
+        // Writing 0x01 without 0x00 first does not latch
+        rtc.write_latch(0x01);
+        assert_eq!(rtc.read(RTC_S), 10);
+
+        latch(&mut rtc);
+        assert_eq!(rtc.read(RTC_S), 20);
+    }
+
+    #[test]
+    fn day_counter_carry() {
+        let (clock, mut rtc) = setup();
+        rtc.write(0xFF, RTC_DL);
+        rtc.write(0x01, RTC_DH);
+        latch(&mut rtc);
+        assert_eq!(rtc.read(RTC_DL), 0xFF);
+        assert_eq!(rtc.read(RTC_DH), 0x01);
+
+        clock.advance(86400);
+        latch(&mut rtc);
+        assert_eq!(rtc.read(RTC_DL), 0x00);
+        assert_eq!(rtc.read(RTC_DH), DH_CARRY);
+
+        // Carry stays set until cleared by the game
+        clock.advance(86400);
+        latch(&mut rtc);
+        assert_eq!(rtc.read(RTC_DL), 0x01);
+        assert_eq!(rtc.read(RTC_DH), DH_CARRY);
+
+        rtc.write(0x00, RTC_DH);
+        latch(&mut rtc);
+        assert_eq!(rtc.read(RTC_DH), 0x00);
+    }
+
+    #[test]
+    fn halt_stops_counting() {
+        let (clock, mut rtc) = setup();
+        clock.advance(5);
+        rtc.write(DH_HALT, RTC_DH);
+        clock.advance(100);
+        latch(&mut rtc);
+        assert_eq!(rtc.read(RTC_S), 5);
+        assert_eq!(rtc.read(RTC_DH), DH_HALT);
+
+        rtc.write(0x00, RTC_DH);
+        clock.advance(1);
+        latch(&mut rtc);
+        assert_eq!(rtc.read(RTC_S), 6);
+    }
+
+    #[test]
+    fn write_registers() {
+        let (clock, mut rtc) = setup();
+        rtc.write(59, RTC_S);
+        rtc.write(59, RTC_M);
+        rtc.write(23, RTC_H);
+        clock.advance(1);
+        latch(&mut rtc);
+        assert_eq!(rtc.read(RTC_S), 0);
+        assert_eq!(rtc.read(RTC_M), 0);
+        assert_eq!(rtc.read(RTC_H), 0);
+        assert_eq!(rtc.read(RTC_DL), 1);
+    }
+
+    #[test]
+    fn save_and_load() {
+        let (clock, mut rtc) = setup();
+        clock.advance(3600 + 2);
+        latch(&mut rtc);
+        clock.advance(1);
+        rtc.write(0x01, RTC_DH);
+
+        let data = rtc.save();
+        assert_eq!(data.len(), RTC_SAVE_SIZE);
+        assert_eq!(&data[0..4], &[3, 0, 0, 0]);
+        assert_eq!(&data[8..12], &[1, 0, 0, 0]);
+        assert_eq!(&data[16..20], &[1, 0, 0, 0]);
+        assert_eq!(&data[20..24], &[2, 0, 0, 0]);
+        assert_eq!(&data[40..48], &(1_000_000u64 + 3603).to_le_bytes());
+
+        // Time keeps passing while the emulator isn't running
+        let (later, mut restored) = setup();
+        later.advance(3603 + 60);
+        restored.load(&data);
+        assert_eq!(restored.read(RTC_S), 2);
+        assert_eq!(restored.read(RTC_H), 1);
+
+        latch(&mut restored);
+        assert_eq!(restored.read(RTC_S), 3);
+        assert_eq!(restored.read(RTC_M), 1);
+        assert_eq!(restored.read(RTC_H), 1);
+        assert_eq!(restored.read(RTC_DH), 0x01);
+    }
+
+    #[test]
+    fn load_short_timestamp() {
+        let (_, mut rtc) = setup();
+        let mut data = [0; RTC_SAVE_SIZE_SHORT];
+        data[0] = 10;
+        data[40..44].copy_from_slice(&(1_000_000u32 - 5).to_le_bytes());
+        rtc.load(&data);
+        latch(&mut rtc);
+        assert_eq!(rtc.read(RTC_S), 15);
+
+        // Unknown sizes are ignored
+        rtc.load(&[0xFF; 12]);
+        latch(&mut rtc);
+        assert_eq!(rtc.read(RTC_S), 15);
+    }
+}
@@ -0,0 +1,166 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::Cartridge;
+
+/// Save file holding the battery-backed memory of a cartridge. The file
+/// contains the raw external RAM, followed by the RTC state for cartridges
+/// with a clock, which is the layout most other emulators use as well.
+#[derive(Debug)]
+pub struct SaveFile {
+    path: PathBuf,
+    /// Data as of the last load or flush, used to skip writing unchanged data
+    saved: Option<Vec<u8>>,
+}
+
+impl SaveFile {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            saved: None,
+        }
+    }
+
+    /// Save file next to the ROM at `rom_path`, with the extension replaced
+    /// by `.sav`
+    pub fn for_rom(rom_path: impl AsRef<Path>) -> Self {
+        Self::new(rom_path.as_ref().with_extension("sav"))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Restore the battery-backed memory of `cartridge` from the file. A
+    /// missing file is not an error, since that's the case for a game that
+    /// has never been saved.
+    pub fn load(&mut self, cartridge: &mut dyn Cartridge) -> io::Result<()> {
+        if cartridge.save_data().is_none() {
+            return Ok(());
+        }
+
+        let data = match fs::read(&self.path) {
+            Ok(data) => data,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(error) => return Err(error),
+        };
+
+        cartridge.load_save_data(&data);
+        self.saved = cartridge.save_data();
+        Ok(())
+    }
+
+    /// Write the battery-backed memory of `cartridge` to the file, if it has
+    /// changed since the last load or flush. The data is written to a
+    /// temporary file first, so that a crash can't leave a truncated save.
+    pub fn flush(&mut self, cartridge: &dyn Cartridge) -> io::Result<()> {
+        let data = cartridge.save_data();
+        if data.is_none() || data == self.saved {
+            return Ok(());
+        }
+
+        let temp_path = self.path.with_extension("sav.tmp");
+        fs::write(&temp_path, data.as_deref().unwrap_or_default())?;
+        fs::rename(&temp_path, &self.path)?;
+        self.saved = data;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::rtc::{ManualClock, SystemClock};
+    use super::super::{Mbc1, Mbc3, RomOnly, RAM_BANK_SIZE, ROM_BANK_SIZE};
+    use super::*;
+    use crate::memory::map::ERAM_START;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("gibberish-{}-{}", std::process::id(), name));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    fn rom() -> Vec<u8> {
+        vec![0; 2 * ROM_BANK_SIZE]
+    }
+
+    #[test]
+    fn for_rom() {
+        let save = SaveFile::for_rom("roms/game.gb");
+        assert_eq!(save.path(), Path::new("roms/game.sav"));
+    }
+
+    #[test]
+    fn round_trip() {
+        let path = temp_path("round_trip.sav");
+        let mut cartridge = Mbc1::new(rom(), RAM_BANK_SIZE).with_battery(true);
+        cartridge.write_byte(0x0A, 0x0000);
+        cartridge.write_byte(0x42, ERAM_START + 0x123);
+
+        let mut save = SaveFile::new(&path);
+        save.flush(&cartridge).unwrap();
+        let data = fs::read(&path).unwrap();
+        assert_eq!(data.len(), RAM_BANK_SIZE);
+        assert_eq!(data[0x123], 0x42);
+
+        let mut restored = Mbc1::new(rom(), RAM_BANK_SIZE).with_battery(true);
+        SaveFile::new(&path).load(&mut restored).unwrap();
+        restored.write_byte(0x0A, 0x0000);
+        assert_eq!(restored.read_byte(ERAM_START + 0x123), 0x42);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn rtc_footer() {
+        let path = temp_path("rtc_footer.sav");
+        let clock = Box::new(ManualClock::new(0));
+        let cartridge = Mbc3::with_rtc(rom(), RAM_BANK_SIZE, clock).with_battery(true);
+        SaveFile::new(&path).flush(&cartridge).unwrap();
+        assert_eq!(fs::read(&path).unwrap().len(), RAM_BANK_SIZE + 48);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn flush_skips_unchanged_data() {
+        let path = temp_path("unchanged.sav");
+        let mut cartridge = RomOnly::new(rom(), RAM_BANK_SIZE).with_battery(true);
+        let mut save = SaveFile::new(&path);
+        save.flush(&cartridge).unwrap();
+
+        fs::remove_file(&path).unwrap();
+        save.flush(&cartridge).unwrap();
+        assert!(!path.exists());
+
+        cartridge.write_byte(0x01, ERAM_START);
+        save.flush(&cartridge).unwrap();
+        assert!(path.exists());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn no_battery() {
+        let path = temp_path("no_battery.sav");
+        let mut cartridge = Mbc3::with_rtc(rom(), RAM_BANK_SIZE, Box::new(SystemClock));
+        let mut save = SaveFile::new(&path);
+        save.flush(&cartridge).unwrap();
+        assert!(!path.exists());
+
+        fs::write(&path, [0x42; RAM_BANK_SIZE]).unwrap();
+        save.load(&mut cartridge).unwrap();
+        cartridge.write_byte(0x0A, 0x0000);
+        assert_eq!(cartridge.read_byte(ERAM_START), 0x00);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn missing_file() {
+        let mut cartridge = RomOnly::new(rom(), RAM_BANK_SIZE).with_battery(true);
+        let mut save = SaveFile::new(temp_path("missing.sav"));
+        assert!(save.load(&mut cartridge).is_ok());
+    }
+}
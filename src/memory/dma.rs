@@ -0,0 +1,145 @@
+use super::map::{IRAM_ECHO_START, IRAM_START, SPRITE_ATTRS_START};
+
+/// Memory mapped location of the [`register`](#structfield.register).
+pub const DMA: u16 = 0xFF46;
+
+/// Bytes copied by a transfer, which is all of OAM
+const TRANSFER_LENGTH: u16 = 160;
+
+/// The OAM DMA controller. Writing the upper byte of a source address to
+/// [`DMA`] copies 160 bytes from there to OAM, one byte per machine cycle.
+/// While a transfer is running, the CPU can only access HRAM.
+#[derive(Debug, Clone)]
+pub struct Dma {
+    /// DMA source (R/W). Upper byte of the address to copy from.
+    register: u8,
+    /// Internal use. Number of bytes copied by the running transfer, or
+    /// [`None`] if no transfer is running.
+    copied: Option<u16>,
+}
+
+impl Dma {
+    pub fn new() -> Self {
+        Self {
+            register: 0xFF,
+            copied: None,
+        }
+    }
+
+    pub fn read_byte(&self) -> u8 {
+        self.register
+    }
+
+    /// Start a transfer from `byte` * 0x100. Starting a new transfer while one
+    /// is running restarts from the beginning.
+    pub fn write_byte(&mut self, byte: u8) {
+        self.register = byte;
+        self.copied = Some(0);
+    }
+
+    /// Check whether a transfer is running
+    pub fn active(&self) -> bool {
+        self.copied.is_some()
+    }
+
+    /// Advance the transfer by a machine cycle.
+    ///
+    /// # Returns
+    /// The source and destination addresses of the byte to copy this cycle,
+    /// or [`None`] if no transfer is running.
+    pub fn next(&mut self) -> Option<(u16, u16)> {
+        let copied = self.copied?;
+        self.copied = Some(copied + 1).filter(|copied| *copied < TRANSFER_LENGTH);
+        Some((self.source() + copied, SPRITE_ATTRS_START + copied))
+    }
+
+    /// Start of the source area. The DMA controller can't reach anything above
+    /// internal RAM, so sources from 0xE000 and up read internal RAM as well.
+    fn source(&self) -> u16 {
+        let source = (self.register as u16) << 8;
+        if source >= IRAM_ECHO_START {
+            source - IRAM_ECHO_START + IRAM_START
+        } else {
+            source
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::memory::map::{MemoryMap, HRAM_START};
+    use crate::memory::region::MemoryRegion;
+
+    /// Fill 160 bytes from `start` with an increasing pattern
+    fn fill(memory: &mut MemoryMap, start: u16) {
+        for i in 0..TRANSFER_LENGTH {
+            memory.write_byte(i as u8 ^ 0x5A, start + i);
+        }
+    }
+
+    fn oam(memory: &MemoryMap, index: u16) -> u8 {
+        memory.get_ppu().read_byte(SPRITE_ATTRS_START + index)
+    }
+
+    #[test]
+    fn copies_to_oam() {
+        let mut memory = MemoryMap::new();
+        fill(&mut memory, 0xC100);
+        memory.write_byte(0xC1, DMA);
+        memory.tick_dma(TRANSFER_LENGTH as usize);
+
+        for i in 0..TRANSFER_LENGTH {
+            assert_eq!(oam(&memory, i), i as u8 ^ 0x5A);
+        }
+        assert_eq!(memory.read_byte(DMA), 0xC1);
+    }
+
+    #[test]
+    fn one_byte_per_machine_cycle() {
+        let mut memory = MemoryMap::new();
+        fill(&mut memory, 0xC000);
+        memory.write_byte(0xC0, DMA);
+
+        memory.tick_dma(80);
+        assert_eq!(oam(&memory, 79), 79 ^ 0x5A);
+        assert_eq!(oam(&memory, 80), 0);
+
+        // The CPU can't reach the source until the transfer is done
+        memory.tick_dma(79);
+        assert_eq!(memory.peek_byte(0xC000), 0xFF);
+        memory.tick_dma(1);
+        assert_eq!(memory.peek_byte(0xC000), 0x5A);
+        assert_eq!(oam(&memory, 159), 159 ^ 0x5A);
+    }
+
+    #[test]
+    fn bus_restricted_to_hram() {
+        let mut memory = MemoryMap::new();
+        memory.write_byte(0x12, 0xC000);
+        memory.write_byte(0xC0, DMA);
+
+        assert_eq!(memory.read_byte(0xC000), 0xFF);
+        memory.write_byte(0x34, 0xC000);
+        memory.write_byte(0x56, HRAM_START);
+        assert_eq!(memory.read_byte(HRAM_START), 0x56);
+
+        memory.tick_dma(TRANSFER_LENGTH as usize);
+        assert_eq!(memory.read_byte(0xC000), 0x12);
+    }
+
+    #[test]
+    fn source_above_internal_ram() {
+        let mut memory = MemoryMap::new();
+        fill(&mut memory, 0xDE00);
+        memory.write_byte(0xFE, DMA);
+        memory.tick_dma(TRANSFER_LENGTH as usize);
+        assert_eq!(oam(&memory, 0), 0x5A);
+        assert_eq!(oam(&memory, 159), 159 ^ 0x5A);
+
+        fill(&mut memory, 0xC200);
+        memory.write_byte(0xE2, DMA);
+        memory.tick_dma(TRANSFER_LENGTH as usize);
+        assert_eq!(oam(&memory, 1), 1 ^ 0x5A);
+    }
+}
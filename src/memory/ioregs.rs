@@ -1,7 +1,11 @@
+use crate::apu::{Apu, NR10, WAVE_RAM_END};
+use crate::memory::joypad::{Joypad, P1};
+use crate::memory::serial::{Serial, SB, SC};
 use crate::memory::timer::DIV;
+use crate::ppu::WX;
 
 use super::{
-    map::{IO_REGS_END, IO_REGS_START},
+    map::{INT_FLAG_ADDR, IO_REGS_END},
     ram::Ram,
     region::MemoryRegion,
     timer::{TimerRegisters, TAC},
@@ -9,40 +13,75 @@ use super::{
 
 #[derive(Debug, Clone)]
 pub struct IoRegs {
-    p1: u8, // joypad
+    joypad: Joypad,
+    serial: Serial,
     timer: TimerRegisters,
-    others: Ram, // TODO
+    apu: Apu,
+    /// Unused registers up to and including IF
+    others: Ram,
+    /// Registers after the PPU's, unused on the original Game Boy
+    others_upper: Ram,
 }
 
 impl IoRegs {
     pub fn new() -> Self {
         Self {
-            p1: 0,
+            joypad: Joypad::new(),
+            serial: Serial::new(),
             timer: TimerRegisters::new(),
-            others: Ram::new(TAC + 1, IO_REGS_END),
+            apu: Apu::new(),
+            others: Ram::new(TAC + 1, INT_FLAG_ADDR),
+            others_upper: Ram::new(WX + 1, IO_REGS_END),
         }
     }
 
     pub fn get_timer_mut(&mut self) -> &mut TimerRegisters {
         &mut self.timer
     }
+
+    pub fn get_joypad_mut(&mut self) -> &mut Joypad {
+        &mut self.joypad
+    }
+
+    pub fn get_serial_mut(&mut self) -> &mut Serial {
+        &mut self.serial
+    }
+
+    pub fn get_apu_mut(&mut self) -> &mut Apu {
+        &mut self.apu
+    }
+
+    /// Run the APU for a number of machine cycles, clocking its frame
+    /// sequencer from the timer's DIV register
+    pub fn tick_apu(&mut self, machine_cycles: usize) {
+        let div = self.timer.read_byte(DIV);
+        self.apu.tick(machine_cycles, div);
+    }
 }
 
 impl MemoryRegion for IoRegs {
     fn read_byte(&self, addr: u16) -> u8 {
         match addr {
-            IO_REGS_START => self.p1,
+            P1 => self.joypad.read_byte(),
+            SB..=SC => self.serial.read_byte(addr),
             DIV..=TAC => self.timer.read_byte(addr),
-            IO_REGS_START..=IO_REGS_END => self.others.read_byte(addr),
+            NR10..=WAVE_RAM_END => self.apu.read_byte(addr),
+            0xFF03 => 0xFF, // unused
+            0xFF08..=INT_FLAG_ADDR => self.others.read_byte(addr),
+            0xFF4C..=IO_REGS_END => self.others_upper.read_byte(addr),
             _ => panic!("Invalid i/o register address: {:x}", addr),
         }
     }
 
     fn write_byte(&mut self, byte: u8, addr: u16) {
         match addr {
-            IO_REGS_START => self.p1 = byte,
+            P1 => self.joypad.write_byte(byte),
+            SB..=SC => self.serial.write_byte(byte, addr),
             DIV..=TAC => self.timer.write_byte(byte, addr),
-            IO_REGS_START..=IO_REGS_END => self.others.write_byte(byte, addr),
+            NR10..=WAVE_RAM_END => self.apu.write_byte(byte, addr),
+            0xFF03 => {}
+            0xFF08..=INT_FLAG_ADDR => self.others.write_byte(byte, addr),
+            0xFF4C..=IO_REGS_END => self.others_upper.write_byte(byte, addr),
             _ => panic!("Invalid i/o register address: {:x}", addr),
         }
     }
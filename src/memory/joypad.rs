@@ -0,0 +1,202 @@
+use crate::cpu::interrupts::Interrupt;
+
+/// Memory mapped location of the joypad register.
+pub const P1: u16 = 0xFF00;
+
+/// Select bits of [`P1`] that can be written
+const SELECT_MASK: u8 = 0b0011_0000;
+/// Cleared to read the direction buttons
+const SELECT_DIRECTIONS: u8 = 1 << 4;
+/// Cleared to read the action buttons
+const SELECT_ACTIONS: u8 = 1 << 5;
+
+/// The eight buttons of the Game Boy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Button {
+    Right,
+    Left,
+    Up,
+    Down,
+    A,
+    B,
+    Select,
+    Start,
+}
+
+impl Button {
+    /// Look up a button by name, ignoring case
+    pub fn parse(name: &str) -> Option<Self> {
+        let button = match name.to_ascii_lowercase().as_str() {
+            "right" => Self::Right,
+            "left" => Self::Left,
+            "up" => Self::Up,
+            "down" => Self::Down,
+            "a" => Self::A,
+            "b" => Self::B,
+            "select" => Self::Select,
+            "start" => Self::Start,
+            _ => return None,
+        };
+        Some(button)
+    }
+
+    /// Bit of the button in [`Joypad::pressed`]. The lower nybble holds the
+    /// direction buttons and the upper the action buttons, both in the order
+    /// of the P1 input lines.
+    fn mask(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+/// The joypad. The buttons are wired as a 2x4 matrix, where the game selects
+/// the direction and/or action row through P1 and reads the four input lines
+/// back. Pressed buttons pull their line low.
+#[derive(Debug, Clone)]
+pub struct Joypad {
+    /// Row select bits of P1 (R/W).
+    /// ```text
+    /// xx000000
+    ///   ||````- Input lines, 0 if pressed (read only)
+    ///   |`----- Select direction buttons (0: selected)
+    ///   `------ Select action buttons (0: selected)
+    /// ```
+    select: u8,
+    /// Currently pressed buttons, one bit per button
+    pressed: u8,
+    /// Internal use. Set when an input line went from high to low, until the
+    /// interrupt is taken.
+    interrupt: bool,
+}
+
+impl Joypad {
+    pub fn new() -> Self {
+        Self {
+            select: 0,
+            pressed: 0,
+            interrupt: false,
+        }
+    }
+
+    /// The four input lines, active low
+    fn lines(&self) -> u8 {
+        let mut pressed = 0;
+        if self.select & SELECT_DIRECTIONS == 0 {
+            pressed |= self.pressed & 0x0F;
+        }
+        if self.select & SELECT_ACTIONS == 0 {
+            pressed |= self.pressed >> 4;
+        }
+        !pressed & 0x0F
+    }
+
+    /// Run `change`, requesting an interrupt if it pulls any input line low
+    fn update(&mut self, change: impl FnOnce(&mut Self)) {
+        let before = self.lines();
+        change(self);
+        if before & !self.lines() != 0 {
+            self.interrupt = true;
+        }
+    }
+
+    pub fn press(&mut self, button: Button) {
+        self.update(|joypad| joypad.pressed |= button.mask());
+    }
+
+    pub fn release(&mut self, button: Button) {
+        self.update(|joypad| joypad.pressed &= !button.mask());
+    }
+
+    /// Take the joypad interrupt, if one was requested since the last call
+    ///
+    /// # Returns
+    /// * An [`Option<Interrupt>`] with the value [`Interrupt::Joypad`] if an
+    ///   input line went low, otherwise [`None`].
+    pub fn take_interrupt(&mut self) -> Option<Interrupt> {
+        std::mem::take(&mut self.interrupt).then_some(Interrupt::Joypad)
+    }
+
+    /// Read P1. The unused upper bits read as 1.
+    pub fn read_byte(&self) -> u8 {
+        0b1100_0000 | self.select | self.lines()
+    }
+
+    /// Write P1. Only the select bits can be written.
+    pub fn write_byte(&mut self, byte: u8) {
+        self.update(|joypad| joypad.select = byte & SELECT_MASK);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn nothing_pressed() {
+        let mut joypad = Joypad::new();
+        assert_eq!(joypad.read_byte(), 0xCF);
+        joypad.write_byte(0xFF);
+        assert_eq!(joypad.read_byte(), 0xFF);
+        joypad.write_byte(0x10);
+        assert_eq!(joypad.read_byte(), 0xDF);
+    }
+
+    #[test]
+    fn button_matrix() {
+        let mut joypad = Joypad::new();
+        joypad.press(Button::Down);
+        joypad.press(Button::A);
+        joypad.press(Button::Start);
+
+        joypad.write_byte(SELECT_ACTIONS);
+        assert_eq!(joypad.read_byte(), 0xE7);
+        joypad.write_byte(SELECT_DIRECTIONS);
+        assert_eq!(joypad.read_byte(), 0xD6);
+
+        // Both rows selected read as the combination
+        joypad.write_byte(0x00);
+        assert_eq!(joypad.read_byte(), 0xC6);
+        joypad.write_byte(SELECT_MASK);
+        assert_eq!(joypad.read_byte(), 0xFF);
+
+        joypad.release(Button::Start);
+        joypad.write_byte(SELECT_DIRECTIONS);
+        assert_eq!(joypad.read_byte(), 0xDE);
+    }
+
+    #[test]
+    fn interrupt_on_press() {
+        let mut joypad = Joypad::new();
+        joypad.write_byte(SELECT_DIRECTIONS);
+        joypad.press(Button::B);
+        assert!(matches!(joypad.take_interrupt(), Some(Interrupt::Joypad)));
+        assert!(joypad.take_interrupt().is_none());
+
+        // Releasing is a low to high transition
+        joypad.release(Button::B);
+        assert!(joypad.take_interrupt().is_none());
+    }
+
+    #[test]
+    fn no_interrupt_for_unselected_row() {
+        let mut joypad = Joypad::new();
+        joypad.write_byte(SELECT_ACTIONS);
+        joypad.press(Button::B);
+        assert!(joypad.take_interrupt().is_none());
+
+        // Selecting the row with the held button pulls the line low
+        joypad.write_byte(SELECT_DIRECTIONS);
+        assert!(matches!(joypad.take_interrupt(), Some(Interrupt::Joypad)));
+    }
+
+    #[test]
+    fn no_interrupt_if_line_already_low() {
+        let mut joypad = Joypad::new();
+        joypad.write_byte(0x00);
+        joypad.press(Button::A);
+        joypad.take_interrupt();
+
+        // Right shares the line with A
+        joypad.press(Button::Right);
+        assert!(joypad.take_interrupt().is_none());
+    }
+}
@@ -0,0 +1,359 @@
+use std::fmt::Debug;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use super::serial::SerialDevice;
+
+/// Message sent by the end driving a transfer, carrying its byte
+const TRANSFER: u8 = 0x01;
+/// Message sent back by the end that was clocked, carrying its byte
+const REPLY: u8 = 0x02;
+/// Bytes in a message: kind, sequence number and data
+const MESSAGE_LENGTH: usize = 3;
+
+/// How long a link partner gets to answer a transfer before the line is
+/// considered disconnected
+const LINK_TIMEOUT: Duration = Duration::from_millis(100);
+/// How long to sleep between reads while waiting for an answer, as the socket
+/// never blocks
+const RECEIVE_INTERVAL: Duration = Duration::from_micros(100);
+
+/// Address of a link cable socket. Unix domain sockets are given as
+/// `unix:PATH`, anything else is a TCP address like `127.0.0.1:8765`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Address {
+    Tcp(String),
+    #[cfg(unix)]
+    Unix(std::path::PathBuf),
+}
+
+impl Address {
+    pub fn parse(address: &str) -> Self {
+        #[cfg(unix)]
+        if let Some(path) = address.strip_prefix("unix:") {
+            return Self::Unix(path.into());
+        }
+        Self::Tcp(address.to_owned())
+    }
+}
+
+impl std::fmt::Display for Address {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Tcp(address) => write!(f, "{}", address),
+            #[cfg(unix)]
+            Self::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+/// The parts of TCP and Unix domain streams the link cable needs
+trait Socket: Read + Write + Debug + Send {
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()>;
+}
+
+impl Socket for TcpStream {
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        TcpStream::set_nonblocking(self, nonblocking)
+    }
+}
+
+#[cfg(unix)]
+impl Socket for UnixStream {
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        UnixStream::set_nonblocking(self, nonblocking)
+    }
+}
+
+#[derive(Debug)]
+struct Connection {
+    /// Non-blocking, so polling for a transfer doesn't hold up emulation
+    socket: Box<dyn Socket>,
+    /// Received bytes not making up a whole message yet
+    buffer: Vec<u8>,
+    /// Sequence number of the last transfer driven from this end
+    sequence: u8,
+    /// Cleared when the other end hangs up or the socket fails
+    open: bool,
+}
+
+impl Connection {
+    fn send(&mut self, kind: u8, sequence: u8, byte: u8) {
+        if self.open && self.socket.write_all(&[kind, sequence, byte]).is_err() {
+            self.open = false;
+        }
+    }
+
+    /// Receive the next message. Without a deadline this only looks at what
+    /// has already arrived, otherwise it waits until the deadline passes.
+    fn receive(&mut self, deadline: Option<Instant>) -> Option<[u8; MESSAGE_LENGTH]> {
+        while self.buffer.len() < MESSAGE_LENGTH {
+            if !self.open {
+                return None;
+            }
+
+            let mut chunk = [0; 64];
+            match self.socket.read(&mut chunk) {
+                Ok(0) => self.open = false,
+                Ok(length) => self.buffer.extend_from_slice(&chunk[..length]),
+                Err(error) if error.kind() == ErrorKind::Interrupted => {}
+                Err(error) if error.kind() == ErrorKind::WouldBlock => match deadline {
+                    Some(deadline) if Instant::now() < deadline => {
+                        std::thread::sleep(RECEIVE_INTERVAL)
+                    }
+                    _ => return None,
+                },
+                Err(_) => self.open = false,
+            }
+        }
+
+        let mut message = [0; MESSAGE_LENGTH];
+        message.copy_from_slice(&self.buffer[..MESSAGE_LENGTH]);
+        self.buffer.drain(..MESSAGE_LENGTH);
+        Some(message)
+    }
+}
+
+/// A link cable to another emulator over a local socket.
+///
+/// Each end decides for itself whether it drives a transfer, like the real
+/// cable. The end using its internal clock sends its byte and waits for the
+/// other end, which answers with its own byte when it's waiting for the
+/// external clock. Waiting keeps the two emulators in step for the duration
+/// of the transfer even though they otherwise run independently. If both
+/// ends drive a transfer at once, they exchange bytes the same way.
+///
+/// Transfers are numbered, so an answer arriving after the driving end gave
+/// up waiting is thrown away instead of being taken for the next answer.
+/// Likewise, anything that arrived before a transfer is driven is thrown
+/// away, so a transfer the other end gave up on isn't taken for one driven at
+/// the same time. Clones share the same connection.
+#[derive(Debug, Clone)]
+pub struct SocketLink {
+    connection: Arc<Mutex<Connection>>,
+}
+
+impl SocketLink {
+    fn new(socket: Box<dyn Socket>) -> io::Result<Self> {
+        socket.set_nonblocking(true)?;
+        Ok(Self {
+            connection: Arc::new(Mutex::new(Connection {
+                socket,
+                buffer: Vec::new(),
+                sequence: 0,
+                open: true,
+            })),
+        })
+    }
+
+    /// Wait for the other end to connect to `address`
+    pub fn listen(address: &Address) -> io::Result<Self> {
+        match address {
+            Address::Tcp(address) => {
+                let (stream, _) = TcpListener::bind(address)?.accept()?;
+                Self::from_tcp(stream)
+            }
+            #[cfg(unix)]
+            Address::Unix(path) => {
+                remove_stale_socket(path)?;
+                let listener = UnixListener::bind(path)?;
+                let accepted = listener.accept();
+                let _ = std::fs::remove_file(path);
+                Self::new(Box::new(accepted?.0))
+            }
+        }
+    }
+
+    /// Connect to the other end, listening on `address`
+    pub fn connect(address: &Address) -> io::Result<Self> {
+        match address {
+            Address::Tcp(address) => Self::from_tcp(TcpStream::connect(address)?),
+            #[cfg(unix)]
+            Address::Unix(path) => Self::new(Box::new(UnixStream::connect(path)?)),
+        }
+    }
+
+    fn from_tcp(stream: TcpStream) -> io::Result<Self> {
+        // Transfers are a few bytes each way, and waiting to batch them up
+        // would only stall the end waiting for an answer
+        stream.set_nodelay(true)?;
+        Self::new(Box::new(stream))
+    }
+}
+
+/// Remove a socket file left behind by an earlier run, so it can be bound
+/// again. Anything that isn't a socket is left alone.
+#[cfg(unix)]
+fn remove_stale_socket(path: &std::path::Path) -> io::Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => std::fs::remove_file(path),
+        _ => Ok(()),
+    }
+}
+
+impl SerialDevice for SocketLink {
+    fn transfer(&mut self, byte: u8) -> u8 {
+        let mut connection = self.connection.lock().unwrap();
+        connection.sequence = connection.sequence.wrapping_add(1);
+        let sequence = connection.sequence;
+        while connection.receive(None).is_some() {}
+        connection.send(TRANSFER, sequence, byte);
+
+        let deadline = Instant::now() + LINK_TIMEOUT;
+        while let Some([kind, number, data]) = connection.receive(Some(deadline)) {
+            match kind {
+                REPLY if number == sequence => return data,
+                // Both ends drove a transfer, so each gets the other's byte.
+                // The other end may have thrown our transfer away as stale,
+                // so answer it as well.
+                TRANSFER => {
+                    connection.send(REPLY, number, byte);
+                    return data;
+                }
+                _ => {}
+            }
+        }
+        0xFF
+    }
+
+    fn external_transfer(&mut self, byte: u8) -> Option<u8> {
+        let mut connection = self.connection.lock().unwrap();
+        while let Some([kind, number, data]) = connection.receive(None) {
+            if kind == TRANSFER {
+                connection.send(REPLY, number, byte);
+                return Some(data);
+            }
+        }
+        None
+    }
+
+    fn box_clone(&self) -> Box<dyn SerialDevice> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::memory::serial::{Serial, SB, SC};
+
+    /// Run a transfer on `serial` until it completes, returning SB
+    fn run_transfer(serial: &mut Serial, sb: u8, sc: u8) -> u8 {
+        serial.write_byte(sb, SB);
+        serial.write_byte(sc, SC);
+        while serial.tick(4).is_none() {}
+        serial.read_byte(SB)
+    }
+
+    fn tcp_pair() -> (SocketLink, SocketLink) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = Address::Tcp(listener.local_addr().unwrap().to_string());
+        let connecting = std::thread::spawn(move || SocketLink::connect(&address).unwrap());
+        let (stream, _) = listener.accept().unwrap();
+        (
+            SocketLink::from_tcp(stream).unwrap(),
+            connecting.join().unwrap(),
+        )
+    }
+
+    #[test]
+    fn parse_address() {
+        assert_eq!(
+            Address::parse("127.0.0.1:8765"),
+            Address::Tcp("127.0.0.1:8765".to_owned())
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            Address::parse("unix:/tmp/link.sock"),
+            Address::Unix("/tmp/link.sock".into())
+        );
+    }
+
+    #[test]
+    fn tcp_transfers() {
+        let (master, slave) = tcp_pair();
+        let slave = std::thread::spawn(move || {
+            let mut serial = Serial::new();
+            serial.connect(Box::new(slave));
+            let first = run_transfer(&mut serial, 0x10, 0x80);
+            let second = run_transfer(&mut serial, 0x20, 0x80);
+            (first, second)
+        });
+
+        let mut serial = Serial::new();
+        serial.connect(Box::new(master));
+        assert_eq!(run_transfer(&mut serial, 0xA1, 0x81), 0x10);
+        assert_eq!(run_transfer(&mut serial, 0xA2, 0x81), 0x20);
+        assert_eq!(slave.join().unwrap(), (0xA1, 0xA2));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn both_ends_driving() {
+        let (a, b) = UnixStream::pair().unwrap();
+        let (mut a, mut b) = (
+            SocketLink::new(Box::new(a)).unwrap(),
+            SocketLink::new(Box::new(b)).unwrap(),
+        );
+        let other = std::thread::spawn(move || b.transfer(0x22));
+        assert_eq!(a.transfer(0x11), 0x22);
+        assert_eq!(other.join().unwrap(), 0x11);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn late_reply_ignored() {
+        let (a, b) = UnixStream::pair().unwrap();
+        let (mut master, mut slave) = (
+            SocketLink::new(Box::new(a)).unwrap(),
+            SocketLink::new(Box::new(b)).unwrap(),
+        );
+
+        // Nobody is listening on the other end yet
+        assert_eq!(master.transfer(0x01), 0xFF);
+        assert_eq!(slave.external_transfer(0x99), Some(0x01));
+
+        let slave = std::thread::spawn(move || loop {
+            if let Some(byte) = slave.external_transfer(0x42) {
+                return byte;
+            }
+        });
+        assert_eq!(master.transfer(0x02), 0x42);
+        assert_eq!(slave.join().unwrap(), 0x02);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn stale_transfer_ignored() {
+        let (a, b) = UnixStream::pair().unwrap();
+        let (mut master, mut slave) = (
+            SocketLink::new(Box::new(a)).unwrap(),
+            SocketLink::new(Box::new(b)).unwrap(),
+        );
+
+        // The other end gives up driving a transfer nobody answered
+        assert_eq!(slave.transfer(0x01), 0xFF);
+
+        let slave = std::thread::spawn(move || loop {
+            if let Some(byte) = slave.external_transfer(0x42) {
+                return byte;
+            }
+        });
+        assert_eq!(master.transfer(0x02), 0x42);
+        assert_eq!(slave.join().unwrap(), 0x02);
+    }
+
+    #[test]
+    fn other_end_gone() {
+        let (mut master, slave) = tcp_pair();
+        drop(slave);
+        assert_eq!(master.transfer(0x01), 0xFF);
+        assert_eq!(master.external_transfer(0x01), None);
+    }
+}
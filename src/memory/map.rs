@@ -1,20 +1,23 @@
 use crate::memory::ram::Ram;
 use crate::memory::region::MemoryRegion;
+use crate::ppu::{Ppu, BGP, LCDC, LYC, WX};
 
+use super::cartridge::{Cartridge, FlatRam};
+use super::dma::{Dma, DMA};
 use super::ioregs::IoRegs;
+use super::watch::{Access, Watchpoints};
 
 #[derive(Debug, Clone)]
 pub struct MemoryMap {
-    // Temporarily model everything with the Ram struct
-    cartridge: Ram,
-    vram: Ram,
-    eram: Ram,
+    cartridge: Box<dyn Cartridge>,
+    ppu: Ppu,
     iram: Ram,
     iram_echo: Ram,
-    sprite_attrs: Ram,
     io_regs: IoRegs,
+    dma: Dma,
     hram: Ram,
     int_enable_reg: u8,
+    watchpoints: Watchpoints,
 }
 
 impl Default for MemoryMap {
@@ -26,15 +29,15 @@ impl Default for MemoryMap {
 impl MemoryMap {
     pub fn new() -> Self {
         Self {
-            cartridge: Ram::new(CART_START, CART_END),
-            vram: Ram::new(VRAM_START, VRAM_END),
-            eram: Ram::new(ERAM_START, ERAM_END),
+            cartridge: Box::new(FlatRam::new()),
+            ppu: Ppu::new(),
             iram: Ram::new(IRAM_START, IRAM_END),
             iram_echo: Ram::new(IRAM_ECHO_START, IRAM_ECHO_END),
-            sprite_attrs: Ram::new(SPRITE_ATTRS_START, SPRITE_ATTRS_END),
             io_regs: IoRegs::new(),
+            dma: Dma::new(),
             hram: Ram::new(HRAM_START, HRAM_END),
             int_enable_reg: 0,
+            watchpoints: Watchpoints::default(),
         }
     }
 
@@ -42,14 +45,77 @@ impl MemoryMap {
         &mut self.io_regs
     }
 
+    pub fn get_ppu(&self) -> &Ppu {
+        &self.ppu
+    }
+
+    pub fn get_ppu_mut(&mut self) -> &mut Ppu {
+        &mut self.ppu
+    }
+
+    pub fn get_cartridge(&self) -> &dyn Cartridge {
+        self.cartridge.as_ref()
+    }
+
+    /// Replace the cartridge mapped into the ROM and external RAM areas
+    pub fn load_cartridge(&mut self, cartridge: Box<dyn Cartridge>) {
+        self.cartridge = cartridge;
+    }
+
+    pub fn watchpoints(&self) -> &Watchpoints {
+        &self.watchpoints
+    }
+
+    pub fn watchpoints_mut(&mut self) -> &mut Watchpoints {
+        &mut self.watchpoints
+    }
+
+    /// Check whether a running OAM DMA transfer is keeping the CPU away from
+    /// `addr`
+    fn dma_blocks(&self, addr: u16) -> bool {
+        // IE and IF live in the CPU, so they stay reachable along with HRAM
+        self.dma.active() && !matches!(addr, HRAM_START..=INT_ENABLE_ADDR | INT_FLAG_ADDR)
+    }
+
+    /// Run the OAM DMA controller, copying a byte to OAM every machine cycle
+    /// while a transfer is running
+    pub fn tick_dma(&mut self, machine_cycles: usize) {
+        for _ in 0..machine_cycles {
+            let Some((source, destination)) = self.dma.next() else {
+                break;
+            };
+            let byte = self.read_byte_unrestricted(source);
+            self.ppu.write_byte(byte, destination);
+        }
+    }
+
+    /// Read a byte as the CPU, checking watchpoints
     pub fn read_byte(&self, addr: u16) -> u8 {
+        let byte = self.peek_byte(addr);
+        self.watchpoints.check(addr, Access::Read, byte);
+        byte
+    }
+
+    /// Read a byte as seen by the CPU, without it counting as an access for
+    /// watchpoints. During OAM DMA, anything outside of HRAM reads as 0xFF.
+    pub fn peek_byte(&self, addr: u16) -> u8 {
+        if self.dma_blocks(addr) {
+            return 0xFF;
+        }
+        self.read_byte_unrestricted(addr)
+    }
+
+    /// Read a byte, ignoring any bus restrictions
+    fn read_byte_unrestricted(&self, addr: u16) -> u8 {
         match addr {
             CART_START..=CART_END => self.cartridge.read_byte(addr),
-            VRAM_START..=VRAM_END => self.vram.read_byte(addr),
-            ERAM_START..=ERAM_END => self.eram.read_byte(addr),
+            VRAM_START..=VRAM_END => self.ppu.read_byte(addr),
+            ERAM_START..=ERAM_END => self.cartridge.read_byte(addr),
             IRAM_START..=IRAM_END => self.iram.read_byte(addr),
             IRAM_ECHO_START..=IRAM_ECHO_END => self.iram_echo.read_byte(addr),
-            SPRITE_ATTRS_START..=SPRITE_ATTRS_END => self.sprite_attrs.read_byte(addr),
+            SPRITE_ATTRS_START..=SPRITE_ATTRS_END => self.ppu.read_byte(addr),
+            LCDC..=LYC | BGP..=WX => self.ppu.read_byte(addr),
+            DMA => self.dma.read_byte(),
             IO_REGS_START..=IO_REGS_END => self.io_regs.read_byte(addr),
             HRAM_START..=HRAM_END => self.hram.read_byte(addr),
             INT_ENABLE_ADDR => self.int_enable_reg,
@@ -59,11 +125,25 @@ impl MemoryMap {
         }
     }
 
+    /// Write a byte as the CPU, checking watchpoints
     pub fn write_byte(&mut self, byte: u8, addr: u16) {
+        if !self.dma_blocks(addr) {
+            self.watchpoints.check(addr, Access::Write, byte);
+        }
+        self.poke_byte(byte, addr);
+    }
+
+    /// Write a byte as the CPU, without it counting as an access for
+    /// watchpoints. During OAM DMA, writes outside of HRAM are ignored.
+    pub fn poke_byte(&mut self, byte: u8, addr: u16) {
+        if self.dma_blocks(addr) {
+            return;
+        }
+
         match addr {
             CART_START..=CART_END => self.cartridge.write_byte(byte, addr),
-            VRAM_START..=VRAM_END => self.vram.write_byte(byte, addr),
-            ERAM_START..=ERAM_END => self.eram.write_byte(byte, addr),
+            VRAM_START..=VRAM_END => self.ppu.write_byte(byte, addr),
+            ERAM_START..=ERAM_END => self.cartridge.write_byte(byte, addr),
 
             // Make sure internal ram gets echoed
             IRAM_START..=IRAM_END => {
@@ -79,7 +159,9 @@ impl MemoryMap {
                     .write_byte(byte, addr - IRAM_ECHO_START + IRAM_START);
             }
 
-            SPRITE_ATTRS_START..=SPRITE_ATTRS_END => self.sprite_attrs.write_byte(byte, addr),
+            SPRITE_ATTRS_START..=SPRITE_ATTRS_END => self.ppu.write_byte(byte, addr),
+            LCDC..=LYC | BGP..=WX => self.ppu.write_byte(byte, addr),
+            DMA => self.dma.write_byte(byte),
             IO_REGS_START..=IO_REGS_END => self.io_regs.write_byte(byte, addr),
             HRAM_START..=HRAM_END => self.hram.write_byte(byte, addr),
             INT_ENABLE_ADDR => self.int_enable_reg = byte,
@@ -89,35 +171,60 @@ impl MemoryMap {
         }
     }
 
+    /// Read a word as the CPU, checking watchpoints for both bytes
     pub fn read_word(&self, addr: u16) -> u16 {
+        let word = self.peek_word(addr);
+        let [lo, hi] = word.to_le_bytes();
+        self.watchpoints.check(addr, Access::Read, lo);
+        self.watchpoints
+            .check(addr.wrapping_add(1), Access::Read, hi);
+        word
+    }
+
+    /// Read a word like `peek_byte` reads a byte. The two bytes may belong to
+    /// different regions or registers.
+    pub fn peek_word(&self, addr: u16) -> u16 {
+        if self.dma.active() || ends_region(addr) {
+            return self.peek_bytes(addr);
+        }
+
         match addr {
-            CART_START..=CART_END => self.cartridge.read_word(addr),
-            VRAM_START..=VRAM_END => self.vram.read_word(addr),
-            ERAM_START..=ERAM_END => self.eram.read_word(addr),
+            VRAM_START..=VRAM_END => self.ppu.read_word(addr),
             IRAM_START..=IRAM_END => self.iram.read_word(addr),
             IRAM_ECHO_START..=IRAM_ECHO_END => self.iram_echo.read_word(addr),
-            SPRITE_ATTRS_START..=SPRITE_ATTRS_END => self.sprite_attrs.read_word(addr),
-            IO_REGS_START..=IO_REGS_END => self.io_regs.read_word(addr),
+            SPRITE_ATTRS_START..=SPRITE_ATTRS_END => self.ppu.read_word(addr),
             HRAM_START..=HRAM_END => self.hram.read_word(addr),
-            _ => {
-                panic!("Unusable address: {:x}", addr);
-            }
+            // The cartridge and the registers
+            _ => self.peek_bytes(addr),
         }
     }
 
+    fn peek_bytes(&self, addr: u16) -> u16 {
+        let lo = self.peek_byte(addr);
+        let hi = self.peek_byte(addr.wrapping_add(1));
+        u16::from_le_bytes([lo, hi])
+    }
+
+    /// Write a word as the CPU, checking watchpoints for both bytes
     pub fn write_word(&mut self, word: u16, addr: u16) {
+        let [lo, hi] = word.to_le_bytes();
+        if self.dma.active() || ends_region(addr) {
+            self.write_byte(lo, addr);
+            self.write_byte(hi, addr.wrapping_add(1));
+            return;
+        }
+        self.watchpoints.check(addr, Access::Write, lo);
+        self.watchpoints
+            .check(addr.wrapping_add(1), Access::Write, hi);
+
         match addr {
-            CART_START..=CART_END => self.cartridge.write_word(word, addr),
-            VRAM_START..=VRAM_END => self.vram.write_word(word, addr),
-            ERAM_START..=ERAM_END => self.eram.write_word(word, addr),
+            VRAM_START..=VRAM_END => self.ppu.write_word(word, addr),
 
             // Make sure internal ram gets echoed
-            IRAM_START..=IRAM_END => {
+            IRAM_START..=IRAM_END if addr - IRAM_START < IRAM_ECHO_END - IRAM_ECHO_START => {
                 self.iram.write_word(word, addr);
-                if addr - IRAM_START <= IRAM_ECHO_END - IRAM_ECHO_START {
-                    self.iram_echo
-                        .write_word(word, addr - IRAM_START + IRAM_ECHO_START);
-                }
+                self.iram_echo
+                    .write_word(word, addr - IRAM_START + IRAM_ECHO_START);
             }
             IRAM_ECHO_START..=IRAM_ECHO_END => {
                 self.iram_echo.write_word(word, addr);
@@ -125,11 +232,12 @@ impl MemoryMap {
                     .write_word(word, addr - IRAM_ECHO_START + IRAM_START);
             }
 
-            SPRITE_ATTRS_START..=SPRITE_ATTRS_END => self.sprite_attrs.write_word(word, addr),
-            IO_REGS_START..=IO_REGS_END => self.io_regs.write_word(word, addr),
+            SPRITE_ATTRS_START..=SPRITE_ATTRS_END => self.ppu.write_word(word, addr),
             HRAM_START..=HRAM_END => self.hram.write_word(word, addr),
+            // The cartridge, the registers and internal RAM past its echo
             _ => {
-                panic!("Unusable address: {:x}", addr);
+                self.poke_byte(lo, addr);
+                self.poke_byte(hi, addr.wrapping_add(1));
             }
         }
     }
@@ -148,13 +256,81 @@ pub const IRAM_ECHO_END: u16 = 0xFDFF;
 pub const SPRITE_ATTRS_START: u16 = 0xFE00;
 pub const SPRITE_ATTRS_END: u16 = 0xFE9F;
 
-#[allow(dead_code)]
 pub const UNUSABLE_START: u16 = 0xFEA0;
-#[allow(dead_code)]
 pub const UNUSABLE_END: u16 = 0xFEFF;
 
 pub const IO_REGS_START: u16 = 0xFF00;
 pub const IO_REGS_END: u16 = 0xFF7F;
+pub const INT_FLAG_ADDR: u16 = 0xFF0F;
 pub const HRAM_START: u16 = 0xFF80;
 pub const HRAM_END: u16 = 0xFFFE;
 pub const INT_ENABLE_ADDR: u16 = 0xFFFF;
+
+/// Check whether a word at `addr` would straddle two memory regions
+fn ends_region(addr: u16) -> bool {
+    matches!(
+        addr,
+        VRAM_END | IRAM_END | IRAM_ECHO_END | SPRITE_ATTRS_END | HRAM_END
+    )
+}
+
+/// Check whether `addr` can be accessed at all. Reading or writing the area
+/// between OAM and the I/O registers panics.
+pub fn usable(addr: u16) -> bool {
+    !(UNUSABLE_START..=UNUSABLE_END).contains(&addr)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn words_across_boundaries() {
+        let mut memory = MemoryMap::new();
+
+        // LYC and DMA
+        memory.write_word(0xC042, LYC);
+        assert!(memory.dma.active());
+        memory.tick_dma(160);
+        assert_eq!(memory.peek_byte(LYC), 0x42);
+
+        // Wave RAM and LCDC
+        memory.write_word(0x91AB, 0xFF3F);
+        assert_eq!(memory.peek_byte(0xFF3F), 0xAB);
+        assert_eq!(memory.peek_byte(LCDC), 0x91);
+
+        // WX and the unmapped registers after it
+        memory.write_byte(0x07, WX);
+        assert_eq!(memory.peek_word(WX) & 0xFF, 0x07);
+
+        // Internal RAM and its echo
+        memory.write_word(0x1234, IRAM_END);
+        assert_eq!(memory.peek_byte(IRAM_END), 0x34);
+        assert_eq!(memory.peek_byte(IRAM_START), 0x12);
+
+        // HRAM and IE
+        memory.write_word(0x1F80, HRAM_END);
+        assert_eq!(memory.read_word(HRAM_END), 0x1F80);
+    }
+
+    #[test]
+    fn words_within_regions() {
+        let mut memory = MemoryMap::new();
+
+        memory.write_word(0xBEEF, IRAM_START);
+        assert_eq!(memory.read_word(IRAM_ECHO_START), 0xBEEF);
+
+        memory.write_word(0xCAFE, IRAM_ECHO_END - 1);
+        assert_eq!(
+            memory.read_word(IRAM_ECHO_END - 1 - IRAM_ECHO_START + IRAM_START),
+            0xCAFE
+        );
+
+        memory.write_word(0x1234, 0xDDFF);
+        assert_eq!(memory.peek_byte(IRAM_ECHO_END), 0x34);
+        assert_eq!(memory.read_word(0xDDFF), 0x1234);
+
+        memory.write_word(0x5678, VRAM_START);
+        assert_eq!(memory.read_word(VRAM_START), 0x5678);
+    }
+}
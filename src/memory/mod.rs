@@ -1,5 +1,12 @@
+pub mod cartridge;
+mod dma;
 mod ioregs;
+pub mod joypad;
+pub mod link;
 pub mod map;
-mod ram;
-mod region;
+pub mod printer;
+pub mod ram;
+pub mod region;
+pub mod serial;
 mod timer;
+pub mod watch;
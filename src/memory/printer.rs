@@ -0,0 +1,435 @@
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+
+use super::serial::SerialDevice;
+use crate::png;
+
+/// Commands sent in printer packets
+const INIT: u8 = 0x01;
+const PRINT: u8 = 0x02;
+const DATA: u8 = 0x04;
+const STATUS: u8 = 0x0F;
+
+/// Byte answered in place of the first byte after a packet, telling the Game
+/// Boy a printer is connected
+const ALIVE: u8 = 0x81;
+
+/// Status bits
+const STATUS_CHECKSUM_ERROR: u8 = 1 << 0;
+const STATUS_PRINTING: u8 = 1 << 1;
+const STATUS_IMAGE_FULL: u8 = 1 << 2;
+const STATUS_UNPROCESSED: u8 = 1 << 3;
+const STATUS_PACKET_ERROR: u8 = 1 << 4;
+const STATUS_OTHER_ERROR: u8 = 1 << 6;
+
+/// Bytes of image data the printer can hold
+const BUFFER_SIZE: usize = 0x2000;
+/// Width of a printed strip in pixels, and in tiles
+const WIDTH: usize = 160;
+const WIDTH_TILES: usize = WIDTH / 8;
+/// Bytes in a tile, 2 per row of 8 pixels
+const TILE_SIZE: usize = 16;
+
+/// Status packets answered with the printing bit set after a print command,
+/// as games wait for the printer to finish before sending the next strip
+const PRINT_DURATION: u8 = 4;
+
+/// Palette used in place of 0x00, which games send to mean the default
+/// palette rather than a blank sheet
+const DEFAULT_PALETTE: u8 = 0xE4;
+
+/// Where in a packet the next byte from the Game Boy goes
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    Magic1,
+    Magic2,
+    Command,
+    Compression,
+    LengthLo,
+    LengthHi,
+    Data,
+    ChecksumLo,
+    ChecksumHi,
+    Alive,
+    Status,
+}
+
+/// A Game Boy Printer, saving each printed strip to a numbered PNG file.
+///
+/// The Game Boy talks to the printer in packets:
+/// ```text
+/// 0x88 0x33 command compression length(lo hi) data... checksum(lo hi) 0x00 0x00
+/// ```
+/// The checksum is the sum of everything from the command to the end of the
+/// data. The printer answers the last two bytes with [`ALIVE`] and its
+/// status.
+#[derive(Debug, Clone)]
+pub struct Printer {
+    /// Directory the printed strips are saved in
+    directory: PathBuf,
+    state: State,
+    command: u8,
+    compressed: bool,
+    length: u16,
+    data: Vec<u8>,
+    /// Sum of the packet so far
+    sum: u16,
+    /// Checksum sent with the packet
+    checksum: u16,
+    /// Image data received since the last print, in tiles of 2 bits per
+    /// pixel, [`WIDTH_TILES`] tiles per row
+    buffer: Vec<u8>,
+    status: u8,
+    /// Status packets left until the current print finishes
+    printing: u8,
+}
+
+impl Printer {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+            state: State::Magic1,
+            command: 0,
+            compressed: false,
+            length: 0,
+            data: Vec::new(),
+            sum: 0,
+            checksum: 0,
+            buffer: Vec::new(),
+            status: 0,
+            printing: 0,
+        }
+    }
+
+    /// Run the command of a fully received packet
+    fn execute(&mut self) {
+        if self.sum != self.checksum {
+            self.status |= STATUS_CHECKSUM_ERROR;
+            return;
+        }
+        self.status &= !STATUS_CHECKSUM_ERROR;
+
+        match self.command {
+            INIT => {
+                self.buffer.clear();
+                self.status = 0;
+                self.printing = 0;
+            }
+            DATA if self.data.is_empty() => {
+                // An empty data packet ends the image
+                self.status |= STATUS_IMAGE_FULL;
+            }
+            DATA => {
+                let data = if self.compressed {
+                    decompress(&self.data)
+                } else {
+                    std::mem::take(&mut self.data)
+                };
+                let room = BUFFER_SIZE - self.buffer.len();
+                self.buffer.extend(data.into_iter().take(room));
+                self.status |= STATUS_UNPROCESSED;
+                if self.buffer.len() == BUFFER_SIZE {
+                    self.status |= STATUS_IMAGE_FULL;
+                }
+            }
+            PRINT if self.data.len() >= 4 => {
+                let sheets = self.data[0];
+                let palette = self.data[2];
+                if sheets > 0 && !self.buffer.is_empty() {
+                    let shades = render(&self.buffer, palette);
+                    if let Err(error) = self.save(&shades) {
+                        eprintln!("Failed to save printout: {}", error);
+                        self.status |= STATUS_OTHER_ERROR;
+                    }
+                }
+                self.buffer.clear();
+                self.status &= !(STATUS_UNPROCESSED | STATUS_IMAGE_FULL);
+                self.status |= STATUS_PRINTING;
+                self.printing = PRINT_DURATION;
+            }
+            STATUS => {
+                if self.printing > 0 {
+                    self.printing -= 1;
+                    if self.printing == 0 {
+                        self.status &= !STATUS_PRINTING;
+                    }
+                }
+            }
+            _ => self.status |= STATUS_PACKET_ERROR,
+        }
+    }
+
+    /// Save a printed strip to the first free file name in the directory
+    fn save(&self, shades: &[u8]) -> io::Result<PathBuf> {
+        let path = next_path(&self.directory);
+        let mut writer = BufWriter::new(File::create(&path)?);
+        png::write_shades(&mut writer, WIDTH, shades)?;
+        writer.into_inner()?;
+        Ok(path)
+    }
+}
+
+/// First `print-NNN.png` in `directory` that doesn't exist yet
+fn next_path(directory: &Path) -> PathBuf {
+    (1..)
+        .map(|number| directory.join(format!("print-{:03}.png", number)))
+        .find(|path| !path.exists())
+        .unwrap()
+}
+
+/// Expand run-length compressed image data. Each run starts with a control
+/// byte. With the top bit set, the byte after it repeats (control & 0x7F) + 2
+/// times. Otherwise, control + 1 bytes follow as they are.
+fn decompress(data: &[u8]) -> Vec<u8> {
+    let mut output = Vec::new();
+    let mut bytes = data.iter().copied();
+    while let Some(control) = bytes.next() {
+        if control & 0x80 != 0 {
+            let Some(byte) = bytes.next() else {
+                break;
+            };
+            let count = (control & 0x7F) as usize + 2;
+            output.extend(std::iter::repeat_n(byte, count));
+        } else {
+            output.extend(bytes.by_ref().take(control as usize + 1));
+        }
+    }
+    output
+}
+
+/// Turn image data into shades, [`WIDTH`] pixels per row. Each color is
+/// mapped to a shade through `palette` the same way BGP does it. Tile rows
+/// that weren't filled completely are left out.
+fn render(buffer: &[u8], palette: u8) -> Vec<u8> {
+    let palette = if palette == 0 {
+        DEFAULT_PALETTE
+    } else {
+        palette
+    };
+    let height = buffer.len() / (WIDTH_TILES * TILE_SIZE) * 8;
+
+    let mut shades = Vec::with_capacity(WIDTH * height);
+    for y in 0..height {
+        for x in 0..WIDTH {
+            let tile = (y / 8) * WIDTH_TILES + x / 8;
+            let row = tile * TILE_SIZE + (y % 8) * 2;
+            let bit = 7 - x % 8;
+            let lo = (buffer[row] >> bit) & 1;
+            let hi = (buffer[row + 1] >> bit) & 1;
+            let color = (hi << 1) | lo;
+            shades.push((palette >> (color * 2)) & 0b11);
+        }
+    }
+    shades
+}
+
+impl SerialDevice for Printer {
+    fn transfer(&mut self, byte: u8) -> u8 {
+        let mut response = 0x00;
+        self.state = match self.state {
+            State::Magic1 if byte == 0x88 => State::Magic2,
+            State::Magic1 => State::Magic1,
+            State::Magic2 if byte == 0x33 => State::Command,
+            State::Magic2 if byte == 0x88 => State::Magic2,
+            State::Magic2 => State::Magic1,
+            State::Command => {
+                self.command = byte;
+                self.sum = byte as u16;
+                State::Compression
+            }
+            State::Compression => {
+                self.compressed = byte & 1 != 0;
+                self.sum = self.sum.wrapping_add(byte as u16);
+                State::LengthLo
+            }
+            State::LengthLo => {
+                self.length = byte as u16;
+                self.sum = self.sum.wrapping_add(byte as u16);
+                State::LengthHi
+            }
+            State::LengthHi => {
+                self.length |= (byte as u16) << 8;
+                self.sum = self.sum.wrapping_add(byte as u16);
+                self.data.clear();
+                if self.length > 0 {
+                    State::Data
+                } else {
+                    State::ChecksumLo
+                }
+            }
+            State::Data => {
+                self.data.push(byte);
+                self.sum = self.sum.wrapping_add(byte as u16);
+                if self.data.len() == self.length as usize {
+                    State::ChecksumLo
+                } else {
+                    State::Data
+                }
+            }
+            State::ChecksumLo => {
+                self.checksum = byte as u16;
+                State::ChecksumHi
+            }
+            State::ChecksumHi => {
+                self.checksum |= (byte as u16) << 8;
+                self.execute();
+                State::Alive
+            }
+            State::Alive => {
+                response = ALIVE;
+                State::Status
+            }
+            State::Status => {
+                response = self.status;
+                State::Magic1
+            }
+        };
+        response
+    }
+
+    fn box_clone(&self) -> Box<dyn SerialDevice> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("gibberish-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir(&path).unwrap();
+        path
+    }
+
+    /// Send a packet to the printer, returning the alive and status bytes
+    fn send(printer: &mut Printer, command: u8, compressed: bool, data: &[u8]) -> (u8, u8) {
+        let mut packet = vec![0x88, 0x33, command, compressed as u8];
+        packet.extend_from_slice(&(data.len() as u16).to_le_bytes());
+        packet.extend_from_slice(data);
+        let checksum = packet[2..]
+            .iter()
+            .fold(0u16, |sum, byte| sum.wrapping_add(*byte as u16));
+        packet.extend_from_slice(&checksum.to_le_bytes());
+
+        for byte in packet {
+            assert_eq!(printer.transfer(byte), 0x00);
+        }
+        (printer.transfer(0x00), printer.transfer(0x00))
+    }
+
+    /// A tile row where every pixel has color `x / 8 % 4`
+    fn tile_row() -> Vec<u8> {
+        (0..WIDTH_TILES)
+            .flat_map(|tile| {
+                let color = tile % 4;
+                let lo = if color & 1 != 0 { 0xFF } else { 0x00 };
+                let hi = if color & 2 != 0 { 0xFF } else { 0x00 };
+                [lo, hi].repeat(8)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn status_packets() {
+        let mut printer = Printer::new(".");
+        assert_eq!(send(&mut printer, STATUS, false, &[]), (ALIVE, 0x00));
+
+        send(&mut printer, DATA, false, &tile_row());
+        assert_eq!(
+            send(&mut printer, STATUS, false, &[]),
+            (ALIVE, STATUS_UNPROCESSED)
+        );
+        assert_eq!(send(&mut printer, INIT, false, &[]), (ALIVE, 0x00));
+        assert!(printer.buffer.is_empty());
+
+        assert_eq!(send(&mut printer, 0x07, false, &[]).1, STATUS_PACKET_ERROR);
+    }
+
+    #[test]
+    fn checksum_error() {
+        let mut printer = Printer::new(".");
+        for byte in [0x88, 0x33, DATA, 0, 1, 0, 0xAB, 0x00, 0x00] {
+            printer.transfer(byte);
+        }
+        assert_eq!(printer.transfer(0x00), ALIVE);
+        assert_eq!(printer.transfer(0x00), STATUS_CHECKSUM_ERROR);
+        assert!(printer.buffer.is_empty());
+
+        assert_eq!(send(&mut printer, STATUS, false, &[]).1, 0x00);
+    }
+
+    #[test]
+    fn resyncs_on_magic() {
+        let mut printer = Printer::new(".");
+        for byte in [0x00, 0x88, 0x88, 0x12, 0x88] {
+            printer.transfer(byte);
+        }
+        assert_eq!(printer.state, State::Magic2);
+    }
+
+    #[test]
+    fn compression() {
+        assert_eq!(
+            decompress(&[0x81, 0xAA, 0x02, 1, 2, 3, 0x80, 0x55]),
+            [0xAA, 0xAA, 0xAA, 1, 2, 3, 0x55, 0x55]
+        );
+
+        let mut printer = Printer::new(".");
+        send(&mut printer, DATA, true, &[0x83, 0x12, 0x00, 0x34]);
+        assert_eq!(printer.buffer, [0x12, 0x12, 0x12, 0x12, 0x12, 0x34]);
+    }
+
+    #[test]
+    fn palette() {
+        let buffer = tile_row();
+        let shades = render(&buffer, 0xE4);
+        assert_eq!(shades.len(), WIDTH * 8);
+        let colors: Vec<u8> = (0..4).map(|tile| shades[tile * 8]).collect();
+        assert_eq!(colors, [0, 1, 2, 3]);
+        assert_eq!(shades[WIDTH * 7 + 8 * 7], 3);
+
+        let shades = render(&buffer, 0b00_01_10_11);
+        let colors: Vec<u8> = (0..4).map(|tile| shades[tile * 8]).collect();
+        assert_eq!(colors, [3, 2, 1, 0]);
+
+        assert_eq!(render(&buffer, 0x00), render(&buffer, DEFAULT_PALETTE));
+        assert!(render(&buffer[1..], 0xE4).is_empty());
+    }
+
+    #[test]
+    fn prints_to_png() {
+        let directory = temp_dir("printer");
+        let mut printer = Printer::new(&directory);
+        send(&mut printer, INIT, false, &[]);
+        send(&mut printer, DATA, false, &tile_row().repeat(2));
+        assert_eq!(
+            send(&mut printer, DATA, false, &[]).1 & STATUS_IMAGE_FULL,
+            STATUS_IMAGE_FULL
+        );
+
+        let (_, status) = send(&mut printer, PRINT, false, &[1, 0x13, 0xE4, 0x40]);
+        assert_eq!(status, STATUS_PRINTING);
+        for _ in 1..PRINT_DURATION {
+            assert_eq!(send(&mut printer, STATUS, false, &[]).1, STATUS_PRINTING);
+        }
+        assert_eq!(send(&mut printer, STATUS, false, &[]).1, 0x00);
+
+        let mut expected = Vec::new();
+        png::write_shades(&mut expected, WIDTH, &render(&tile_row().repeat(2), 0xE4)).unwrap();
+        assert_eq!(
+            std::fs::read(directory.join("print-001.png")).unwrap(),
+            expected
+        );
+
+        // The next strip doesn't overwrite the first
+        send(&mut printer, DATA, false, &tile_row());
+        send(&mut printer, PRINT, false, &[1, 0x13, 0x1B, 0x40]);
+        let second = std::fs::read(directory.join("print-002.png")).unwrap();
+        assert_eq!(second[16..24], [0, 0, 0, 160, 0, 0, 0, 8]);
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,345 @@
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+
+use crate::cpu::interrupts::Interrupt;
+
+/// Memory mapped location of the [`sb`](#structfield.sb) register.
+pub const SB: u16 = 0xFF01;
+/// Memory mapped location of the [`sc`](#structfield.sc) register.
+pub const SC: u16 = 0xFF02;
+
+/// Set in SC while a transfer is requested or running
+const SC_TRANSFER: u8 = 1 << 7;
+/// Set in SC to drive the transfer with the internal clock
+const SC_INTERNAL_CLOCK: u8 = 1 << 0;
+/// Bits of SC that can be written. The rest read as 1.
+const SC_MASK: u8 = SC_TRANSFER | SC_INTERNAL_CLOCK;
+
+/// Machine cycles per bit with the internal clock, which runs at 8192 Hz
+const CYCLES_PER_BIT: usize = 128;
+/// Machine cycles between asking the device whether it clocked a transfer.
+/// Asking can be slow, and no transfer is quicker than a byte on the internal
+/// clock.
+const EXTERNAL_POLL_CYCLES: usize = 8 * CYCLES_PER_BIT;
+
+/// Something plugged into the serial port
+pub trait SerialDevice: Debug {
+    /// Exchange a byte with the device, clocked by the Game Boy.
+    ///
+    /// # Arguments
+    /// * `byte` - The byte shifted out of SB.
+    ///
+    /// # Returns
+    /// * The byte shifted in from the device.
+    fn transfer(&mut self, byte: u8) -> u8;
+
+    /// Check whether the device clocked a transfer while the Game Boy waits
+    /// for the external clock. Devices without a clock of their own never do.
+    ///
+    /// # Arguments
+    /// * `byte` - The byte shifted out of SB.
+    ///
+    /// # Returns
+    /// * The byte shifted in from the device if it clocked a transfer,
+    ///   otherwise [`None`].
+    fn external_transfer(&mut self, _byte: u8) -> Option<u8> {
+        None
+    }
+
+    /// Clone the device into a new box, so that the memory map stays clonable
+    fn box_clone(&self) -> Box<dyn SerialDevice>;
+}
+
+impl Clone for Box<dyn SerialDevice> {
+    fn clone(&self) -> Self {
+        self.box_clone()
+    }
+}
+
+/// Nothing plugged in. The input line floats high, so every transfer reads
+/// 0xFF.
+#[derive(Debug, Clone, Default)]
+pub struct Disconnected;
+
+impl SerialDevice for Disconnected {
+    fn transfer(&mut self, _byte: u8) -> u8 {
+        0xFF
+    }
+
+    fn box_clone(&self) -> Box<dyn SerialDevice> {
+        Box::new(self.clone())
+    }
+}
+
+/// Output wired straight back to input, so every transfer reads back the
+/// byte that was sent
+#[derive(Debug, Clone, Default)]
+pub struct Loopback;
+
+impl SerialDevice for Loopback {
+    fn transfer(&mut self, byte: u8) -> u8 {
+        byte
+    }
+
+    fn box_clone(&self) -> Box<dyn SerialDevice> {
+        Box::new(self.clone())
+    }
+}
+
+/// Collects every byte sent, like test ROMs printing their results over
+/// serial. Clones share the same buffer, so a handle can be kept around to
+/// read what a capture given to the emulator received.
+#[derive(Debug, Clone, Default)]
+pub struct Capture {
+    bytes: Arc<Mutex<Vec<u8>>>,
+}
+
+impl Capture {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// All bytes sent so far
+    pub fn bytes(&self) -> Vec<u8> {
+        self.bytes.lock().unwrap().clone()
+    }
+}
+
+impl SerialDevice for Capture {
+    fn transfer(&mut self, byte: u8) -> u8 {
+        self.bytes.lock().unwrap().push(byte);
+        0xFF
+    }
+
+    fn box_clone(&self) -> Box<dyn SerialDevice> {
+        Box::new(self.clone())
+    }
+}
+
+/// The serial controller. A transfer shifts SB out one bit at a time, most
+/// significant bit first, while shifting the device's byte in from the other
+/// end. The Game Boy either drives the clock itself or waits for the device
+/// to do it.
+#[derive(Debug, Clone)]
+pub struct Serial {
+    /// Serial transfer data (R/W). Byte to send, replaced by the received byte
+    /// as the transfer runs.
+    sb: u8,
+    /// Serial transfer control (R/W).
+    /// ```text
+    /// 0xxxxxx0
+    /// |      `- Shift clock
+    /// |           0: External clock
+    /// |           1: Internal clock, 8192 Hz
+    /// |
+    /// `-------- Transfer start flag
+    ///             0: No transfer
+    ///             1: Transfer requested or running
+    /// ```
+    sc: u8,
+    /// The device plugged into the port
+    device: Box<dyn SerialDevice>,
+    /// Internal use. Byte received from the device in the running transfer,
+    /// shifted into [`sb`](#structfield.sb) one bit at a time.
+    incoming: u8,
+    /// Internal use. Number of bits shifted in the running transfer.
+    bits: u8,
+    /// Internal use. A clock divider to help decide when to shift the next
+    /// bit, or when to ask the device for an external clock.
+    internal_clock: usize,
+}
+
+impl Serial {
+    pub fn new() -> Self {
+        Self {
+            sb: 0,
+            sc: 0,
+            device: Box::new(Disconnected),
+            incoming: 0xFF,
+            bits: 0,
+            internal_clock: 0,
+        }
+    }
+
+    /// Plug a device into the port, replacing the current one
+    pub fn connect(&mut self, device: Box<dyn SerialDevice>) {
+        self.device = device;
+    }
+
+    /// Run the serial controller.
+    ///
+    /// # Arguments
+    /// * `machine_cycles` - The amount of machine cycles that have ticked since
+    ///   last invocation.
+    ///
+    /// # Returns
+    /// * An [`Option<Interrupt>`] with the value [`Interrupt::Serial`] if a
+    ///   transfer completed, otherwise [`None`].
+    pub fn tick(&mut self, machine_cycles: usize) -> Option<Interrupt> {
+        if self.sc & SC_TRANSFER == 0 {
+            return None;
+        }
+
+        self.internal_clock += machine_cycles;
+        if self.sc & SC_INTERNAL_CLOCK == 0 {
+            if self.internal_clock < EXTERNAL_POLL_CYCLES {
+                return None;
+            }
+            self.internal_clock = 0;
+            self.sb = self.device.external_transfer(self.sb)?;
+            return self.complete();
+        }
+
+        while self.internal_clock >= CYCLES_PER_BIT {
+            self.internal_clock -= CYCLES_PER_BIT;
+            let bit = (self.incoming >> (7 - self.bits)) & 1;
+            self.sb = (self.sb << 1) | bit;
+            self.bits += 1;
+            if self.bits == 8 {
+                return self.complete();
+            }
+        }
+        None
+    }
+
+    fn complete(&mut self) -> Option<Interrupt> {
+        self.sc &= !SC_TRANSFER;
+        Some(Interrupt::Serial)
+    }
+
+    pub fn read_byte(&self, addr: u16) -> u8 {
+        match addr {
+            SB => self.sb,
+            SC => self.sc | !SC_MASK,
+            _ => panic!("Invalid serial register address: {:x}", addr),
+        }
+    }
+
+    /// Write SB or SC. Requesting a transfer on the internal clock exchanges
+    /// the byte with the device right away, and the received bits are then
+    /// shifted in over the duration of the transfer.
+    pub fn write_byte(&mut self, byte: u8, addr: u16) {
+        match addr {
+            SB => self.sb = byte,
+            SC => {
+                self.sc = byte & SC_MASK;
+                if self.sc == SC_MASK {
+                    self.incoming = self.device.transfer(self.sb);
+                    self.bits = 0;
+                    self.internal_clock = 0;
+                }
+            }
+            _ => panic!("Invalid serial register address: {:x}", addr),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Clocks a transfer whenever it's asked, sending back the inverted byte
+    #[derive(Debug, Clone)]
+    struct ExternalClock;
+
+    impl SerialDevice for ExternalClock {
+        fn transfer(&mut self, _byte: u8) -> u8 {
+            0xFF
+        }
+
+        fn external_transfer(&mut self, byte: u8) -> Option<u8> {
+            Some(!byte)
+        }
+
+        fn box_clone(&self) -> Box<dyn SerialDevice> {
+            Box::new(self.clone())
+        }
+    }
+
+    /// Run until a transfer completes, returning the machine cycles it took
+    fn run_transfer(serial: &mut Serial) -> usize {
+        let mut cycles = 0;
+        while serial.tick(4).is_none() {
+            cycles += 4;
+            assert!(cycles < 100_000, "Transfer never completed");
+        }
+        cycles + 4
+    }
+
+    #[test]
+    fn register_reads() {
+        let mut serial = Serial::new();
+        assert_eq!(serial.read_byte(SC), 0x7E);
+        serial.write_byte(0xA5, SB);
+        assert_eq!(serial.read_byte(SB), 0xA5);
+        serial.write_byte(0x80, SC);
+        assert_eq!(serial.read_byte(SC), 0xFE);
+    }
+
+    #[test]
+    fn internal_clock_timing() {
+        let mut serial = Serial::new();
+        serial.write_byte(0x00, SB);
+        serial.write_byte(0x81, SC);
+        assert_eq!(run_transfer(&mut serial), 8 * CYCLES_PER_BIT);
+        assert_eq!(serial.read_byte(SC), 0x7F);
+        assert_eq!(serial.read_byte(SB), 0xFF);
+        assert!(serial.tick(1000).is_none());
+    }
+
+    #[test]
+    fn bits_shift_in_msb_first() {
+        let mut serial = Serial::new();
+        serial.connect(Box::new(Loopback));
+        serial.write_byte(0b1010_0000, SB);
+        serial.write_byte(0x81, SC);
+
+        serial.tick(CYCLES_PER_BIT);
+        assert_eq!(serial.read_byte(SB), 0b0100_0001);
+        serial.tick(CYCLES_PER_BIT);
+        assert_eq!(serial.read_byte(SB), 0b1000_0010);
+        serial.tick(CYCLES_PER_BIT * 5);
+        assert_eq!(serial.read_byte(SB), 0b0101_0000);
+
+        assert!(matches!(
+            serial.tick(CYCLES_PER_BIT),
+            Some(Interrupt::Serial)
+        ));
+        assert_eq!(serial.read_byte(SB), 0b1010_0000);
+    }
+
+    #[test]
+    fn external_clock_waits() {
+        let mut serial = Serial::new();
+        serial.write_byte(0x42, SB);
+        serial.write_byte(0x80, SC);
+        assert!(serial.tick(100_000).is_none());
+        assert_eq!(serial.read_byte(SB), 0x42);
+        assert_eq!(serial.read_byte(SC), 0xFE);
+    }
+
+    #[test]
+    fn external_clock_polled_once_per_byte() {
+        let mut serial = Serial::new();
+        serial.connect(Box::new(ExternalClock));
+        serial.write_byte(0x42, SB);
+        serial.write_byte(0x80, SC);
+        assert_eq!(run_transfer(&mut serial), EXTERNAL_POLL_CYCLES);
+        assert_eq!(serial.read_byte(SB), 0xBD);
+        assert_eq!(serial.read_byte(SC), 0x7E);
+    }
+
+    #[test]
+    fn capture() {
+        let capture = Capture::new();
+        let mut serial = Serial::new();
+        serial.connect(Box::new(capture.clone()));
+        for byte in b"Passed" {
+            serial.write_byte(*byte, SB);
+            serial.write_byte(0x81, SC);
+            run_transfer(&mut serial);
+            assert_eq!(serial.read_byte(SB), 0xFF);
+        }
+        assert_eq!(capture.bytes(), b"Passed");
+    }
+}
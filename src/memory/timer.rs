@@ -63,13 +63,13 @@ impl TimerRegisters {
     ///
     /// # Arguments
     /// * `machine_cycles` - The amount of machine cycles that have ticked since
-    /// last invocation. The machine clock rate is ~1.05MHz, and the
-    /// [`div`](#structfield.div) and [`tima`](#structfield.tima) registers
-    /// are incremented at a divided rate.
+    ///   last invocation. The machine clock rate is ~1.05MHz, and the
+    ///   [`div`](#structfield.div) and [`tima`](#structfield.tima) registers
+    ///   are incremented at a divided rate.
     ///
     /// # Returns
     /// * An [`Option<Interrupt>`] with the value [`Interrupt::Timer`] if
-    /// [`tima`](#structfield.tima) overflowed, otherwise [`None`].
+    ///   [`tima`](#structfield.tima) overflowed, otherwise [`None`].
     pub fn tick(&mut self, machine_cycles: usize) -> Option<Interrupt> {
         self.internal_div += machine_cycles * 4;
 
@@ -0,0 +1,269 @@
+use std::cell::Cell;
+use std::fmt;
+use std::ops::RangeInclusive;
+
+/// Kind of memory access
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+    /// Either reading or writing
+    ReadWrite,
+}
+
+impl Access {
+    /// Check whether watching for `self` catches `access`
+    fn catches(self, access: Access) -> bool {
+        self == Access::ReadWrite || self == access
+    }
+}
+
+/// Comparison the byte read or written has to pass for a watchpoint to
+/// catch the access
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Condition {
+    Equal(u8),
+    NotEqual(u8),
+    Less(u8),
+    Greater(u8),
+}
+
+impl Condition {
+    fn matches(self, value: u8) -> bool {
+        match self {
+            Self::Equal(other) => value == other,
+            Self::NotEqual(other) => value != other,
+            Self::Less(other) => value < other,
+            Self::Greater(other) => value > other,
+        }
+    }
+}
+
+/// Watch for accesses to a range of addresses
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub range: RangeInclusive<u16>,
+    pub access: Access,
+    /// Only catch accesses where the byte passes this, if given
+    pub condition: Option<Condition>,
+}
+
+impl Watchpoint {
+    fn catches(&self, addr: u16, access: Access, value: u8) -> bool {
+        self.range.contains(&addr)
+            && self.access.catches(access)
+            && self
+                .condition
+                .is_none_or(|condition| condition.matches(value))
+    }
+}
+
+impl fmt::Display for Watchpoint {
+    /// Like `c000-c00f w == 42`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04x}", self.range.start())?;
+        if self.range.end() != self.range.start() {
+            write!(f, "-{:04x}", self.range.end())?;
+        }
+        let access = match self.access {
+            Access::Read => "r",
+            Access::Write => "w",
+            Access::ReadWrite => "rw",
+        };
+        write!(f, " {}", access)?;
+        match self.condition {
+            Some(Condition::Equal(value)) => write!(f, " == {:02x}", value),
+            Some(Condition::NotEqual(value)) => write!(f, " != {:02x}", value),
+            Some(Condition::Less(value)) => write!(f, " < {:02x}", value),
+            Some(Condition::Greater(value)) => write!(f, " > {:02x}", value),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A memory access caught by a watchpoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchHit {
+    /// Address of the instruction that made the access
+    pub pc: u16,
+    pub addr: u16,
+    /// How the address was accessed, either `Read` or `Write`
+    pub access: Access,
+    /// The byte read or written
+    pub value: u8,
+    /// What the watchpoint that caught the access watches for
+    pub watched: Access,
+}
+
+/// Watchpoints on memory, checked by the memory map on every access the CPU
+/// makes. Reads only borrow the memory map, so a hit is kept in a `Cell`
+/// until it's taken.
+#[derive(Debug, Clone, Default)]
+pub struct Watchpoints {
+    watchpoints: Vec<Watchpoint>,
+    hit: Cell<Option<WatchHit>>,
+    /// Address of the instruction making accesses, kept up to date by the
+    /// CPU
+    pc: Cell<u16>,
+}
+
+impl Watchpoints {
+    pub fn add(&mut self, watchpoint: Watchpoint) {
+        if !self.watchpoints.contains(&watchpoint) {
+            self.watchpoints.push(watchpoint);
+        }
+    }
+
+    /// Remove a watchpoint equal to `watchpoint`
+    ///
+    /// # Returns
+    /// * `false` if there was no such watchpoint, otherwise `true`.
+    pub fn remove(&mut self, watchpoint: &Watchpoint) -> bool {
+        let count = self.watchpoints.len();
+        self.watchpoints.retain(|other| other != watchpoint);
+        self.watchpoints.len() != count
+    }
+
+    /// Tell which instruction makes the accesses from now on
+    pub fn set_pc(&self, pc: u16) {
+        self.pc.set(pc);
+    }
+
+    /// All watchpoints, in the order they were added
+    pub fn list(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    /// Check an access against the watchpoints. Only the first hit is kept
+    /// until it's taken.
+    pub fn check(&self, addr: u16, access: Access, value: u8) {
+        if self.watchpoints.is_empty() {
+            return;
+        }
+        if self.hit.get().is_some() {
+            return;
+        }
+
+        let watchpoint = self
+            .watchpoints
+            .iter()
+            .find(|watchpoint| watchpoint.catches(addr, access, value));
+        if let Some(watchpoint) = watchpoint {
+            self.hit.set(Some(WatchHit {
+                pc: self.pc.get(),
+                addr,
+                access,
+                value,
+                watched: watchpoint.access,
+            }));
+        }
+    }
+
+    /// The first access caught since the last time this was called
+    pub fn take_hit(&self) -> Option<WatchHit> {
+        self.hit.take()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::memory::map::MemoryMap;
+
+    fn watchpoint(range: RangeInclusive<u16>, access: Access) -> Watchpoint {
+        Watchpoint {
+            range,
+            access,
+            condition: None,
+        }
+    }
+
+    #[test]
+    fn ranges_and_access() {
+        let mut watchpoints = Watchpoints::default();
+        watchpoints.add(watchpoint(0xC000..=0xC00F, Access::Write));
+        watchpoints.add(watchpoint(0xFF80..=0xFF80, Access::ReadWrite));
+
+        watchpoints.check(0xC010, Access::Write, 1);
+        watchpoints.check(0xC000, Access::Read, 2);
+        assert_eq!(watchpoints.take_hit(), None);
+
+        watchpoints.set_pc(0x0150);
+        watchpoints.check(0xC00F, Access::Write, 3);
+        watchpoints.check(0xFF80, Access::Read, 4);
+        let hit = WatchHit {
+            pc: 0x0150,
+            addr: 0xC00F,
+            access: Access::Write,
+            value: 3,
+            watched: Access::Write,
+        };
+        assert_eq!(watchpoints.take_hit(), Some(hit));
+        assert_eq!(watchpoints.take_hit(), None);
+
+        watchpoints.check(0xFF80, Access::Read, 4);
+        assert_eq!(watchpoints.take_hit().unwrap().watched, Access::ReadWrite);
+    }
+
+    #[test]
+    fn remove() {
+        let mut watchpoints = Watchpoints::default();
+        watchpoints.add(watchpoint(0xC000..=0xC001, Access::Read));
+        assert!(!watchpoints.remove(&watchpoint(0xC000..=0xC001, Access::Write)));
+        assert!(watchpoints.remove(&watchpoint(0xC000..=0xC001, Access::Read)));
+        assert!(watchpoints.list().is_empty());
+
+        watchpoints.check(0xC000, Access::Read, 0);
+        assert_eq!(watchpoints.take_hit(), None);
+    }
+
+    #[test]
+    fn conditions() {
+        let mut watchpoints = Watchpoints::default();
+        watchpoints.add(Watchpoint {
+            condition: Some(Condition::Greater(0x7F)),
+            ..watchpoint(0xC000..=0xC000, Access::Write)
+        });
+        watchpoints.add(Watchpoint {
+            condition: Some(Condition::Equal(0)),
+            ..watchpoint(0xC000..=0xC0FF, Access::ReadWrite)
+        });
+
+        watchpoints.check(0xC000, Access::Write, 0x7F);
+        watchpoints.check(0xC001, Access::Read, 1);
+        assert_eq!(watchpoints.take_hit(), None);
+
+        watchpoints.check(0xC000, Access::Write, 0x80);
+        assert_eq!(watchpoints.take_hit().unwrap().watched, Access::Write);
+        watchpoints.check(0xC000, Access::Read, 0);
+        assert_eq!(watchpoints.take_hit().unwrap().watched, Access::ReadWrite);
+
+        let listed: Vec<String> = watchpoints.list().iter().map(|w| w.to_string()).collect();
+        assert_eq!(listed, ["c000 w > 7f", "c000-c0ff rw == 00"]);
+    }
+
+    #[test]
+    fn memory_map() {
+        let mut memory = MemoryMap::new();
+        memory
+            .watchpoints_mut()
+            .add(watchpoint(0xC001..=0xC001, Access::ReadWrite));
+
+        // Peeking doesn't count as an access
+        memory.peek_word(0xC000);
+        assert_eq!(memory.watchpoints().take_hit(), None);
+
+        // Words are accessed a byte at a time
+        memory.write_word(0x1234, 0xC000);
+        let hit = memory.watchpoints().take_hit().unwrap();
+        assert_eq!(
+            (hit.addr, hit.access, hit.value),
+            (0xC001, Access::Write, 0x12)
+        );
+        memory.read_byte(0xC001);
+        assert_eq!(
+            memory.watchpoints().take_hit().unwrap().access,
+            Access::Read
+        );
+    }
+}
@@ -0,0 +1,144 @@
+use std::io::{self, Write};
+
+use crate::ppu::GRAYS;
+
+/// Largest amount of data a stored deflate block can hold
+const MAX_BLOCK: usize = 0xFFFF;
+
+/// CRC-32 lookup table, for the checksum ending each PNG chunk
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut c = n as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 {
+                0xEDB8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            k += 1;
+        }
+        table[n] = c;
+        n += 1;
+    }
+    table
+};
+
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0, |crc, byte| {
+        CRC_TABLE[((crc ^ *byte as u32) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (a, b) = data.iter().fold((1u32, 0u32), |(a, b), byte| {
+        let a = (a + *byte as u32) % 65521;
+        (a, (b + a) % 65521)
+    });
+    (b << 16) | a
+}
+
+fn write_chunk(writer: &mut impl Write, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    writer.write_all(&(data.len() as u32).to_be_bytes())?;
+    let mut checked = kind.to_vec();
+    checked.extend_from_slice(data);
+    writer.write_all(&checked)?;
+    writer.write_all(&crc32(&checked).to_be_bytes())
+}
+
+/// Wrap `data` in a zlib stream of stored, uncompressed deflate blocks.
+/// Images from the Game Boy are small enough that compressing them isn't
+/// worth a compressor.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut stream = vec![0x78, 0x01];
+    let mut blocks = data.chunks(MAX_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        stream.extend_from_slice(&[1, 0x00, 0x00, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        let length = block.len() as u16;
+        stream.push(blocks.peek().is_none() as u8);
+        stream.extend_from_slice(&length.to_le_bytes());
+        stream.extend_from_slice(&(!length).to_le_bytes());
+        stream.extend_from_slice(block);
+    }
+    stream.extend_from_slice(&adler32(data).to_be_bytes());
+    stream
+}
+
+/// Write an image of Game Boy shades as a grayscale PNG.
+///
+/// # Arguments
+/// * `writer` - Where to write the PNG file.
+/// * `width` - Width of the image in pixels.
+/// * `shades` - Shades of the image, `width` pixels per row. Each shade is a
+///   value from 0 (white) to 3 (black).
+pub fn write_shades(writer: &mut impl Write, width: usize, shades: &[u8]) -> io::Result<()> {
+    let height = shades.len() / width;
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    // 8 bit grayscale, deflate, adaptive filtering, no interlacing
+    header.extend_from_slice(&[8, 0, 0, 0, 0]);
+
+    // Each row starts with its filter type, which is always none
+    let mut rows = Vec::with_capacity(height * (width + 1));
+    for row in shades.chunks_exact(width) {
+        rows.push(0);
+        rows.extend(row.iter().map(|shade| GRAYS[(shade & 0b11) as usize]));
+    }
+
+    writer.write_all(b"\x89PNG\r\n\x1a\n")?;
+    write_chunk(writer, b"IHDR", &header)?;
+    write_chunk(writer, b"IDAT", &zlib_stored(&rows))?;
+    write_chunk(writer, b"IEND", &[])
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn checksums() {
+        assert_eq!(crc32(b"IEND"), 0xAE42_6082);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+    }
+
+    #[test]
+    fn stored_blocks() {
+        let data: Vec<u8> = (0..70_000).map(|i| i as u8).collect();
+        let stream = zlib_stored(&data);
+
+        // Two blocks, only the last one final
+        assert_eq!(stream[..7], [0x78, 0x01, 0, 0xFF, 0xFF, 0x00, 0x00]);
+        let second = 7 + MAX_BLOCK;
+        let length = (data.len() - MAX_BLOCK) as u16;
+        assert_eq!(stream[second], 1);
+        assert_eq!(stream[second + 1..second + 3], length.to_le_bytes());
+        assert_eq!(stream[second + 5..stream.len() - 4], data[MAX_BLOCK..]);
+        assert_eq!(stream[stream.len() - 4..], adler32(&data).to_be_bytes());
+    }
+
+    #[test]
+    fn image() {
+        let mut png = Vec::new();
+        write_shades(&mut png, 2, &[0, 1, 2, 3, 3, 0]).unwrap();
+
+        assert_eq!(png[..8], *b"\x89PNG\r\n\x1a\n");
+        assert_eq!(png[8..16], [0, 0, 0, 13, b'I', b'H', b'D', b'R']);
+        assert_eq!(png[16..29], [0, 0, 0, 2, 0, 0, 0, 3, 8, 0, 0, 0, 0]);
+        assert_eq!(png[29..33], crc32(&png[12..29]).to_be_bytes());
+
+        // IDAT holds a single stored block with the filtered rows
+        let rows = [0, 0xFF, 0xAA, 0, 0x55, 0x00, 0, 0x00, 0xFF];
+        assert_eq!(png[33..41], [0, 0, 0, 20, b'I', b'D', b'A', b'T']);
+        assert_eq!(png[43..48], [1, 9, 0, 0xF6, 0xFF]);
+        assert_eq!(png[48..57], rows);
+
+        assert_eq!(png[png.len() - 12..], *b"\0\0\0\0IEND\xAE\x42\x60\x82");
+    }
+}
@@ -0,0 +1,477 @@
+use std::collections::VecDeque;
+
+use super::{
+    Ppu, Sprite, LCDC_BG_ENABLE, LCDC_SPRITE_ENABLE, LCDC_WINDOW_ENABLE, SCREEN_WIDTH,
+    SPRITE_X_OFFSET, WX_OFFSET,
+};
+
+/// Dots at the start of [`Mode::Drawing`](super::Mode::Drawing) spent on a
+/// tile fetch that is thrown away
+const FIRST_FETCH_DOTS: u8 = 6;
+/// Dots the LCD is stalled for every fetched sprite
+const SPRITE_FETCH_DOTS: u8 = 6;
+/// Most extra dots spent waiting for the background fetcher before a sprite
+/// fetch, for the first sprite on a background tile
+const SPRITE_TILE_PENALTY: u8 = 5;
+
+/// Steps of the background fetcher. All but [`Push`](FetchStep::Push) take
+/// two dots. Pushing is retried every dot until the background FIFO is empty.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+enum FetchStep {
+    #[default]
+    Tile,
+    DataLow,
+    DataHigh,
+    Push,
+}
+
+/// The background fetcher, reading a row of eight pixels from the background
+/// or window tile map
+#[derive(Debug, Clone, Default)]
+struct Fetcher {
+    step: FetchStep,
+    /// Set on the second dot of a two dot step
+    second_dot: bool,
+    /// Tiles fetched so far on this line, or since the window started
+    tile_x: u8,
+    /// Address of the tile row being fetched
+    row_address: u16,
+    lo: u8,
+    hi: u8,
+}
+
+/// A pixel in the sprite FIFO
+#[derive(Debug, Clone, Copy, Default)]
+struct SpritePixel {
+    /// Color number, where 0 is transparent
+    color: u8,
+    /// Flags of the sprite the pixel belongs to
+    flags: u8,
+}
+
+/// State of the pixel FIFO renderer. Instead of drawing a whole line at once,
+/// pixels are fetched and pushed to the LCD one dot at a time, so register
+/// writes in the middle of a line show up in the middle of the line. This
+/// makes the length of [`Mode::Drawing`](super::Mode::Drawing) vary with the
+/// fine scroll, the window and the sprites on the line.
+#[derive(Debug, Clone, Default)]
+pub(super) struct Fifo {
+    /// Background and window color numbers waiting to be pushed to the LCD
+    background: VecDeque<u8>,
+    /// Sprite pixels to be mixed with the background pixels, lined up with
+    /// [`background`](#structfield.background)
+    sprites: VecDeque<SpritePixel>,
+    fetcher: Fetcher,
+    /// X coordinate of the next pixel pushed to the LCD
+    x: u8,
+    /// Background pixels left to throw away before pushing to the LCD
+    discard: u8,
+    /// Dots left until the fetcher and LCD continue
+    stall: u8,
+    /// Set once the window has started on this line
+    window: bool,
+    /// Index into the line sprites of the next sprite to fetch
+    next_sprite: usize,
+    /// Background tile that the last sprite penalty was paid for
+    penalized_tile: Option<u8>,
+}
+
+impl Ppu {
+    /// Prepare the FIFO renderer for a new line. Called when entering
+    /// [`Mode::Drawing`](super::Mode::Drawing).
+    pub(super) fn fifo_start_line(&mut self) {
+        self.fifo = Fifo {
+            background: std::mem::take(&mut self.fifo.background),
+            sprites: std::mem::take(&mut self.fifo.sprites),
+            discard: self.scx % 8,
+            stall: FIRST_FETCH_DOTS,
+            ..Fifo::default()
+        };
+        self.fifo.background.clear();
+        self.fifo.sprites.clear();
+    }
+
+    /// Run the FIFO renderer for a single dot.
+    ///
+    /// # Returns
+    /// True when the last pixel of the line has been pushed to the LCD.
+    pub(super) fn fifo_step(&mut self) -> bool {
+        if self.fifo.stall > 0 {
+            self.fifo.stall -= 1;
+            return false;
+        }
+
+        self.fifo_check_window();
+        if self.fifo_fetch_sprite() {
+            return false;
+        }
+
+        self.fetcher_step();
+        self.fifo_push_pixel();
+
+        if self.fifo.x as usize == SCREEN_WIDTH {
+            if self.fifo.window {
+                self.window_line += 1;
+            }
+            return true;
+        }
+        false
+    }
+
+    /// Start fetching the window once the LCD reaches it. The background FIFO
+    /// is cleared and the fetcher starts over on the window tile map.
+    fn fifo_check_window(&mut self) {
+        let enabled = LCDC_BG_ENABLE | LCDC_WINDOW_ENABLE;
+        let window_reached = self.fifo.x + WX_OFFSET >= self.wx;
+        if self.fifo.window
+            || self.lcdc & enabled != enabled
+            || !self.window_y_triggered
+            || !window_reached
+        {
+            return;
+        }
+
+        self.fifo.window = true;
+        self.fifo.background.clear();
+        self.fifo.fetcher = Fetcher::default();
+        // With WX below 7, the window starts left of the screen
+        self.fifo.discard = WX_OFFSET.saturating_sub(self.wx);
+    }
+
+    /// Fetch the next sprite if the LCD has reached it, stalling the LCD
+    /// while doing so.
+    ///
+    /// # Returns
+    /// True if a sprite fetch was started.
+    fn fifo_fetch_sprite(&mut self) -> bool {
+        let Some(&sprite) = self.line_sprites.get(self.fifo.next_sprite) else {
+            return false;
+        };
+        if sprite.x as i16 > self.fifo.x as i16 + SPRITE_X_OFFSET {
+            return false;
+        }
+
+        self.fifo.next_sprite += 1;
+        if self.lcdc & LCDC_SPRITE_ENABLE == 0 {
+            return false;
+        }
+
+        self.fifo_merge_sprite(&sprite);
+
+        // The first sprite on a background tile also waits for the fetcher
+        // to finish that tile
+        let scrolled_x = self.fifo.x.wrapping_add(self.scx);
+        let tile = scrolled_x / 8;
+        let mut penalty = SPRITE_FETCH_DOTS;
+        if self.fifo.penalized_tile != Some(tile) {
+            penalty += SPRITE_TILE_PENALTY - (scrolled_x % 8).min(SPRITE_TILE_PENALTY);
+            self.fifo.penalized_tile = Some(tile);
+        }
+        // This dot counts as the first dot of the stall
+        self.fifo.stall = penalty - 1;
+        true
+    }
+
+    /// Mix the pixels of `sprite` into the sprite FIFO. Pixels already in the
+    /// FIFO belong to sprites with higher priority, so only transparent
+    /// pixels are replaced.
+    fn fifo_merge_sprite(&mut self, sprite: &Sprite) {
+        let left = sprite.x as i16 - SPRITE_X_OFFSET - self.fifo.x as i16;
+        for column in 0..8 {
+            let Ok(index) = usize::try_from(left + column as i16) else {
+                continue;
+            };
+
+            if self.fifo.sprites.len() <= index {
+                self.fifo.sprites.resize(index + 1, SpritePixel::default());
+            }
+            if self.fifo.sprites[index].color == 0 {
+                self.fifo.sprites[index] = SpritePixel {
+                    color: self.sprite_color(sprite, column),
+                    flags: sprite.flags,
+                };
+            }
+        }
+    }
+
+    /// Advance the background fetcher by a dot
+    fn fetcher_step(&mut self) {
+        let fetcher = &mut self.fifo.fetcher;
+        if fetcher.step != FetchStep::Push && !fetcher.second_dot {
+            fetcher.second_dot = true;
+            return;
+        }
+        fetcher.second_dot = false;
+
+        match self.fifo.fetcher.step {
+            FetchStep::Tile => {
+                let tile_x = self.fifo.fetcher.tile_x;
+                let (map, x, y) = if self.fifo.window {
+                    (self.window_map(), tile_x * 8, self.window_line)
+                } else {
+                    // Coarse scroll is read for every tile, fine scroll only
+                    // at the start of the line
+                    let x = (self.scx & !0x07).wrapping_add(tile_x * 8);
+                    (self.background_map(), x, self.scy.wrapping_add(self.ly))
+                };
+                let tile = self.map_tile(map, x, y);
+                self.fifo.fetcher.row_address = self.tile_address(tile) + (y % 8) as u16 * 2;
+                self.fifo.fetcher.step = FetchStep::DataLow;
+            }
+            FetchStep::DataLow => {
+                self.fifo.fetcher.lo = self.vram.read_byte(self.fifo.fetcher.row_address);
+                self.fifo.fetcher.step = FetchStep::DataHigh;
+            }
+            FetchStep::DataHigh => {
+                self.fifo.fetcher.hi = self.vram.read_byte(self.fifo.fetcher.row_address + 1);
+                self.fifo.fetcher.step = FetchStep::Push;
+            }
+            FetchStep::Push => {
+                if !self.fifo.background.is_empty() {
+                    return;
+                }
+
+                let Fetcher { lo, hi, .. } = self.fifo.fetcher;
+                for bit in (0..8).rev() {
+                    let color = ((hi >> bit) & 1) << 1 | ((lo >> bit) & 1);
+                    self.fifo.background.push_back(color);
+                }
+                self.fifo.fetcher.tile_x = self.fifo.fetcher.tile_x.wrapping_add(1);
+                self.fifo.fetcher.step = FetchStep::Tile;
+            }
+        }
+    }
+
+    /// Push a pixel to the LCD if there is one in the background FIFO. The
+    /// palettes are applied here, so palette writes take effect on the very
+    /// next pixel.
+    fn fifo_push_pixel(&mut self) {
+        let Some(bg_color) = self.fifo.background.pop_front() else {
+            return;
+        };
+        if self.fifo.discard > 0 {
+            self.fifo.discard -= 1;
+            return;
+        }
+
+        let bg_color = if self.lcdc & LCDC_BG_ENABLE != 0 {
+            bg_color
+        } else {
+            0
+        };
+        let sprite = self
+            .fifo
+            .sprites
+            .pop_front()
+            .filter(|pixel| pixel.color != 0 && self.lcdc & LCDC_SPRITE_ENABLE != 0)
+            .map(|pixel| (pixel.color, pixel.flags));
+
+        let index = self.ly as usize * SCREEN_WIDTH + self.fifo.x as usize;
+        self.frame_buffer[index] = self.mix(bg_color, sprite);
+        self.fifo.x += 1;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::test::{setup, setup_sprites, setup_window, solid_tile, sprite, LINE};
+    use super::super::*;
+
+    /// Switch `ppu` over to the FIFO renderer
+    fn fifo(mut ppu: Ppu) -> Ppu {
+        ppu.set_renderer(Renderer::Fifo);
+        ppu
+    }
+
+    /// Run to the start of `line`, then measure the length of
+    /// [`Mode::Drawing`] on it in dots
+    fn drawing_length(ppu: &mut Ppu, line: u8) -> usize {
+        while ppu.ly != line || ppu.mode != Mode::OamScan {
+            ppu.step_dot();
+        }
+        while ppu.mode != Mode::Drawing {
+            ppu.step_dot();
+        }
+
+        let mut dots = 0;
+        while ppu.mode == Mode::Drawing {
+            ppu.step_dot();
+            dots += 1;
+        }
+        dots
+    }
+
+    /// Run until the LCD is at pixel `x` of `line`
+    fn run_to_pixel(ppu: &mut Ppu, line: u8, x: u8) {
+        while ppu.ly != line || ppu.mode != Mode::Drawing || ppu.fifo.x != x {
+            ppu.step_dot();
+        }
+    }
+
+    /// Run until the end of the current frame
+    fn run_to_vblank(ppu: &mut Ppu) {
+        while ppu.ly != SCREEN_HEIGHT as u8 {
+            ppu.step_dot();
+        }
+    }
+
+    /// Draw a frame with both renderers and compare the results
+    fn assert_same_frame(ppu: Ppu) {
+        let mut scanline = ppu.clone();
+        let mut fifo = fifo(ppu);
+        for _ in 0..2 {
+            scanline.tick(LINE * LINES_PER_FRAME as usize);
+            fifo.tick(LINE * LINES_PER_FRAME as usize);
+        }
+
+        for (i, (a, b)) in scanline
+            .frame_buffer()
+            .iter()
+            .zip(fifo.frame_buffer())
+            .enumerate()
+        {
+            let (x, y) = (i % SCREEN_WIDTH, i / SCREEN_WIDTH);
+            assert_eq!(a, b, "pixel {}, {}", x, y);
+        }
+    }
+
+    /// A busy scene: a background with a pattern in every tile, a window and
+    /// a few overlapping sprites
+    fn scene() -> Ppu {
+        let mut ppu = setup_sprites();
+        for tile in 0..32u16 {
+            for row in 0..16 {
+                let byte = (tile as u8).wrapping_mul(37).wrapping_add(row as u8 * 11);
+                ppu.write_byte(byte, 0x8000 + tile * 16 + row);
+            }
+        }
+        for addr in 0x9800..0xA000u16 {
+            ppu.write_byte((addr % 32) as u8, addr);
+        }
+        sprite(&mut ppu, 0, 20, 12, 5, 0);
+        sprite(&mut ppu, 1, 22, 15, 6, SPRITE_X_FLIP | SPRITE_PALETTE);
+        sprite(&mut ppu, 2, 40, 100, 7, SPRITE_BG_PRIORITY);
+        sprite(&mut ppu, 3, 40, 4, 8, SPRITE_Y_FLIP);
+        sprite(&mut ppu, 4, 100, 166, 9, 0);
+        ppu.write_byte(0x1B, OBP1);
+        ppu
+    }
+
+    #[test]
+    fn same_output_as_scanline() {
+        assert_same_frame(scene());
+
+        let mut ppu = scene();
+        ppu.write_byte(13, SCX);
+        ppu.write_byte(200, SCY);
+        assert_same_frame(ppu);
+
+        let mut ppu = scene();
+        ppu.write_byte(ppu.read_byte(LCDC) | LCDC_WINDOW_ENABLE, LCDC);
+        ppu.write_byte(50, WX);
+        ppu.write_byte(30, WY);
+        ppu.write_byte(5, SCX);
+        assert_same_frame(ppu);
+
+        let mut ppu = scene();
+        ppu.write_byte(ppu.read_byte(LCDC) | LCDC_WINDOW_ENABLE, LCDC);
+        ppu.write_byte(3, WX);
+        assert_same_frame(ppu);
+
+        let mut ppu = scene();
+        ppu.write_byte(ppu.read_byte(LCDC) | LCDC_SPRITE_SIZE, LCDC);
+        assert_same_frame(ppu);
+    }
+
+    #[test]
+    fn drawing_length_plain() {
+        let mut ppu = fifo(setup());
+        assert_eq!(drawing_length(&mut ppu, 1), DRAWING_DOTS);
+    }
+
+    #[test]
+    fn drawing_length_fine_scroll() {
+        let mut ppu = fifo(setup());
+        ppu.write_byte(3, SCX);
+        assert_eq!(drawing_length(&mut ppu, 1), DRAWING_DOTS + 3);
+
+        // Coarse scroll is free
+        ppu.write_byte(16, SCX);
+        assert_eq!(drawing_length(&mut ppu, 2), DRAWING_DOTS);
+    }
+
+    #[test]
+    fn drawing_length_window() {
+        let mut ppu = fifo(setup_window(50, 0));
+        assert_eq!(drawing_length(&mut ppu, 1), DRAWING_DOTS + 6);
+    }
+
+    #[test]
+    fn drawing_length_sprites() {
+        let mut ppu = fifo(setup_sprites());
+        sprite(&mut ppu, 0, 16, 8, 1, 0);
+        assert_eq!(drawing_length(&mut ppu, 1), DRAWING_DOTS + 11);
+
+        // Second sprite on the same tile only pays for its own fetch
+        sprite(&mut ppu, 1, 16, 8, 1, 0);
+        assert_eq!(drawing_length(&mut ppu, 2), DRAWING_DOTS + 17);
+
+        // Sprites late in a tile wait less for the fetcher
+        let mut ppu = fifo(setup_sprites());
+        sprite(&mut ppu, 0, 16, 8 + 13, 1, 0);
+        assert_eq!(drawing_length(&mut ppu, 1), DRAWING_DOTS + 6);
+
+        // Sprites are only fetched if enabled
+        ppu.write_byte(ppu.read_byte(LCDC) & !LCDC_SPRITE_ENABLE, LCDC);
+        assert_eq!(drawing_length(&mut ppu, 2), DRAWING_DOTS);
+    }
+
+    #[test]
+    fn background_disabled_ignores_palette() {
+        let mut ppu = fifo(setup_sprites());
+        ppu.write_byte(0xFF, BGP);
+        ppu.write_byte(LCDC_LCD_ENABLE | LCDC_TILE_DATA | LCDC_SPRITE_ENABLE, LCDC);
+        sprite(&mut ppu, 0, 16, 8, 2, 0);
+        run_to_vblank(&mut ppu);
+
+        let line = &ppu.frame_buffer()[..SCREEN_WIDTH];
+        assert_eq!(line[0], 2);
+        assert!(line[8..].iter().all(|shade| *shade == 0));
+    }
+
+    #[test]
+    fn mid_line_palette_change() {
+        let mut ppu = fifo(setup());
+        solid_tile(&mut ppu, 0x8000, 1);
+        run_to_pixel(&mut ppu, 5, 80);
+        ppu.write_byte(0xE4 ^ 0x0C, BGP);
+        run_to_vblank(&mut ppu);
+
+        let line = &ppu.frame_buffer()[5 * SCREEN_WIDTH..6 * SCREEN_WIDTH];
+        assert!(line[..80].iter().all(|shade| *shade == 1));
+        assert!(line[80..].iter().all(|shade| *shade == 2));
+    }
+
+    #[test]
+    fn mid_line_scroll_change() {
+        let mut ppu = fifo(setup());
+        solid_tile(&mut ppu, 0x8010, 3);
+        for addr in (0x9800..0x9C00).step_by(2) {
+            ppu.write_byte(1, addr);
+        }
+
+        // Coarse scroll changes show up from the next fetched tile
+        run_to_pixel(&mut ppu, 5, 80);
+        ppu.write_byte(8, SCX);
+        run_to_vblank(&mut ppu);
+
+        let line = &ppu.frame_buffer()[5 * SCREEN_WIDTH..6 * SCREEN_WIDTH];
+        assert_eq!(line[0], 3);
+        assert_eq!(line[8], 0);
+        // The tile at 80 was fetched before the write
+        assert_eq!(line[80], 3);
+        assert_eq!(line[88], 3);
+        assert_eq!(line[96], 0);
+        assert_eq!(line[104], 3);
+    }
+}
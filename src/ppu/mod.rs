@@ -0,0 +1,1299 @@
+use crate::cpu::interrupts::Interrupt;
+use crate::memory::map::{SPRITE_ATTRS_END, SPRITE_ATTRS_START, VRAM_END, VRAM_START};
+use crate::memory::ram::Ram;
+use crate::memory::region::MemoryRegion;
+
+mod fifo;
+
+use fifo::Fifo;
+
+/// Memory mapped location of the [`lcdc`](#structfield.lcdc) register.
+pub const LCDC: u16 = 0xFF40;
+/// Memory mapped location of the [`stat`](#structfield.stat) register.
+pub const STAT: u16 = 0xFF41;
+/// Memory mapped location of the [`scy`](#structfield.scy) register.
+pub const SCY: u16 = 0xFF42;
+/// Memory mapped location of the [`scx`](#structfield.scx) register.
+pub const SCX: u16 = 0xFF43;
+/// Memory mapped location of the [`ly`](#structfield.ly) register.
+pub const LY: u16 = 0xFF44;
+/// Memory mapped location of the [`lyc`](#structfield.lyc) register.
+pub const LYC: u16 = 0xFF45;
+/// Memory mapped location of the [`bgp`](#structfield.bgp) register.
+pub const BGP: u16 = 0xFF47;
+/// Memory mapped location of the [`obp0`](#structfield.obp0) register.
+pub const OBP0: u16 = 0xFF48;
+/// Memory mapped location of the [`obp1`](#structfield.obp1) register.
+pub const OBP1: u16 = 0xFF49;
+/// Memory mapped location of the [`wy`](#structfield.wy) register.
+pub const WY: u16 = 0xFF4A;
+/// Memory mapped location of the [`wx`](#structfield.wx) register.
+pub const WX: u16 = 0xFF4B;
+
+/// Width of the LCD in pixels
+pub const SCREEN_WIDTH: usize = 160;
+/// Height of the LCD in pixels
+pub const SCREEN_HEIGHT: usize = 144;
+
+/// Dots (4.19 MHz clock ticks) per machine cycle
+const DOTS_PER_MACHINE_CYCLE: usize = 4;
+/// Dots spent on every line, visible or not
+const DOTS_PER_LINE: usize = 456;
+/// Dots spent in [`Mode::OamScan`] at the start of every visible line
+const OAM_SCAN_DOTS: usize = 80;
+/// Dots spent in [`Mode::Drawing`]. On hardware this varies with scrolling,
+/// the window and sprites, but the minimum is used here.
+const DRAWING_DOTS: usize = 172;
+/// Lines per frame, including the ten lines of vertical blank
+const LINES_PER_FRAME: u8 = 154;
+/// Machine cycles it takes to draw a frame
+pub const MACHINE_CYCLES_PER_FRAME: u64 =
+    (DOTS_PER_LINE * LINES_PER_FRAME as usize / DOTS_PER_MACHINE_CYCLE) as u64;
+
+/// Gray level of each of the four shades, from white to black
+pub const GRAYS: [u8; 4] = [0xFF, 0xAA, 0x55, 0x00];
+
+/// LCDC bit: BG enable
+const LCDC_BG_ENABLE: u8 = 1 << 0;
+/// LCDC bit: Sprite enable
+const LCDC_SPRITE_ENABLE: u8 = 1 << 1;
+/// LCDC bit: Sprite size select
+const LCDC_SPRITE_SIZE: u8 = 1 << 2;
+/// LCDC bit: BG tile map select
+const LCDC_BG_TILE_MAP: u8 = 1 << 3;
+/// LCDC bit: BG and window tile data select
+const LCDC_TILE_DATA: u8 = 1 << 4;
+/// LCDC bit: Window enable
+const LCDC_WINDOW_ENABLE: u8 = 1 << 5;
+/// LCDC bit: Window tile map select
+const LCDC_WINDOW_TILE_MAP: u8 = 1 << 6;
+/// LCDC bit: LCD enable
+const LCDC_LCD_ENABLE: u8 = 1 << 7;
+
+/// WX is the window's left edge plus this offset
+const WX_OFFSET: u8 = 7;
+
+/// Number of sprites in OAM
+const SPRITE_COUNT: u16 = 40;
+/// Bytes per sprite in OAM
+const SPRITE_SIZE: u16 = 4;
+/// Most sprites the PPU can draw on a single line
+const MAX_SPRITES_PER_LINE: usize = 10;
+/// Sprite Y coordinates are offset by this, so 0 is fully above the screen
+const SPRITE_Y_OFFSET: i16 = 16;
+/// Sprite X coordinates are offset by this, so 0 is fully left of the screen
+const SPRITE_X_OFFSET: i16 = 8;
+
+/// Sprite flag: BG and window colors 1-3 are drawn over the sprite
+const SPRITE_BG_PRIORITY: u8 = 1 << 7;
+/// Sprite flag: Flip vertically
+const SPRITE_Y_FLIP: u8 = 1 << 6;
+/// Sprite flag: Flip horizontally
+const SPRITE_X_FLIP: u8 = 1 << 5;
+/// Sprite flag: Use OBP1 instead of OBP0
+const SPRITE_PALETTE: u8 = 1 << 4;
+
+/// STAT bits writable by the CPU
+const STAT_WRITABLE: u8 = 0b0111_1000;
+/// STAT bit: LYC == LY
+const STAT_COINCIDENCE: u8 = 1 << 2;
+/// STAT bit: Mode 0 interrupt select
+const STAT_HBLANK_INTERRUPT: u8 = 1 << 3;
+/// STAT bit: Mode 1 interrupt select
+const STAT_VBLANK_INTERRUPT: u8 = 1 << 4;
+/// STAT bit: Mode 2 interrupt select
+const STAT_OAM_INTERRUPT: u8 = 1 << 5;
+/// STAT bit: LYC == LY interrupt select
+const STAT_LYC_INTERRUPT: u8 = 1 << 6;
+
+/// The PPU mode, as reported in the lower two bits of STAT
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+    /// Horizontal blank, after a line has been drawn
+    HBlank = 0,
+    /// Vertical blank, lines 144-153
+    VBlank = 1,
+    /// Searching OAM for sprites on the current line
+    OamScan = 2,
+    /// Transferring pixels to the LCD
+    Drawing = 3,
+}
+
+/// How the PPU turns VRAM into pixels
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Renderer {
+    /// Draw each line at once at the end of [`Mode::Drawing`]. Fast, and good
+    /// enough for most games.
+    #[default]
+    Scanline,
+    /// Push pixels to the LCD one dot at a time through a pixel FIFO. Slower,
+    /// but picks up register writes in the middle of a line and gives
+    /// [`Mode::Drawing`] its real, variable length.
+    Fifo,
+}
+
+/// A sprite as stored in OAM
+#[derive(Debug, Clone, Copy)]
+struct Sprite {
+    /// Y position plus 16
+    y: u8,
+    /// X position plus 8
+    x: u8,
+    /// Tile number, always from 0x8000
+    tile: u8,
+    /// Attributes.
+    /// ```text
+    /// 0000xxxx
+    /// |||`----- Palette (0: OBP0, 1: OBP1)
+    /// ||`------ X flip
+    /// |`------- Y flip
+    /// `-------- BG and window over sprite
+    /// ```
+    flags: u8,
+}
+
+/// The pixel processing unit. Owns video RAM, sprite attribute memory and the
+/// LCD registers, and draws a frame into a frame buffer as it's ticked along
+/// with the CPU.
+#[derive(Debug, Clone)]
+pub struct Ppu {
+    vram: Ram,
+    oam: Ram,
+    /// LCD control (R/W).
+    /// ```text
+    /// 00000000
+    /// |||||||`- BG enable
+    /// ||||||`-- Sprite enable
+    /// |||||`--- Sprite size (0: 8x8, 1: 8x16)
+    /// ||||`---- BG tile map (0: 0x9800-0x9BFF, 1: 0x9C00-0x9FFF)
+    /// |||`----- BG and window tile data (0: 0x8800-0x97FF, 1: 0x8000-0x8FFF)
+    /// ||`------ Window enable
+    /// |`------- Window tile map (0: 0x9800-0x9BFF, 1: 0x9C00-0x9FFF)
+    /// `-------- LCD enable
+    /// ```
+    lcdc: u8,
+    /// LCD status (R/W). Only the interrupt select bits are stored here, the
+    /// mode and coincidence flag are added when read.
+    /// ```text
+    /// x0000000
+    ///  ||||||`- Mode (read only)
+    ///  |||||`-- LYC == LY (read only)
+    ///  ||||`--- Mode 0 interrupt select
+    ///  |||`---- Mode 1 interrupt select
+    ///  ||`----- Mode 2 interrupt select
+    ///  |`------ LYC == LY interrupt select
+    /// ```
+    stat: u8,
+    /// Background scroll Y (R/W)
+    scy: u8,
+    /// Background scroll X (R/W)
+    scx: u8,
+    /// Current line (R), 0-153
+    ly: u8,
+    /// LY compare (R/W)
+    lyc: u8,
+    /// Background palette (R/W). Two bits per color number, mapping it to a
+    /// shade from 0 (white) to 3 (black).
+    bgp: u8,
+    /// Sprite palette 0 (R/W)
+    obp0: u8,
+    /// Sprite palette 1 (R/W)
+    obp1: u8,
+    /// Window Y position (R/W)
+    wy: u8,
+    /// Window X position plus 7 (R/W)
+    wx: u8,
+    mode: Mode,
+    /// Internal use. Dots spent on the current line so far.
+    dot: usize,
+    /// Internal use. State of the STAT interrupt line, which is the OR of all
+    /// selected STAT interrupt sources. An interrupt is only requested when
+    /// the line goes from low to high.
+    stat_line: bool,
+    /// Internal use. Sprites found on the current line during OAM scan,
+    /// ordered by drawing priority.
+    line_sprites: Vec<Sprite>,
+    /// Internal use. Set once LY has matched WY during the current frame,
+    /// after which the window can be drawn.
+    window_y_triggered: bool,
+    /// Internal use. The line of the window to draw next. Only advances on
+    /// lines where the window was drawn, so hiding the window for a few
+    /// lines doesn't skip any of its lines.
+    window_line: u8,
+    /// Internal use. Which renderer draws the lines.
+    renderer: Renderer,
+    /// Internal use. State of the FIFO renderer.
+    fifo: Fifo,
+    /// Shades of the last drawn frame, row by row
+    frame_buffer: Vec<u8>,
+    /// Set when a frame has been completed and not yet polled
+    frame_ready: bool,
+}
+
+impl Default for Ppu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Ppu {
+    pub fn new() -> Self {
+        Self {
+            vram: Ram::new(VRAM_START, VRAM_END),
+            oam: Ram::new(SPRITE_ATTRS_START, SPRITE_ATTRS_END),
+            lcdc: 0,
+            stat: 0,
+            scy: 0,
+            scx: 0,
+            ly: 0,
+            lyc: 0,
+            bgp: 0,
+            obp0: 0,
+            obp1: 0,
+            wy: 0,
+            wx: 0,
+            mode: Mode::HBlank,
+            dot: 0,
+            stat_line: false,
+            line_sprites: Vec::with_capacity(MAX_SPRITES_PER_LINE),
+            window_y_triggered: false,
+            window_line: 0,
+            renderer: Renderer::default(),
+            fifo: Fifo::default(),
+            frame_buffer: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT],
+            frame_ready: false,
+        }
+    }
+
+    /// Switch to drawing with `renderer`
+    pub fn set_renderer(&mut self, renderer: Renderer) {
+        self.renderer = renderer;
+    }
+
+    fn lcd_enabled(&self) -> bool {
+        self.lcdc & LCDC_LCD_ENABLE != 0
+    }
+
+    /// Shades of the last drawn frame, [`SCREEN_WIDTH`] pixels per row and
+    /// [`SCREEN_HEIGHT`] rows. Each shade is a value from 0 (white) to 3
+    /// (black).
+    pub fn frame_buffer(&self) -> &[u8] {
+        &self.frame_buffer
+    }
+
+    /// Check whether a frame was completed since the last call
+    pub fn poll_frame(&mut self) -> bool {
+        std::mem::take(&mut self.frame_ready)
+    }
+
+    /// Run the PPU for a number of machine cycles.
+    ///
+    /// # Arguments
+    /// * `machine_cycles` - The amount of machine cycles that have ticked since
+    ///   last invocation. Every machine cycle is four dots.
+    ///
+    /// # Returns
+    /// A bitmask of the interrupts that were requested while ticking.
+    pub fn tick(&mut self, machine_cycles: usize) -> u8 {
+        let mut interrupts = 0;
+        if !self.lcd_enabled() {
+            return interrupts;
+        }
+
+        for _ in 0..machine_cycles * DOTS_PER_MACHINE_CYCLE {
+            interrupts |= self.step_dot();
+        }
+        interrupts
+    }
+
+    /// Advance the mode state machine by a single dot
+    fn step_dot(&mut self) -> u8 {
+        let mut interrupts = 0;
+        self.dot += 1;
+
+        match self.mode {
+            Mode::OamScan if self.dot == OAM_SCAN_DOTS => {
+                self.scan_oam();
+                self.mode = Mode::Drawing;
+                if self.renderer == Renderer::Fifo {
+                    self.fifo_start_line();
+                }
+            }
+            Mode::Drawing => {
+                let done = match self.renderer {
+                    Renderer::Scanline if self.dot == OAM_SCAN_DOTS + DRAWING_DOTS => {
+                        self.render_line();
+                        true
+                    }
+                    Renderer::Scanline => false,
+                    Renderer::Fifo => self.fifo_step(),
+                };
+                if done {
+                    self.mode = Mode::HBlank;
+                }
+            }
+            Mode::HBlank | Mode::VBlank if self.dot == DOTS_PER_LINE => {
+                self.dot = 0;
+                self.ly += 1;
+
+                if self.ly == SCREEN_HEIGHT as u8 {
+                    self.mode = Mode::VBlank;
+                    self.frame_ready = true;
+                    interrupts |= u8::from(Interrupt::Vblank);
+                } else if self.ly == LINES_PER_FRAME {
+                    self.ly = 0;
+                    self.window_y_triggered = false;
+                    self.window_line = 0;
+                    self.start_line();
+                } else if self.mode == Mode::HBlank {
+                    self.start_line();
+                }
+            }
+            _ => {}
+        }
+
+        let stat_line = self.stat_sources() != 0;
+        if stat_line && !self.stat_line {
+            interrupts |= u8::from(Interrupt::Lcdc);
+        }
+        self.stat_line = stat_line;
+
+        interrupts
+    }
+
+    /// The STAT interrupt sources that are selected and currently active
+    fn stat_sources(&self) -> u8 {
+        let mut sources = match self.mode {
+            Mode::HBlank => STAT_HBLANK_INTERRUPT,
+            // The mode 2 source also fires at the very start of VBlank
+            Mode::VBlank if self.ly == SCREEN_HEIGHT as u8 && self.dot == 0 => {
+                STAT_VBLANK_INTERRUPT | STAT_OAM_INTERRUPT
+            }
+            Mode::VBlank => STAT_VBLANK_INTERRUPT,
+            Mode::OamScan => STAT_OAM_INTERRUPT,
+            Mode::Drawing => 0,
+        };
+        if self.ly == self.lyc {
+            sources |= STAT_LYC_INTERRUPT;
+        }
+
+        sources & self.stat
+    }
+
+    /// Enter [`Mode::OamScan`] at the start of a visible line. This is where
+    /// LY is compared against WY to decide if the window starts on this line.
+    fn start_line(&mut self) {
+        self.mode = Mode::OamScan;
+        if self.ly == self.wy {
+            self.window_y_triggered = true;
+        }
+    }
+
+    fn sprite_height(&self) -> i16 {
+        if self.lcdc & LCDC_SPRITE_SIZE != 0 {
+            16
+        } else {
+            8
+        }
+    }
+
+    /// Find the sprites on the current line. The first ten sprites in OAM
+    /// that overlap the line are picked, no matter their X position. They are
+    /// then ordered for drawing: the sprite with the lowest X coordinate is
+    /// drawn on top, and sprites with the same X coordinate are drawn in OAM
+    /// order.
+    fn scan_oam(&mut self) {
+        let line = self.ly as i16 + SPRITE_Y_OFFSET;
+        let height = self.sprite_height();
+
+        self.line_sprites.clear();
+        for i in 0..SPRITE_COUNT {
+            let addr = SPRITE_ATTRS_START + i * SPRITE_SIZE;
+            let y = self.oam.read_byte(addr);
+            if !(y as i16..y as i16 + height).contains(&line) {
+                continue;
+            }
+
+            self.line_sprites.push(Sprite {
+                y,
+                x: self.oam.read_byte(addr + 1),
+                tile: self.oam.read_byte(addr + 2),
+                flags: self.oam.read_byte(addr + 3),
+            });
+            if self.line_sprites.len() == MAX_SPRITES_PER_LINE {
+                break;
+            }
+        }
+
+        // Stable sort, so OAM order is kept for equal X coordinates
+        self.line_sprites.sort_by_key(|sprite| sprite.x);
+    }
+
+    /// Draw the current line into the frame buffer. The scroll and window
+    /// registers are sampled here, at the end of [`Mode::Drawing`], so
+    /// changes made during HBlank take effect from the next line.
+    fn render_line(&mut self) {
+        let line = self.ly as usize * SCREEN_WIDTH;
+        let bg_enabled = self.lcdc & LCDC_BG_ENABLE != 0;
+        let sprites_enabled = self.lcdc & LCDC_SPRITE_ENABLE != 0;
+        let window_visible = self.window_visible();
+
+        for x in 0..SCREEN_WIDTH as u8 {
+            let bg_color = if !bg_enabled {
+                0
+            } else if window_visible && x + WX_OFFSET >= self.wx {
+                self.window_color(x + WX_OFFSET - self.wx)
+            } else {
+                self.background_color(x)
+            };
+
+            let sprite = self
+                .sprite_pixel(x)
+                .filter(|_| sprites_enabled)
+                .map(|(color, sprite)| (color, sprite.flags));
+            self.frame_buffer[line + x as usize] = self.mix(bg_color, sprite);
+        }
+
+        if window_visible {
+            self.window_line += 1;
+        }
+    }
+
+    /// Shade of a pixel with background color number `bg_color`, and
+    /// optionally an opaque sprite pixel given as its color number and flags
+    fn mix(&self, bg_color: u8, sprite: Option<(u8, u8)>) -> u8 {
+        match sprite {
+            Some((color, flags)) if flags & SPRITE_BG_PRIORITY == 0 || bg_color == 0 => {
+                let palette = if flags & SPRITE_PALETTE != 0 {
+                    self.obp1
+                } else {
+                    self.obp0
+                };
+                Self::shade(palette, color)
+            }
+            // The background being off blanks it to white, whatever BGP says
+            _ if self.lcdc & LCDC_BG_ENABLE == 0 => 0,
+            _ => Self::shade(self.bgp, bg_color),
+        }
+    }
+
+    /// The opaque sprite pixel with the highest priority at pixel `x` of the
+    /// current line, along with the sprite it belongs to. Color 0 is
+    /// transparent, letting sprites with lower priority show through.
+    fn sprite_pixel(&self, x: u8) -> Option<(u8, Sprite)> {
+        self.line_sprites.iter().find_map(|sprite| {
+            let column = x as i16 + SPRITE_X_OFFSET - sprite.x as i16;
+            if !(0..8).contains(&column) {
+                return None;
+            }
+
+            let color = self.sprite_color(sprite, column as u8);
+            (color != 0).then_some((color, *sprite))
+        })
+    }
+
+    /// Color number of `column` of `sprite` on the current line
+    fn sprite_color(&self, sprite: &Sprite, column: u8) -> u8 {
+        let height = self.sprite_height();
+        let row = (self.ly as i16 + SPRITE_Y_OFFSET - sprite.y as i16) & (height - 1);
+        let row = if sprite.flags & SPRITE_Y_FLIP != 0 {
+            height - 1 - row
+        } else {
+            row
+        };
+        let column = if sprite.flags & SPRITE_X_FLIP != 0 {
+            7 - column
+        } else {
+            column
+        };
+
+        // In 8x16 mode, the top half is the even tile and the bottom half the
+        // odd tile following it
+        let tile = if height == 16 {
+            sprite.tile & 0xFE
+        } else {
+            sprite.tile
+        };
+        self.tile_color(0x8000 + tile as u16 * 16, column, row as u8)
+    }
+
+    /// Whether any part of the window is drawn on the current line. On the
+    /// DMG, clearing LCDC bit 0 hides the window as well as the background.
+    fn window_visible(&self) -> bool {
+        let enabled = LCDC_BG_ENABLE | LCDC_WINDOW_ENABLE;
+        self.lcdc & enabled == enabled
+            && self.window_y_triggered
+            && self.wx < SCREEN_WIDTH as u8 + WX_OFFSET
+    }
+
+    /// Color number of the background at pixel `x` of the current line
+    fn background_color(&self, x: u8) -> u8 {
+        self.map_color(
+            self.background_map(),
+            self.scx.wrapping_add(x),
+            self.scy.wrapping_add(self.ly),
+        )
+    }
+
+    /// Color number of pixel `x` of the current window line
+    fn window_color(&self, x: u8) -> u8 {
+        self.map_color(self.window_map(), x, self.window_line)
+    }
+
+    /// Address of the background tile map
+    fn background_map(&self) -> u16 {
+        if self.lcdc & LCDC_BG_TILE_MAP != 0 {
+            0x9C00
+        } else {
+            0x9800
+        }
+    }
+
+    /// Address of the window tile map
+    fn window_map(&self) -> u16 {
+        if self.lcdc & LCDC_WINDOW_TILE_MAP != 0 {
+            0x9C00
+        } else {
+            0x9800
+        }
+    }
+
+    /// Color number of pixel `x`, `y` of the 256x256 pixel image described by
+    /// the tile map at `map`
+    fn map_color(&self, map: u16, x: u8, y: u8) -> u8 {
+        let tile = self.map_tile(map, x, y);
+        self.tile_color(self.tile_address(tile), x % 8, y % 8)
+    }
+
+    /// Tile number at pixel `x`, `y` of the tile map at `map`
+    fn map_tile(&self, map: u16, x: u8, y: u8) -> u8 {
+        self.vram
+            .read_byte(map + (y as u16 / 8) * 32 + x as u16 / 8)
+    }
+
+    /// Address of the tile data for background and window tile `tile`. With
+    /// LCDC bit 4 set, tiles are numbered 0-255 from 0x8000. Otherwise they
+    /// are numbered -128-127 around 0x9000.
+    fn tile_address(&self, tile: u8) -> u16 {
+        if self.lcdc & LCDC_TILE_DATA != 0 {
+            0x8000 + tile as u16 * 16
+        } else {
+            0x9000u16.wrapping_add_signed(tile as i8 as i16 * 16)
+        }
+    }
+
+    /// Color number (0-3) of pixel `x`, `y` of the tile at `address`. Every
+    /// row of a tile is two bytes, where the first holds the low bits of the
+    /// colors and the second the high bits. The leftmost pixel is bit 7.
+    fn tile_color(&self, address: u16, x: u8, y: u8) -> u8 {
+        let row = address + y as u16 * 2;
+        let lo = self.vram.read_byte(row);
+        let hi = self.vram.read_byte(row + 1);
+        let bit = 7 - x;
+        ((hi >> bit) & 1) << 1 | ((lo >> bit) & 1)
+    }
+
+    /// Map a color number to a shade through `palette`
+    fn shade(palette: u8, color: u8) -> u8 {
+        (palette >> (color * 2)) & 0x03
+    }
+
+    fn write_lcdc(&mut self, byte: u8) {
+        let was_enabled = self.lcd_enabled();
+        self.lcdc = byte;
+
+        match (was_enabled, self.lcd_enabled()) {
+            // Turning the LCD off resets the line, and it starts over from
+            // the top when turned back on
+            (true, false) => {
+                self.ly = 0;
+                self.dot = 0;
+                self.mode = Mode::HBlank;
+                self.stat_line = false;
+                self.window_y_triggered = false;
+                self.window_line = 0;
+            }
+            (false, true) => self.start_line(),
+            _ => {}
+        }
+    }
+}
+
+impl MemoryRegion for Ppu {
+    /// Read byte from video RAM, sprite attribute memory or the LCD
+    /// registers.
+    ///
+    /// # Panics
+    /// If `addr` is not owned by the PPU, the function panics.
+    fn read_byte(&self, addr: u16) -> u8 {
+        match addr {
+            VRAM_START..=VRAM_END => self.vram.read_byte(addr),
+            SPRITE_ATTRS_START..=SPRITE_ATTRS_END => self.oam.read_byte(addr),
+            LCDC => self.lcdc,
+            STAT => {
+                let coincidence = if self.ly == self.lyc {
+                    STAT_COINCIDENCE
+                } else {
+                    0
+                };
+                0x80 | self.stat | coincidence | self.mode as u8
+            }
+            SCY => self.scy,
+            SCX => self.scx,
+            LY => self.ly,
+            LYC => self.lyc,
+            BGP => self.bgp,
+            OBP0 => self.obp0,
+            OBP1 => self.obp1,
+            WY => self.wy,
+            WX => self.wx,
+            _ => panic!("Invalid PPU address: {:x}", addr),
+        }
+    }
+
+    /// Write byte to video RAM, sprite attribute memory or the LCD registers.
+    /// Writes to [`ly`](#structfield.ly) are ignored.
+    ///
+    /// # Panics
+    /// If `addr` is not owned by the PPU, the function panics.
+    fn write_byte(&mut self, byte: u8, addr: u16) {
+        match addr {
+            VRAM_START..=VRAM_END => self.vram.write_byte(byte, addr),
+            SPRITE_ATTRS_START..=SPRITE_ATTRS_END => self.oam.write_byte(byte, addr),
+            LCDC => self.write_lcdc(byte),
+            STAT => self.stat = byte & STAT_WRITABLE,
+            SCY => self.scy = byte,
+            SCX => self.scx = byte,
+            LY => {}
+            LYC => self.lyc = byte,
+            BGP => self.bgp = byte,
+            OBP0 => self.obp0 = byte,
+            OBP1 => self.obp1 = byte,
+            WY => self.wy = byte,
+            WX => self.wx = byte,
+            _ => panic!("Invalid PPU address: {:x}", addr),
+        }
+    }
+
+    fn read_word(&self, addr: u16) -> u16 {
+        let lo = self.read_byte(addr);
+        let hi = self.read_byte(addr + 1);
+        u16::from_le_bytes([lo, hi])
+    }
+
+    fn write_word(&mut self, word: u16, addr: u16) {
+        let [lo, hi] = word.to_le_bytes();
+        self.write_byte(lo, addr);
+        self.write_byte(hi, addr + 1);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Machine cycles per line
+    pub(super) const LINE: usize = DOTS_PER_LINE / DOTS_PER_MACHINE_CYCLE;
+
+    pub(super) fn setup() -> Ppu {
+        let mut ppu = Ppu::new();
+        ppu.write_byte(0xE4, BGP); // Identity palette
+        ppu.write_byte(LCDC_LCD_ENABLE | LCDC_TILE_DATA | LCDC_BG_ENABLE, LCDC);
+        ppu
+    }
+
+    /// Write a tile where every pixel has color number `color`
+    pub(super) fn solid_tile(ppu: &mut Ppu, address: u16, color: u8) {
+        let lo = if color & 1 != 0 { 0xFF } else { 0x00 };
+        let hi = if color & 2 != 0 { 0xFF } else { 0x00 };
+        for row in 0..8 {
+            ppu.write_byte(lo, address + row * 2);
+            ppu.write_byte(hi, address + row * 2 + 1);
+        }
+    }
+
+    /// Run a whole frame, starting from the top
+    fn run_frame(ppu: &mut Ppu) {
+        finish_frame(ppu, 0);
+    }
+
+    /// Run the rest of a frame where `lines` lines have already been run
+    fn finish_frame(ppu: &mut Ppu, lines: usize) {
+        ppu.tick(LINE * (LINES_PER_FRAME as usize - lines));
+    }
+
+    #[test]
+    fn mode_timing() {
+        let mut ppu = setup();
+        assert_eq!(ppu.mode, Mode::OamScan);
+
+        ppu.tick(OAM_SCAN_DOTS / 4 - 1);
+        assert_eq!(ppu.mode, Mode::OamScan);
+        ppu.tick(1);
+        assert_eq!(ppu.mode, Mode::Drawing);
+        assert_eq!(ppu.read_byte(STAT) & 0x03, 3);
+
+        ppu.tick(DRAWING_DOTS / 4);
+        assert_eq!(ppu.mode, Mode::HBlank);
+        assert_eq!(ppu.read_byte(STAT) & 0x03, 0);
+
+        ppu.tick(LINE - (OAM_SCAN_DOTS + DRAWING_DOTS) / 4 - 1);
+        assert_eq!(ppu.ly, 0);
+        ppu.tick(1);
+        assert_eq!(ppu.read_byte(LY), 1);
+        assert_eq!(ppu.mode, Mode::OamScan);
+    }
+
+    #[test]
+    fn vblank() {
+        let mut ppu = setup();
+        assert_eq!(ppu.tick(LINE * 144 - 1), 0);
+        assert!(!ppu.poll_frame());
+
+        assert_eq!(ppu.tick(1), u8::from(Interrupt::Vblank));
+        assert_eq!(ppu.read_byte(LY), 144);
+        assert_eq!(ppu.mode, Mode::VBlank);
+        assert!(ppu.poll_frame());
+        assert!(!ppu.poll_frame());
+
+        for line in 145..154 {
+            assert_eq!(ppu.tick(LINE), 0);
+            assert_eq!(ppu.read_byte(LY), line);
+            assert_eq!(ppu.mode, Mode::VBlank);
+        }
+
+        ppu.tick(LINE);
+        assert_eq!(ppu.read_byte(LY), 0);
+        assert_eq!(ppu.mode, Mode::OamScan);
+    }
+
+    #[test]
+    fn lcd_off() {
+        let mut ppu = setup();
+        ppu.tick(LINE * 10 + 20);
+        ppu.write_byte(0x00, LCDC);
+        assert_eq!(ppu.read_byte(LY), 0);
+        assert_eq!(ppu.mode, Mode::HBlank);
+
+        // Nothing happens while off
+        assert_eq!(ppu.tick(LINE * 200), 0);
+        assert_eq!(ppu.read_byte(LY), 0);
+
+        ppu.write_byte(LCDC_LCD_ENABLE, LCDC);
+        assert_eq!(ppu.mode, Mode::OamScan);
+    }
+
+    #[test]
+    fn ly_is_read_only() {
+        let mut ppu = setup();
+        ppu.tick(LINE * 3);
+        ppu.write_byte(0x42, LY);
+        assert_eq!(ppu.read_byte(LY), 3);
+    }
+
+    #[test]
+    fn background() {
+        let mut ppu = setup();
+        solid_tile(&mut ppu, 0x8010, 1);
+        solid_tile(&mut ppu, 0x8020, 3);
+
+        // Tile 1 at the top left, tile 2 to the right of it and below it
+        ppu.write_byte(1, 0x9800);
+        ppu.write_byte(2, 0x9801);
+        ppu.write_byte(2, 0x9820);
+
+        run_frame(&mut ppu);
+        let frame = ppu.frame_buffer();
+        assert_eq!(frame[0], 1);
+        assert_eq!(frame[7 * SCREEN_WIDTH + 7], 1);
+        assert_eq!(frame[8], 3);
+        assert_eq!(frame[8 * SCREEN_WIDTH], 3);
+        assert_eq!(frame[16], 0);
+        assert_eq!(frame[8 * SCREEN_WIDTH + 8], 0);
+    }
+
+    #[test]
+    fn tile_row_bits() {
+        let mut ppu = setup();
+        // Colors 0, 1, 2, 3, 0, 1, 2, 3 from left to right
+        ppu.write_byte(0b0101_0101, 0x8000);
+        ppu.write_byte(0b0011_0011, 0x8001);
+
+        run_frame(&mut ppu);
+        assert_eq!(&ppu.frame_buffer()[0..8], &[0, 1, 2, 3, 0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn scrolling() {
+        let mut ppu = setup();
+        solid_tile(&mut ppu, 0x8010, 3);
+        // Bottom right tile of the map
+        ppu.write_byte(1, 0x9BFF);
+
+        ppu.write_byte(0xFC, SCX);
+        ppu.write_byte(0xFE, SCY);
+        run_frame(&mut ppu);
+
+        // The tile wraps around to the top left corner
+        let frame = ppu.frame_buffer();
+        assert_eq!(frame[0], 3);
+        assert_eq!(frame[3], 3);
+        assert_eq!(frame[4], 0);
+        assert_eq!(frame[SCREEN_WIDTH], 3);
+        assert_eq!(frame[2 * SCREEN_WIDTH], 0);
+    }
+
+    #[test]
+    fn palette() {
+        let mut ppu = setup();
+        solid_tile(&mut ppu, 0x8000, 2);
+        ppu.write_byte(0b00_01_10_11, BGP);
+        run_frame(&mut ppu);
+        assert_eq!(ppu.frame_buffer()[0], 1);
+    }
+
+    #[test]
+    fn signed_tile_data() {
+        let mut ppu = setup();
+        ppu.write_byte(LCDC_LCD_ENABLE | LCDC_BG_TILE_MAP | LCDC_BG_ENABLE, LCDC);
+        solid_tile(&mut ppu, 0x9000, 1);
+        solid_tile(&mut ppu, 0x8800, 2);
+        ppu.write_byte(0x00, 0x9C00);
+        ppu.write_byte(0x80, 0x9C01);
+
+        run_frame(&mut ppu);
+        let frame = ppu.frame_buffer();
+        assert_eq!(frame[0], 1);
+        assert_eq!(frame[8], 2);
+    }
+
+    #[test]
+    fn background_disabled() {
+        let mut ppu = setup();
+        solid_tile(&mut ppu, 0x8000, 3);
+        ppu.write_byte(LCDC_LCD_ENABLE, LCDC);
+        run_frame(&mut ppu);
+        assert!(ppu.frame_buffer().iter().all(|shade| *shade == 0));
+    }
+
+    #[test]
+    fn background_disabled_ignores_palette() {
+        let mut ppu = setup_sprites();
+        ppu.write_byte(0xFF, BGP);
+        ppu.write_byte(LCDC_LCD_ENABLE | LCDC_TILE_DATA | LCDC_SPRITE_ENABLE, LCDC);
+        sprite(&mut ppu, 0, 16, 8, 2, 0);
+        run_frame(&mut ppu);
+        assert_eq!(pixel(&ppu, 0, 0), 2);
+        assert_eq!(pixel(&ppu, 8, 0), 0);
+        assert_eq!(pixel(&ppu, 100, 100), 0);
+    }
+
+    /// Set up a window of solid color 3 over a background of color 0. The
+    /// window uses the tile map at 0x9800 and the background the one at
+    /// 0x9C00.
+    pub(super) fn setup_window(wx: u8, wy: u8) -> Ppu {
+        let mut ppu = Ppu::new();
+        ppu.write_byte(0xE4, BGP);
+        ppu.write_byte(wx, WX);
+        ppu.write_byte(wy, WY);
+        solid_tile(&mut ppu, 0x8010, 3);
+        for addr in 0x9800..0x9C00 {
+            ppu.write_byte(1, addr);
+        }
+        ppu.write_byte(
+            LCDC_LCD_ENABLE
+                | LCDC_WINDOW_ENABLE
+                | LCDC_TILE_DATA
+                | LCDC_BG_TILE_MAP
+                | LCDC_BG_ENABLE,
+            LCDC,
+        );
+        ppu
+    }
+
+    #[test]
+    fn window_position() {
+        let mut ppu = setup_window(80 + WX_OFFSET, 72);
+        run_frame(&mut ppu);
+
+        let frame = ppu.frame_buffer();
+        assert_eq!(frame[72 * SCREEN_WIDTH + 80], 3);
+        assert_eq!(frame[72 * SCREEN_WIDTH + 79], 0);
+        assert_eq!(frame[71 * SCREEN_WIDTH + 80], 0);
+        assert_eq!(frame[143 * SCREEN_WIDTH + 159], 3);
+    }
+
+    #[test]
+    fn window_hidden() {
+        // Off screen to the right
+        let mut ppu = setup_window(SCREEN_WIDTH as u8 + WX_OFFSET, 0);
+        run_frame(&mut ppu);
+        assert!(ppu.frame_buffer().iter().all(|shade| *shade == 0));
+
+        // Below the screen
+        let mut ppu = setup_window(WX_OFFSET, SCREEN_HEIGHT as u8);
+        run_frame(&mut ppu);
+        assert!(ppu.frame_buffer().iter().all(|shade| *shade == 0));
+
+        // Disabled along with the background
+        let mut ppu = setup_window(WX_OFFSET, 0);
+        ppu.write_byte(LCDC_LCD_ENABLE | LCDC_WINDOW_ENABLE | LCDC_TILE_DATA, LCDC);
+        run_frame(&mut ppu);
+        assert!(ppu.frame_buffer().iter().all(|shade| *shade == 0));
+    }
+
+    #[test]
+    fn window_tile_map() {
+        let mut ppu = setup_window(WX_OFFSET, 0);
+        solid_tile(&mut ppu, 0x8020, 1);
+        ppu.write_byte(2, 0x9C00);
+        ppu.write_byte(ppu.read_byte(LCDC) | LCDC_WINDOW_TILE_MAP, LCDC);
+        run_frame(&mut ppu);
+
+        // Both layers now use the map at 0x9C00
+        let frame = ppu.frame_buffer();
+        assert_eq!(frame[0], 1);
+        assert_eq!(frame[8], 0);
+    }
+
+    #[test]
+    fn window_line_counter() {
+        let mut ppu = setup_window(WX_OFFSET, 0);
+        // Second row of window tiles has color 1
+        solid_tile(&mut ppu, 0x8020, 1);
+        for addr in 0x9820..0x9840 {
+            ppu.write_byte(2, addr);
+        }
+        let lcdc = ppu.read_byte(LCDC);
+
+        ppu.tick(LINE * 4);
+        ppu.write_byte(lcdc & !LCDC_WINDOW_ENABLE, LCDC);
+        ppu.tick(LINE * 4);
+        ppu.write_byte(lcdc, LCDC);
+        finish_frame(&mut ppu, 8);
+
+        // Lines 4-7 had no window, so line 8 shows window line 4 and line 12
+        // shows window line 8
+        let frame = ppu.frame_buffer();
+        assert_eq!(frame[3 * SCREEN_WIDTH], 3);
+        assert_eq!(frame[4 * SCREEN_WIDTH], 0);
+        assert_eq!(frame[8 * SCREEN_WIDTH], 3);
+        assert_eq!(frame[11 * SCREEN_WIDTH], 3);
+        assert_eq!(frame[12 * SCREEN_WIDTH], 1);
+
+        // The counter starts over on the next frame
+        run_frame(&mut ppu);
+        assert_eq!(ppu.frame_buffer()[8 * SCREEN_WIDTH], 1);
+    }
+
+    #[test]
+    fn mid_frame_wx_change() {
+        let mut ppu = setup_window(WX_OFFSET, 0);
+        ppu.tick(LINE * 10);
+        ppu.write_byte(100 + WX_OFFSET, WX);
+        finish_frame(&mut ppu, 10);
+
+        let frame = ppu.frame_buffer();
+        assert_eq!(frame[9 * SCREEN_WIDTH + 50], 3);
+        assert_eq!(frame[10 * SCREEN_WIDTH + 50], 0);
+        assert_eq!(frame[10 * SCREEN_WIDTH + 100], 3);
+    }
+
+    #[test]
+    fn mid_frame_wy_change() {
+        let mut ppu = setup_window(WX_OFFSET, 100);
+        ppu.tick(LINE * 20);
+        ppu.write_byte(21, WY);
+        finish_frame(&mut ppu, 20);
+
+        // LY matches the new WY at the start of line 21
+        let frame = ppu.frame_buffer();
+        assert_eq!(frame[20 * SCREEN_WIDTH], 0);
+        assert_eq!(frame[21 * SCREEN_WIDTH], 3);
+    }
+
+    /// Set up sprites over a background of color 0. Tiles 1-3 are solid
+    /// colors 1-3, tile 4 only has its top left pixel set to color 1.
+    pub(super) fn setup_sprites() -> Ppu {
+        let mut ppu = setup();
+        ppu.write_byte(0xE4, OBP0);
+        ppu.write_byte(0x1B, OBP1); // Inverted
+        for color in 1..=3 {
+            solid_tile(&mut ppu, 0x8000 + color as u16 * 16, color);
+        }
+        ppu.write_byte(0x80, 0x8040);
+        ppu.write_byte(
+            LCDC_LCD_ENABLE | LCDC_TILE_DATA | LCDC_SPRITE_ENABLE | LCDC_BG_ENABLE,
+            LCDC,
+        );
+        ppu
+    }
+
+    pub(super) fn sprite(ppu: &mut Ppu, index: u16, y: u8, x: u8, tile: u8, flags: u8) {
+        let addr = SPRITE_ATTRS_START + index * SPRITE_SIZE;
+        ppu.write_byte(y, addr);
+        ppu.write_byte(x, addr + 1);
+        ppu.write_byte(tile, addr + 2);
+        ppu.write_byte(flags, addr + 3);
+    }
+
+    /// Shade at `x`, `y` of the last frame
+    fn pixel(ppu: &Ppu, x: usize, y: usize) -> u8 {
+        ppu.frame_buffer()[y * SCREEN_WIDTH + x]
+    }
+
+    #[test]
+    fn sprite_position() {
+        let mut ppu = setup_sprites();
+        sprite(&mut ppu, 0, 10 + 16, 20 + 8, 3, 0);
+        run_frame(&mut ppu);
+
+        assert_eq!(pixel(&ppu, 20, 10), 3);
+        assert_eq!(pixel(&ppu, 27, 17), 3);
+        assert_eq!(pixel(&ppu, 19, 10), 0);
+        assert_eq!(pixel(&ppu, 28, 10), 0);
+        assert_eq!(pixel(&ppu, 20, 9), 0);
+        assert_eq!(pixel(&ppu, 20, 18), 0);
+    }
+
+    #[test]
+    fn sprite_partially_off_screen() {
+        let mut ppu = setup_sprites();
+        sprite(&mut ppu, 0, 12, 4, 3, 0);
+        run_frame(&mut ppu);
+
+        assert_eq!(pixel(&ppu, 0, 0), 3);
+        assert_eq!(pixel(&ppu, 3, 0), 3);
+        assert_eq!(pixel(&ppu, 4, 0), 0);
+        assert_eq!(pixel(&ppu, 0, 4), 0);
+    }
+
+    #[test]
+    fn sprite_flip() {
+        let mut ppu = setup_sprites();
+        sprite(&mut ppu, 0, 16, 8, 4, 0);
+        sprite(&mut ppu, 1, 16, 16, 4, SPRITE_X_FLIP);
+        sprite(&mut ppu, 2, 16, 24, 4, SPRITE_Y_FLIP);
+        sprite(&mut ppu, 3, 16, 32, 4, SPRITE_X_FLIP | SPRITE_Y_FLIP);
+        run_frame(&mut ppu);
+
+        // Color 0 is transparent, so only the set pixel shows
+        assert_eq!(pixel(&ppu, 0, 0), 1);
+        assert_eq!(pixel(&ppu, 1, 0), 0);
+        assert_eq!(pixel(&ppu, 0, 1), 0);
+
+        assert_eq!(pixel(&ppu, 15, 0), 1);
+        assert_eq!(pixel(&ppu, 8, 0), 0);
+
+        assert_eq!(pixel(&ppu, 16, 7), 1);
+        assert_eq!(pixel(&ppu, 16, 0), 0);
+
+        assert_eq!(pixel(&ppu, 31, 7), 1);
+    }
+
+    #[test]
+    fn tall_sprites() {
+        let mut ppu = setup_sprites();
+        ppu.write_byte(ppu.read_byte(LCDC) | LCDC_SPRITE_SIZE, LCDC);
+        // Odd tile number, the lowest bit is ignored
+        sprite(&mut ppu, 0, 16, 8, 3, 0);
+        sprite(&mut ppu, 1, 16, 16, 2, SPRITE_Y_FLIP);
+        run_frame(&mut ppu);
+
+        assert_eq!(pixel(&ppu, 0, 0), 2);
+        assert_eq!(pixel(&ppu, 0, 7), 2);
+        assert_eq!(pixel(&ppu, 0, 8), 3);
+        assert_eq!(pixel(&ppu, 0, 15), 3);
+        assert_eq!(pixel(&ppu, 0, 16), 0);
+
+        // Flipping swaps the two tiles
+        assert_eq!(pixel(&ppu, 8, 0), 3);
+        assert_eq!(pixel(&ppu, 8, 15), 2);
+    }
+
+    #[test]
+    fn sprite_palettes() {
+        let mut ppu = setup_sprites();
+        sprite(&mut ppu, 0, 16, 8, 1, 0);
+        sprite(&mut ppu, 1, 16, 16, 1, SPRITE_PALETTE);
+        run_frame(&mut ppu);
+
+        assert_eq!(pixel(&ppu, 0, 0), 1);
+        assert_eq!(pixel(&ppu, 8, 0), 2);
+    }
+
+    #[test]
+    fn bg_priority() {
+        let mut ppu = setup_sprites();
+        // Background color 1 in the first tile, 0 in the second
+        ppu.write_byte(1, 0x9800);
+        sprite(&mut ppu, 0, 16, 12, 3, SPRITE_BG_PRIORITY);
+        sprite(&mut ppu, 1, 24, 12, 3, 0);
+        run_frame(&mut ppu);
+
+        // Hidden behind background colors 1-3, but not color 0
+        assert_eq!(pixel(&ppu, 4, 0), 1);
+        assert_eq!(pixel(&ppu, 8, 0), 3);
+
+        // Without the flag, the sprite is drawn over the background
+        assert_eq!(pixel(&ppu, 4, 8), 3);
+    }
+
+    #[test]
+    fn ten_sprites_per_line() {
+        let mut ppu = setup_sprites();
+        for i in 0..11 {
+            sprite(&mut ppu, i, 16, 8 + i as u8 * 8, 3, 0);
+        }
+        // Off screen sprites count towards the limit too
+        sprite(&mut ppu, 11, 32, 0, 3, 0);
+        for i in 12..22 {
+            sprite(&mut ppu, i, 32, 8 + i as u8 * 8, 3, 0);
+        }
+        run_frame(&mut ppu);
+
+        assert_eq!(pixel(&ppu, 72, 0), 3);
+        assert_eq!(pixel(&ppu, 80, 0), 0);
+        assert_eq!(pixel(&ppu, 12 * 8, 16), 3);
+        assert_eq!(pixel(&ppu, 21 * 8, 16), 0);
+    }
+
+    #[test]
+    fn sprite_x_priority() {
+        let mut ppu = setup_sprites();
+        // Lower X wins, even when later in OAM
+        sprite(&mut ppu, 0, 16, 12, 1, 0);
+        sprite(&mut ppu, 1, 16, 8, 2, 0);
+        // Same X, earlier in OAM wins
+        sprite(&mut ppu, 2, 24, 8, 1, 0);
+        sprite(&mut ppu, 3, 24, 8, 2, 0);
+        // A transparent pixel lets the next sprite show through
+        sprite(&mut ppu, 4, 32, 8, 4, 0);
+        sprite(&mut ppu, 5, 32, 8, 3, 0);
+        run_frame(&mut ppu);
+
+        assert_eq!(pixel(&ppu, 4, 0), 2);
+        assert_eq!(pixel(&ppu, 8, 0), 1);
+        assert_eq!(pixel(&ppu, 0, 8), 1);
+        assert_eq!(pixel(&ppu, 0, 16), 1);
+        assert_eq!(pixel(&ppu, 1, 16), 3);
+    }
+
+    #[test]
+    fn hidden_sprite_still_has_priority() {
+        let mut ppu = setup_sprites();
+        ppu.write_byte(1, 0x9800);
+        // The first sprite is behind the background, and hides the second
+        // sprite even though that one would be drawn over the background
+        sprite(&mut ppu, 0, 16, 8, 3, SPRITE_BG_PRIORITY);
+        sprite(&mut ppu, 1, 16, 8, 2, 0);
+        run_frame(&mut ppu);
+
+        assert_eq!(pixel(&ppu, 0, 0), 1);
+    }
+
+    #[test]
+    fn sprites_disabled() {
+        let mut ppu = setup_sprites();
+        sprite(&mut ppu, 0, 16, 8, 3, 0);
+        ppu.write_byte(ppu.read_byte(LCDC) & !LCDC_SPRITE_ENABLE, LCDC);
+        run_frame(&mut ppu);
+        assert_eq!(pixel(&ppu, 0, 0), 0);
+    }
+
+    /// Run line by line, returning whether each line requested a STAT
+    /// interrupt. An interrupt at the very end of a line, when entering the
+    /// next, is counted for the line being left.
+    fn stat_interrupts(ppu: &mut Ppu, lines: usize) -> Vec<bool> {
+        let lcdc = u8::from(Interrupt::Lcdc);
+        (0..lines).map(|_| ppu.tick(LINE) & lcdc != 0).collect()
+    }
+
+    #[test]
+    fn coincidence_flag() {
+        let mut ppu = setup();
+        ppu.write_byte(3, LYC);
+        assert_eq!(ppu.read_byte(STAT) & STAT_COINCIDENCE, 0);
+
+        ppu.tick(LINE * 3);
+        assert_eq!(ppu.read_byte(STAT) & STAT_COINCIDENCE, STAT_COINCIDENCE);
+        ppu.tick(LINE);
+        assert_eq!(ppu.read_byte(STAT) & STAT_COINCIDENCE, 0);
+
+        // Only the interrupt select bits are writable
+        ppu.write_byte(0xFF, STAT);
+        assert_eq!(ppu.read_byte(STAT), 0xFA);
+    }
+
+    #[test]
+    fn lyc_interrupt() {
+        let mut ppu = setup();
+        ppu.write_byte(STAT_LYC_INTERRUPT, STAT);
+        ppu.write_byte(10, LYC);
+
+        let interrupts = stat_interrupts(&mut ppu, LINES_PER_FRAME as usize);
+        let lines: Vec<_> = (0..interrupts.len()).filter(|i| interrupts[*i]).collect();
+        assert_eq!(lines, vec![9]);
+    }
+
+    #[test]
+    fn hblank_interrupt() {
+        let mut ppu = setup();
+        ppu.write_byte(STAT_HBLANK_INTERRUPT, STAT);
+
+        let interrupts = stat_interrupts(&mut ppu, LINES_PER_FRAME as usize);
+        assert!(interrupts[..SCREEN_HEIGHT]
+            .iter()
+            .all(|interrupt| *interrupt));
+        assert!(interrupts[SCREEN_HEIGHT..]
+            .iter()
+            .all(|interrupt| !interrupt));
+    }
+
+    #[test]
+    fn vblank_and_oam_interrupts() {
+        let mut ppu = setup();
+        ppu.write_byte(STAT_VBLANK_INTERRUPT, STAT);
+        ppu.tick(LINE * SCREEN_HEIGHT - 1);
+        let interrupts = ppu.tick(1);
+        assert_eq!(
+            interrupts,
+            u8::from(Interrupt::Vblank) | u8::from(Interrupt::Lcdc)
+        );
+
+        // The mode 2 source fires when entering VBlank too
+        let mut ppu = setup();
+        ppu.write_byte(STAT_OAM_INTERRUPT, STAT);
+        ppu.tick(LINE * SCREEN_HEIGHT - 1);
+        assert_ne!(ppu.tick(1) & u8::from(Interrupt::Lcdc), 0);
+        assert_eq!(ppu.tick(LINE * 9) & u8::from(Interrupt::Lcdc), 0);
+        assert_ne!(ppu.tick(LINE) & u8::from(Interrupt::Lcdc), 0);
+    }
+
+    #[test]
+    fn stat_blocking() {
+        let mut ppu = setup();
+        ppu.write_byte(STAT_HBLANK_INTERRUPT | STAT_LYC_INTERRUPT, STAT);
+        ppu.write_byte(10, LYC);
+
+        // The LYC source goes high as the HBlank source of line 9 goes low,
+        // and stays high through the HBlank of line 10, so the line never
+        // drops and line 10 gets no interrupt at all
+        let interrupts = stat_interrupts(&mut ppu, 20);
+        for (line, interrupt) in interrupts.iter().enumerate() {
+            assert_eq!(*interrupt, line != 10, "line {}", line);
+        }
+    }
+
+    #[test]
+    fn enabling_active_source() {
+        let mut ppu = setup();
+        ppu.tick(LINE * 5 + 60);
+
+        // Selecting a source that's already active raises the line
+        ppu.write_byte(5, LYC);
+        ppu.write_byte(STAT_LYC_INTERRUPT, STAT);
+        assert_ne!(ppu.tick(1) & u8::from(Interrupt::Lcdc), 0);
+        assert_eq!(ppu.tick(1) & u8::from(Interrupt::Lcdc), 0);
+    }
+}